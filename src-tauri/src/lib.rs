@@ -11,7 +11,7 @@ use std::fs;
 use std::io::Write;
 use std::io;
 use std::path::PathBuf;
-use std::sync::{atomic::{AtomicUsize, Ordering}, Arc, Mutex, OnceLock, RwLock};
+use std::sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc, Mutex, OnceLock, RwLock};
 use tokio::sync::Notify;
 use tokio::sync::oneshot;
 use tauri::Emitter;
@@ -96,6 +96,13 @@ fn proxy_log_db_path() -> PathBuf {
         .join("proxy_logs.db")
 }
 
+fn refresh_stats_path() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".into());
+    PathBuf::from(home)
+        .join(".codex-manager")
+        .join("refresh_stats.json")
+}
+
 fn proxy_config_path() -> PathBuf {
     let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".into());
     PathBuf::from(home)
@@ -174,12 +181,104 @@ struct ProxyConfig {
     vector_api_key: Option<String>,
     #[serde(default)]
     vector_model: Option<String>,
+    #[serde(default)]
+    model_pricing: HashMap<String, ModelPricing>,
+    #[serde(default)]
+    extra_stripped_headers: Vec<String>,
+    #[serde(default)]
+    forward_headers_override: Vec<String>,
+    #[serde(default)]
+    max_response_bytes: Option<usize>,
+    #[serde(default)]
+    sse_heartbeat_secs: Option<u64>,
+    #[serde(default = "default_cache_models")]
+    cache_models: bool,
+    #[serde(default)]
+    forward_client_ip: bool,
+    #[serde(default)]
+    strip_response_headers: Vec<String>,
+    #[serde(default)]
+    idle_shutdown_secs: Option<u64>,
+    #[serde(default = "default_auto_refresh")]
+    auto_refresh: bool,
+    #[serde(default = "default_log_bodies")]
+    log_bodies: String,
+    #[serde(default)]
+    hedge_after_ms: Option<u64>,
+    #[serde(default)]
+    drop_dead_accounts: bool,
+    #[serde(default)]
+    allowed_origins: Vec<String>,
+    #[serde(default)]
+    client_profiles: HashMap<String, ClientProfile>,
+    #[serde(default)]
+    get_cache_secs: Option<u64>,
+    #[serde(default)]
+    echo_mode: bool,
+    #[serde(default)]
+    refresh_lead_secs: Option<u64>,
+    #[serde(default)]
+    import_label_template: Option<String>,
+    #[serde(default)]
+    stream_request_body: bool,
+    #[serde(default)]
+    pretty_log_bodies: bool,
+    #[serde(default)]
+    allowed_paths: Vec<String>,
+    #[serde(default = "default_selection_strategy")]
+    selection_strategy: String,
+    #[serde(default)]
+    connect_timeout_secs: Option<u64>,
+    #[serde(default)]
+    idempotency_enabled: bool,
+    #[serde(default)]
+    usage_warning_percent: Option<f64>,
+    #[serde(default)]
+    default_model: Option<String>,
+    #[serde(default)]
+    strict_session_affinity: bool,
+    #[serde(default)]
+    auto_reload_secs: Option<u64>,
+    #[serde(default)]
+    passthrough_response_headers: Vec<String>,
+}
+
+/// USD price per 1K tokens for a single model, used by `get_cost_report`.
+#[derive(Serialize, Deserialize, Clone, Default)]
+struct ModelPricing {
+    input_per_1k: f64,
+    output_per_1k: f64,
+}
+
+/// The `version` / `User-Agent` / `originator` headers sent upstream for an
+/// account. "default" is always available and matches the built-in constants.
+#[derive(Serialize, Deserialize, Clone)]
+struct ClientProfile {
+    version: String,
+    user_agent: String,
+    originator: String,
 }
 
 fn default_proxy_upstream_mode() -> String {
     "codex".to_string()
 }
 
+fn default_cache_models() -> bool {
+    true
+}
+
+fn default_auto_refresh() -> bool {
+    true
+}
+
+fn default_log_bodies() -> String {
+    "all".to_string()
+}
+
+fn default_selection_strategy() -> String {
+    "round_robin".to_string()
+}
+
 fn default_enable_exact_cache() -> bool {
     true
 }
@@ -221,6 +320,36 @@ impl Default for ProxyConfig {
             vector_api_base_url: None,
             vector_api_key: None,
             vector_model: None,
+            model_pricing: HashMap::new(),
+            extra_stripped_headers: Vec::new(),
+            forward_headers_override: Vec::new(),
+            max_response_bytes: None,
+            sse_heartbeat_secs: None,
+            cache_models: default_cache_models(),
+            forward_client_ip: false,
+            strip_response_headers: Vec::new(),
+            idle_shutdown_secs: None,
+            auto_refresh: default_auto_refresh(),
+            log_bodies: default_log_bodies(),
+            hedge_after_ms: None,
+            drop_dead_accounts: false,
+            allowed_origins: Vec::new(),
+            client_profiles: HashMap::new(),
+            get_cache_secs: None,
+            echo_mode: false,
+            refresh_lead_secs: None,
+            import_label_template: None,
+            stream_request_body: false,
+            pretty_log_bodies: false,
+            allowed_paths: Vec::new(),
+            selection_strategy: default_selection_strategy(),
+            connect_timeout_secs: None,
+            idempotency_enabled: false,
+            usage_warning_percent: None,
+            default_model: None,
+            strict_session_affinity: false,
+            auto_reload_secs: None,
+            passthrough_response_headers: Vec::new(),
         }
     }
 }
@@ -429,6 +558,48 @@ fn normalize_proxy_upstream_mode(value: &str) -> &'static str {
     }
 }
 
+fn normalize_log_bodies(value: &str) -> &'static str {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "errors_only" | "errors-only" | "errors" => "errors_only",
+        "none" | "off" => "none",
+        _ => "all",
+    }
+}
+
+/// "round_robin" (default) or "lru" (the Active account idle the longest).
+fn normalize_selection_strategy(value: &str) -> &'static str {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "lru" | "least_recently_used" | "least-recently-used" => "lru",
+        _ => "round_robin",
+    }
+}
+
+/// Whether a request body can be piped straight upstream under
+/// `stream_request_body`. Anything that has to read or rewrite the body (body
+/// logging, the exact cache, Anthropic conversion, the custom OpenAI upstream,
+/// echo mode, compressed bodies, `Idempotency-Key` replay) keeps the buffered
+/// path.
+fn can_stream_request_body(cfg: &ProxyConfig, method: &reqwest::Method, path: &str, headers: &axum::http::HeaderMap) -> bool {
+    cfg.stream_request_body
+        && matches!(*method, reqwest::Method::POST | reqwest::Method::PUT | reqwest::Method::PATCH)
+        && !path.starts_with("/v1/messages")
+        && !proxy_uses_custom_openai(cfg)
+        && !cfg.echo_mode
+        && !cfg.enable_exact_cache
+        && (!cfg.enable_logging || normalize_log_bodies(&cfg.log_bodies) == "none")
+        && !headers.contains_key(axum::http::header::CONTENT_ENCODING)
+        && !(cfg.idempotency_enabled && headers.contains_key("idempotency-key"))
+}
+
+/// Whether request/response bodies should be kept for a log row with `status`.
+fn should_log_bodies(cfg: &ProxyConfig, status: u16) -> bool {
+    match normalize_log_bodies(&cfg.log_bodies) {
+        "none" => false,
+        "errors_only" => !(200..400).contains(&status),
+        _ => true,
+    }
+}
+
 fn normalized_custom_base_url(value: Option<&String>) -> Option<String> {
     value
         .map(|v| v.trim().trim_end_matches('/').to_string())
@@ -500,6 +671,9 @@ struct ProxyLogDetail {
     response_headers: Option<String>,
     request_body: Option<String>,
     response_body: Option<String>,
+    // "text", or "base64" when the body wasn't valid UTF-8; None without a body
+    request_body_encoding: Option<String>,
+    response_body_encoding: Option<String>,
     input_tokens: Option<i64>,
     output_tokens: Option<i64>,
     cache_status: Option<String>,
@@ -508,6 +682,13 @@ struct ProxyLogDetail {
     cache_bypass_reason: Option<String>,
     local_cached_input_tokens: Option<i64>,
     provider_cached_input_tokens: Option<i64>,
+    request_id: Option<i64>,
+    // SSE responses only, written when the stream ends
+    event_count: Option<i64>,
+    response_bytes: Option<i64>,
+    // Set when the stored body was cut at MAX_LOG_BODY_BYTES
+    request_body_truncated: bool,
+    response_body_truncated: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -533,6 +714,26 @@ struct ProxyTokenStats {
     top_accounts: Vec<ProxyTokenStatsItem>,
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+struct CostReportItem {
+    name: String,
+    requests: i64,
+    input_tokens: i64,
+    output_tokens: i64,
+    cost: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct CostReport {
+    from_ts: Option<String>,
+    to_ts: Option<String>,
+    total_cost: f64,
+    accounts: Vec<CostReportItem>,
+    models: Vec<CostReportItem>,
+    // Tokens from rows whose model has no configured price; no cost attached.
+    unpriced: ProxyTokenStatsItem,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 struct AICacheOverview {
     window_hours: i64,
@@ -608,6 +809,9 @@ struct ProxyLogEntry {
     response_headers: Option<String>,
     request_body: Option<String>,
     response_body: Option<String>,
+    // Set from `truncate_body` when the stored body was cut at MAX_LOG_BODY_BYTES
+    request_body_truncated: bool,
+    response_body_truncated: bool,
     input_tokens: Option<i64>,
     output_tokens: Option<i64>,
     cache_status: Option<String>,
@@ -616,6 +820,7 @@ struct ProxyLogEntry {
     cache_bypass_reason: Option<String>,
     local_cached_input_tokens: Option<i64>,
     provider_cached_input_tokens: Option<i64>,
+    request_id: Option<u64>,
 }
 
 impl Default for ProxyLogEntry {
@@ -635,6 +840,8 @@ impl Default for ProxyLogEntry {
             response_headers: None,
             request_body: None,
             response_body: None,
+            request_body_truncated: false,
+            response_body_truncated: false,
             input_tokens: None,
             output_tokens: None,
             cache_status: None,
@@ -643,21 +850,47 @@ impl Default for ProxyLogEntry {
             cache_bypass_reason: None,
             local_cached_input_tokens: None,
             provider_cached_input_tokens: None,
+            request_id: None,
         }
     }
 }
 
-fn proxy_log_db() -> Result<Connection, String> {
+static PROXY_LOG_DB: OnceLock<Mutex<Connection>> = OnceLock::new();
+
+/// Shared log database connection. Serializing writers through one connection
+/// avoids SQLITE_BUSY between concurrent requests.
+fn proxy_log_db() -> Result<std::sync::MutexGuard<'static, Connection>, String> {
+    if let Some(db) = PROXY_LOG_DB.get() {
+        return Ok(db.lock().unwrap_or_else(|e| e.into_inner()));
+    }
     let path = proxy_log_db_path();
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent).map_err(|e| e.to_string())?;
     }
     let conn = Connection::open(path).map_err(|e| e.to_string())?;
     init_proxy_log_db(&conn)?;
-    Ok(conn)
+    seed_request_ids(&conn);
+    let db = PROXY_LOG_DB.get_or_init(|| Mutex::new(conn));
+    Ok(db.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// Move `PROXY_REQ_ID` past the highest request id already logged, so ids
+/// handed out after a restart (or a restore) don't repeat stored ones.
+fn seed_request_ids(conn: &Connection) {
+    let max: Option<i64> = conn
+        .query_row("SELECT MAX(request_id) FROM request_logs", [], |row| row.get(0))
+        .ok()
+        .flatten();
+    if let Some(max) = max.filter(|v| *v >= 0) {
+        PROXY_REQ_ID.fetch_max(max as usize + 1, Ordering::SeqCst);
+    }
 }
 
 fn init_proxy_log_db(conn: &Connection) -> Result<(), String> {
+    conn.busy_timeout(std::time::Duration::from_secs(5))
+        .map_err(|e| e.to_string())?;
+    conn.query_row("PRAGMA journal_mode=WAL", [], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?;
     conn.execute(
         "CREATE TABLE IF NOT EXISTS request_logs (            id INTEGER PRIMARY KEY AUTOINCREMENT,            timestamp TEXT NOT NULL,            method TEXT NOT NULL,            path TEXT NOT NULL,            request_url TEXT,            status INTEGER NOT NULL,            duration_ms INTEGER NOT NULL,            proxy_account_id TEXT NOT NULL,            account_id TEXT,            error TEXT,            request_headers TEXT,            response_headers TEXT,            request_body TEXT,            response_body TEXT,            model TEXT,            input_tokens INTEGER,            output_tokens INTEGER        )",
         [],
@@ -687,7 +920,30 @@ fn init_proxy_log_db(conn: &Connection) -> Result<(), String> {
         [],
     )
     .map_err(|e| e.to_string())?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS health_events (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            account_id TEXT NOT NULL,
+            from_state TEXT NOT NULL,
+            to_state TEXT NOT NULL,
+            reason TEXT NOT NULL,
+            status_code INTEGER,
+            timestamp TEXT NOT NULL
+        )",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
     ensure_log_columns(conn)?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_request_logs_request_id ON request_logs (request_id)",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_health_events_account ON health_events (account_id, id DESC)",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_request_logs_timestamp ON request_logs (id DESC)",
         [],
@@ -698,6 +954,11 @@ fn init_proxy_log_db(conn: &Connection) -> Result<(), String> {
         [],
     )
     .map_err(|e| e.to_string())?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_request_logs_duration ON request_logs (duration_ms DESC)",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_ai_cache_entries_expires_at ON ai_cache_entries (expires_at)",
         [],
@@ -738,6 +999,11 @@ fn ensure_log_columns(conn: &Connection) -> Result<(), String> {
         ("cache_bypass_reason", "TEXT"),
         ("local_cached_input_tokens", "INTEGER"),
         ("provider_cached_input_tokens", "INTEGER"),
+        ("request_id", "INTEGER"),
+        ("event_count", "INTEGER"),
+        ("response_bytes", "INTEGER"),
+        ("request_body_truncated", "INTEGER"),
+        ("response_body_truncated", "INTEGER"),
     ];
     for (name, ty) in required {
         if !cols.contains(name) {
@@ -772,6 +1038,22 @@ fn extract_provider_cached_input_tokens_from_text(text: Option<&String>) -> Opti
     Some(cached)
 }
 
+/// Record the `data:` event count and byte total of a finished SSE response
+/// on the newest log row for `request_id`.
+fn update_proxy_log_stream_metrics(request_id: u64, event_count: i64, response_bytes: i64) -> Result<(), String> {
+    if !proxy_config_snapshot().enable_logging {
+        return Ok(());
+    }
+    let conn = proxy_log_db()?;
+    conn.execute(
+        "UPDATE request_logs SET event_count = ?1, response_bytes = ?2
+         WHERE id = (SELECT id FROM request_logs WHERE request_id = ?3 ORDER BY id DESC LIMIT 1)",
+        params![event_count, response_bytes, request_id as i64],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
 fn insert_proxy_log(entry: &ProxyLogEntry) -> Result<(), String> {
     let cfg = proxy_config_snapshot();
     if !cfg.enable_logging {
@@ -806,7 +1088,11 @@ fn insert_proxy_log(entry: &ProxyLogEntry) -> Result<(), String> {
         && entry.status >= 200
         && entry.status < 300
     {
-        if let (Some(key), Some(response_body)) = (cache_key.as_ref(), entry.response_body.as_ref()) {
+        let cacheable_body = entry
+            .response_body
+            .as_ref()
+            .filter(|body| !body.starts_with(LOG_BODY_BASE64_PREFIX));
+        if let (Some(key), Some(response_body)) = (cache_key.as_ref(), cacheable_body) {
             let content_type = entry
                 .response_headers
                 .as_ref()
@@ -832,13 +1118,20 @@ fn insert_proxy_log(entry: &ProxyLogEntry) -> Result<(), String> {
             );
         }
     }
+    // Bodies are dropped only after the cache above has had its chance to store them
+    let keep_bodies = should_log_bodies(&cfg, entry.status);
+    let request_body = entry.request_body.as_ref().filter(|_| keep_bodies);
+    let response_body = entry.response_body.as_ref().filter(|_| keep_bodies);
+    let request_body_truncated = request_body.map(|_| entry.request_body_truncated as i64);
+    let response_body_truncated = response_body.map(|_| entry.response_body_truncated as i64);
     let conn = proxy_log_db()?;
     conn.execute(
         "INSERT INTO request_logs (
             timestamp, method, path, request_url, status, duration_ms, proxy_account_id, account_id, error,
             request_headers, response_headers, request_body, response_body, model, input_tokens, output_tokens,
-            cache_status, cache_key, cache_eligible, cache_bypass_reason, local_cached_input_tokens, provider_cached_input_tokens
-        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22)",
+            cache_status, cache_key, cache_eligible, cache_bypass_reason, local_cached_input_tokens, provider_cached_input_tokens,
+            request_id, request_body_truncated, response_body_truncated
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25)",
         params![
             entry.timestamp,
             entry.method,
@@ -851,8 +1144,8 @@ fn insert_proxy_log(entry: &ProxyLogEntry) -> Result<(), String> {
             entry.error,
             entry.request_headers,
             entry.response_headers,
-            entry.request_body,
-            entry.response_body,
+            request_body,
+            response_body,
             entry.model,
             entry.input_tokens,
             entry.output_tokens,
@@ -862,6 +1155,9 @@ fn insert_proxy_log(entry: &ProxyLogEntry) -> Result<(), String> {
             cache_bypass_reason,
             entry.local_cached_input_tokens,
             provider_cached_input_tokens,
+            entry.request_id.map(|v| v as i64),
+            request_body_truncated,
+            response_body_truncated,
         ],
     )
     .map_err(|e| e.to_string())?;
@@ -914,30 +1210,123 @@ fn headers_to_json_string(headers: Vec<(String, String)>) -> Option<String> {
 }
 
 const MAX_LOG_BODY_BYTES: usize = 64 * 1024;
+// Marks a logged body that wasn't valid UTF-8 and was stored as base64
+const LOG_BODY_BASE64_PREFIX: &str = "[codex-manager:base64]";
+// Appended by `truncate_body` to text bodies cut at MAX_LOG_BODY_BYTES
+const LOG_BODY_TRUNCATED_NOTE: &str = "\n...truncated ";
+
+/// Split a stored log body into its text and encoding ("text" or "base64").
+fn split_log_body(body: Option<String>) -> (Option<String>, Option<String>) {
+    match body {
+        Some(body) => match body.strip_prefix(LOG_BODY_BASE64_PREFIX) {
+            Some(encoded) => (Some(encoded.to_string()), Some("base64".to_string())),
+            None => (Some(body), Some("text".to_string())),
+        },
+        None => (None, None),
+    }
+}
+
+/// Render a body for the log, returning the text and whether it was cut at
+/// MAX_LOG_BODY_BYTES. With `pretty_log_bodies`, JSON that fits under the cap is
+/// re-indented first, unless `log_bodies` is "none" and the text is only kept
+/// for the cache.
+fn truncate_body(cfg: &ProxyConfig, bytes: &[u8]) -> (String, bool) {
+    let pretty = if cfg.pretty_log_bodies
+        && bytes.len() <= MAX_LOG_BODY_BYTES
+        && normalize_log_bodies(&cfg.log_bodies) != "none"
+    {
+        serde_json::from_slice::<Value>(bytes)
+            .ok()
+            .and_then(|value| serde_json::to_vec_pretty(&value).ok())
+    } else {
+        None
+    };
+    cap_body_text(pretty.as_deref().unwrap_or(bytes))
+}
 
-fn truncate_body(bytes: &[u8]) -> String {
+/// `bytes` as text cut at MAX_LOG_BODY_BYTES (base64 when not UTF-8), plus
+/// whether it was cut.
+fn cap_body_text(bytes: &[u8]) -> (String, bool) {
     if bytes.is_empty() {
-        return String::new();
+        return (String::new(), false);
     }
     let slice = if bytes.len() > MAX_LOG_BODY_BYTES {
         &bytes[..MAX_LOG_BODY_BYTES]
     } else {
         bytes
     };
-    let mut text = String::from_utf8_lossy(slice).to_string();
+    let mut text = match std::str::from_utf8(slice) {
+        Ok(text) => text.to_string(),
+        // The cut at MAX_LOG_BODY_BYTES may split a character; keep the valid prefix
+        Err(err) if err.error_len().is_none() => String::from_utf8_lossy(&slice[..err.valid_up_to()]).to_string(),
+        // Binary: keep the payload decodable, so no truncation note is appended
+        Err(_) => {
+            return (
+                format!("{LOG_BODY_BASE64_PREFIX}{}", base64::engine::general_purpose::STANDARD.encode(slice)),
+                bytes.len() > MAX_LOG_BODY_BYTES,
+            );
+        }
+    };
     if bytes.len() > MAX_LOG_BODY_BYTES {
-        text.push_str(&format!("
-...truncated {} bytes", bytes.len() - MAX_LOG_BODY_BYTES));
+        text.push_str(&format!("{LOG_BODY_TRUNCATED_NOTE}{} bytes", bytes.len() - MAX_LOG_BODY_BYTES));
     }
-    text
+    (text, bytes.len() > MAX_LOG_BODY_BYTES)
+}
+
+/// `truncate_body` for a log entry field; an empty body is stored as NULL.
+fn log_body_field(cfg: &ProxyConfig, bytes: &[u8]) -> (Option<String>, bool) {
+    if bytes.is_empty() {
+        return (None, false);
+    }
+    let (text, truncated) = truncate_body(cfg, bytes);
+    (Some(text), truncated)
 }
 
 fn extract_model(body: &[u8]) -> Option<String> {
+    // Tolerate a UTF-8 BOM; serde_json already skips surrounding whitespace
+    let body = body.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(body);
     if body.is_empty() {
         return None;
     }
     let value: Value = serde_json::from_slice(body).ok()?;
-    value.get("model")?.as_str().map(|s| s.to_string())
+    ["/model", "/input/model", "/request/model", "/body/model", "/params/model"]
+        .iter()
+        .find_map(|pointer| value.pointer(pointer).and_then(|v| v.as_str()))
+        .filter(|model| !model.trim().is_empty())
+        .map(|s| s.to_string())
+}
+
+/// Model name recorded for a request whose body names none. A model the client
+/// put in the URL (`?model=`, `/deployments/<name>/`, `/models/<name>`) or the
+/// `x-model` header wins; otherwise `default_model` applies, but only to
+/// body-carrying calls, never to GET/HEAD or the model listing.
+fn default_request_model(
+    cfg: &ProxyConfig,
+    method: &reqwest::Method,
+    path: &str,
+    headers: &axum::http::HeaderMap,
+) -> Option<String> {
+    let non_empty = |value: &str| Some(value.trim().to_string()).filter(|v| !v.is_empty());
+    if let Ok(url) = reqwest::Url::parse(&format!("http://localhost{path}")) {
+        if let Some(model) = url.query_pairs().find(|(key, _)| key == "model").and_then(|(_, v)| non_empty(v.as_ref())) {
+            return Some(model);
+        }
+        let segments: Vec<&str> = url.path_segments().map(|s| s.collect()).unwrap_or_default();
+        let named = segments
+            .windows(2)
+            .find(|pair| pair[0] == "deployments" || pair[0] == "models")
+            .and_then(|pair| non_empty(pair[1]));
+        if named.is_some() {
+            return named;
+        }
+    }
+    if let Some(model) = headers.get("x-model").and_then(|v| v.to_str().ok()).and_then(non_empty) {
+        return Some(model);
+    }
+    if !matches!(*method, reqwest::Method::POST | reqwest::Method::PUT | reqwest::Method::PATCH) || is_models_path(path) {
+        return None;
+    }
+    cfg.default_model.as_deref().and_then(non_empty)
 }
 
 fn canonicalize_json_value(value: &Value) -> Value {
@@ -1156,7 +1545,7 @@ fn store_local_cache_entry(
             status as i64,
             content_type,
             response_body,
-            truncate_body(response_body),
+            truncate_body(&cfg, response_body).0,
             input_tokens.unwrap_or(0),
             output_tokens.unwrap_or(0),
             input_tokens.unwrap_or(0),
@@ -1387,22 +1776,14 @@ fn count_codex_input_tokens(body: &Value) -> i64 {
 fn apply_usage_limit_policy(state: &Arc<ProxyState>, idx: usize, id: &str, until: std::time::Instant) {
     let cfg = proxy_config_snapshot();
     if cfg.disable_on_usage_limit {
-        {
-            let mut accounts_lock = state.accounts.write().unwrap();
-            if let Some(acc) = accounts_lock.get_mut(idx) {
-                acc.health = AccountHealth::Blocked;
-            }
-        }
+        transition_account_health(state, idx, AccountHealth::Blocked, "usage limit reached", None);
         if let Err(err) = update_proxy_enabled(id.to_string(), false) {
             log_proxy(&format!("usage-limit disable failed for {id}: {err}"));
         } else {
             log_proxy(&format!("usage-limit disabled account {id}"));
         }
     } else {
-        let mut accounts_lock = state.accounts.write().unwrap();
-        if let Some(acc) = accounts_lock.get_mut(idx) {
-            acc.health = AccountHealth::Cooldown(until);
-        }
+        transition_account_health(state, idx, AccountHealth::Cooldown(until), "usage limit reached", None);
     }
 }
 
@@ -1414,6 +1795,92 @@ struct MetaEntry {
     added_at: u64,
     #[serde(default = "default_proxy_enabled")]
     proxy_enabled: bool,
+    #[serde(default)]
+    organization: Option<String>,
+    #[serde(default)]
+    project: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    outbound_proxy: Option<String>,
+    // Unix ms of the last successful proxied request, written at most once a minute
+    #[serde(default)]
+    last_used_at: Option<u64>,
+    #[serde(default)]
+    client_profile: Option<String>,
+    // Never refresh this account's tokens (e.g. a shared refresh token rotated elsewhere)
+    #[serde(default)]
+    no_refresh: bool,
+    // Upstream base URL for this account instead of the global one
+    #[serde(default)]
+    base_url: Option<String>,
+}
+
+impl MetaEntry {
+    /// Defaults for an account first seen at `added_at` (unix ms): proxy
+    /// enabled, no label, tags or overrides.
+    fn new(added_at: u64) -> Self {
+        MetaEntry {
+            label: None,
+            added_at,
+            proxy_enabled: true,
+            organization: None,
+            project: None,
+            tags: Vec::new(),
+            outbound_proxy: None,
+            last_used_at: None,
+            client_profile: None,
+            no_refresh: false,
+            base_url: None,
+        }
+    }
+
+    /// Fold `other` (e.g. the entry of a renamed account) into this one: fields
+    /// already set here win, tags are unioned, `no_refresh` sticks if either has
+    /// it, and the earliest `added_at` / latest `last_used_at` are kept.
+    fn merge(&mut self, other: MetaEntry) {
+        if self.label.is_none() {
+            self.label = other.label;
+        }
+        if self.organization.is_none() {
+            self.organization = other.organization;
+        }
+        if self.project.is_none() {
+            self.project = other.project;
+        }
+        for tag in other.tags {
+            if !self.tags.contains(&tag) {
+                self.tags.push(tag);
+            }
+        }
+        if self.outbound_proxy.is_none() {
+            self.outbound_proxy = other.outbound_proxy;
+        }
+        if self.client_profile.is_none() {
+            self.client_profile = other.client_profile;
+        }
+        self.no_refresh |= other.no_refresh;
+        if self.base_url.is_none() {
+            self.base_url = other.base_url;
+        }
+        self.last_used_at = self.last_used_at.max(other.last_used_at);
+        self.added_at = self.added_at.min(other.added_at);
+    }
+
+    /// Copy the stored per-account settings onto an account parsed from auth.json.
+    fn apply_to(&self, account: &mut CodexAccount) {
+        account.label = self.label.clone();
+        account.added_at = self.added_at;
+        account.proxy_enabled = self.proxy_enabled;
+        account.organization = self.organization.clone();
+        account.project = self.project.clone();
+        account.tags = self.tags.clone();
+        account.outbound_proxy = self.outbound_proxy.clone();
+        account.last_used_at = self.last_used_at;
+        account.client_profile = self.client_profile.clone();
+        account.no_refresh = self.no_refresh;
+        account.base_url = self.base_url.clone();
+    }
 }
 
 fn default_proxy_enabled() -> bool {
@@ -1433,6 +1900,16 @@ pub struct CodexAccount {
     label: Option<String>,
     added_at: u64,
     proxy_enabled: bool,
+    organization: Option<String>,
+    project: Option<String>,
+    tags: Vec<String>,
+    // "jwt" | "opaque" | "none"; opaque tokens carry no expiry, so expires_at is 0
+    token_kind: String,
+    outbound_proxy: Option<String>,
+    last_used_at: Option<u64>,
+    client_profile: Option<String>,
+    no_refresh: bool,
+    base_url: Option<String>,
 }
 
 // ─── Global State for Proxy Gateway ──────────────────────────────────────────
@@ -1451,67 +1928,821 @@ struct ProxyAccount {
     access_token: String,
     refresh_token: Option<String>,
     health: AccountHealth,
+    org: AccountUpstreamIdentity,
+    // Consecutive plain-429 cooldowns; cleared by the next successful request
+    cooldown_streak: u32,
+    outbound_proxy: Option<String>,
+    // `exp` (unix seconds) of `access_token`, decoded once when the token is set
+    access_expires_at: Option<i64>,
+    // When this account was last handed a request; drives the "lru" strategy
+    last_used_at: Option<std::time::Instant>,
+    base_url: Option<String>,
+}
+
+/// Per-account upstream identity: optional `openai-organization` /
+/// `openai-project` values and the name of its client profile.
+#[derive(Clone, Default)]
+struct AccountUpstreamIdentity {
+    organization: Option<String>,
+    project: Option<String>,
+    client_profile: Option<String>,
 }
 
 struct ProxyState {
     client: reqwest::Client,
     accounts: Arc<RwLock<Vec<ProxyAccount>>>,
     req_counter: AtomicUsize,
+    models_cache: Mutex<Option<ModelsCacheEntry>>,
+    models_refreshing: AtomicBool,
+    // Per-run counters, reset whenever the proxy is (re)started
+    total_requests: AtomicUsize,
+    total_errors: AtomicUsize,
+    started_at: String,
+    last_request_at: Mutex<std::time::Instant>,
+    // Per-account clients for accounts with an `outbound_proxy`, keyed by account id
+    account_clients: Mutex<HashMap<String, (String, reqwest::Client)>>,
+    // Accounts retired by `drop_dead_accounts`; kept Blocked, and out of reloads, until restart
+    dropped_accounts: Mutex<Vec<String>>,
+    // Tag set by `reload_proxy_accounts_by_tag`; later reloads keep the pool to it
+    // until `reload_proxy_accounts` clears it
+    pool_tag: Mutex<Option<String>>,
+    // LRU of 2xx GET responses for `get_cache_secs`, least recently used first
+    get_cache: Mutex<std::collections::VecDeque<GetCacheEntry>>,
+    get_cache_hits: AtomicUsize,
+    get_cache_misses: AtomicUsize,
+    circuit: Mutex<CircuitBreaker>,
+    // Completed 2xx responses by `Idempotency-Key`, oldest first
+    idempotency_cache: Mutex<std::collections::VecDeque<GetCacheEntry>>,
+    // Keys whose first request is still upstream; duplicates wait on the Notify
+    idempotency_inflight: Mutex<HashMap<String, Arc<Notify>>>,
+    // Socket addresses `run_proxy_server` actually bound for this run
+    bindings: Mutex<Vec<String>>,
 }
 
-// Global proxy shutdown sender and live state
-static PROXY_SHUTDOWN: Mutex<Option<oneshot::Sender<()>>> = Mutex::new(None);
-static PROXY_PORT: Mutex<Option<u16>> = Mutex::new(None);
-// Shared live proxy state for status queries and hot-reload
-static PROXY_STATE: Mutex<Option<Arc<ProxyState>>> = Mutex::new(None);
+/// Proxy-wide breaker for upstream outages: after `CIRCUIT_FAILURE_THRESHOLD`
+/// consecutive transport errors or 5xx responses within `CIRCUIT_WINDOW_SECS`
+/// it opens and requests fail fast with 503. Once `CIRCUIT_COOLOFF_SECS` pass a
+/// single probe request is let through; its outcome closes or re-opens it.
+#[derive(Default)]
+struct CircuitBreaker {
+    failures: u32,
+    first_failure_at: Option<std::time::Instant>,
+    open_until: Option<std::time::Instant>,
+    probe_started: Option<std::time::Instant>,
+}
 
-// ─── Anthropic proxy globals ─────────────────────────────────────────────────
+const CIRCUIT_FAILURE_THRESHOLD: u32 = 10;
+const CIRCUIT_WINDOW_SECS: u64 = 60;
+const CIRCUIT_COOLOFF_SECS: u64 = 30;
 
+/// Ok when a request may go upstream; Err(retry-after seconds) while open.
+fn circuit_admit(state: &ProxyState) -> Result<(), u64> {
+    let mut breaker = state.circuit.lock().unwrap();
+    let Some(open_until) = breaker.open_until else {
+        return Ok(());
+    };
+    let now = std::time::Instant::now();
+    if now < open_until {
+        return Err(open_until.duration_since(now).as_secs().max(1));
+    }
+    // Half-open: one probe at a time; a probe that never reported is replaced
+    if breaker
+        .probe_started
+        .is_some_and(|t| t.elapsed().as_secs() < CIRCUIT_COOLOFF_SECS)
+    {
+        return Err(CIRCUIT_COOLOFF_SECS);
+    }
+    breaker.probe_started = Some(now);
+    Ok(())
+}
 
-fn anthropic_config_path() -> PathBuf {
-    let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".into());
-    PathBuf::from(home).join(".codex-manager").join("anthropic_keys.json")
+/// Record whether upstream answered; `ok` is false for transport errors and 5xx.
+fn circuit_record(state: &ProxyState, ok: bool) {
+    let mut breaker = state.circuit.lock().unwrap();
+    let now = std::time::Instant::now();
+    if ok {
+        if breaker.open_until.is_some() {
+            log_proxy("circuit breaker closed: upstream recovered");
+        }
+        *breaker = CircuitBreaker::default();
+        return;
+    }
+    if breaker.open_until.is_some() {
+        breaker.open_until = Some(now + std::time::Duration::from_secs(CIRCUIT_COOLOFF_SECS));
+        breaker.probe_started = None;
+        return;
+    }
+    match breaker.first_failure_at {
+        Some(first) if first.elapsed().as_secs() < CIRCUIT_WINDOW_SECS => breaker.failures += 1,
+        _ => {
+            breaker.first_failure_at = Some(now);
+            breaker.failures = 1;
+        }
+    }
+    if breaker.failures >= CIRCUIT_FAILURE_THRESHOLD {
+        log_proxy(&format!(
+            "circuit breaker open: {} consecutive upstream failures, pausing {}s",
+            breaker.failures, CIRCUIT_COOLOFF_SECS
+        ));
+        breaker.open_until = Some(now + std::time::Duration::from_secs(CIRCUIT_COOLOFF_SECS));
+    }
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-struct AnthropicKeyEntry {
-    id: String,
-    label: Option<String>,
-    // Either a raw sk-ant- API key or a Claude Code OAuth access_token
+fn circuit_snapshot(state: &ProxyState) -> Value {
+    let breaker = state.circuit.lock().unwrap();
+    let now = std::time::Instant::now();
+    let (status, retry_after) = match breaker.open_until {
+        None => ("closed", None),
+        Some(until) if now < until => ("open", Some(until.duration_since(now).as_secs())),
+        Some(_) => ("half_open", None),
+    };
+    serde_json::json!({
+        "state": status,
+        "failures": breaker.failures,
+        "retry_after_secs": retry_after,
+    })
+}
+
+/// Last successful upstream `/v1/models` response, keyed by the normalized path.
+struct ModelsCacheEntry {
     key: String,
-    added_at: u64,
+    content_type: String,
+    body: Bytes,
+    fetched_at: std::time::Instant,
 }
 
-fn load_anthropic_keys() -> Vec<AnthropicKeyEntry> {
-    let path = anthropic_config_path();
-    if !path.exists() {
-        return vec![];
+const MODELS_CACHE_TTL_SECS: u64 = 300;
+
+/// A cached upstream response to a GET, keyed by `get_cache_key`.
+struct GetCacheEntry {
+    key: String,
+    status: reqwest::StatusCode,
+    headers: reqwest::header::HeaderMap,
+    body: Bytes,
+    fetched_at: std::time::Instant,
+}
+
+const GET_CACHE_MAX_ENTRIES: usize = 64;
+
+/// Fingerprint of the proxy API key a client presented (`Authorization:
+/// Bearer` or `x-api-key`), so per-client caches don't store the key itself.
+/// Empty when the client sent neither.
+fn presented_client_key(headers: &axum::http::HeaderMap) -> String {
+    let presented = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .or_else(|| headers.get("x-api-key").and_then(|v| v.to_str().ok()))
+        .map(str::trim)
+        .unwrap_or("");
+    if presented.is_empty() {
+        return String::new();
     }
-    fs::read_to_string(&path)
-        .ok()
-        .and_then(|s| serde_json::from_str(&s).ok())
-        .unwrap_or_default()
+    Sha256::digest(presented.as_bytes()).iter().take(8).map(|b| format!("{b:02x}")).collect()
 }
 
-fn save_anthropic_keys(keys: &[AnthropicKeyEntry]) {
-    let path = anthropic_config_path();
-    if let Some(parent) = path.parent() {
-        let _ = fs::create_dir_all(parent);
+/// GET cache key: upstream path, the pool account that answers, and the client.
+fn get_cache_key(upstream_path: &str, account: &str, client_key: &str) -> String {
+    format!("GET {upstream_path} {account} {client_key}")
+}
+const GET_CACHE_MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Fresh cached response for `key`, moved to the most-recently-used end.
+fn get_cache_lookup(state: &ProxyState, key: &str, ttl_secs: u64) -> Option<(reqwest::StatusCode, reqwest::header::HeaderMap, Bytes)> {
+    let mut cache = state.get_cache.lock().unwrap();
+    cache.retain(|entry| entry.fetched_at.elapsed().as_secs() < ttl_secs);
+    let pos = cache.iter().position(|entry| entry.key == key)?;
+    let entry = cache.remove(pos)?;
+    let hit = (entry.status, entry.headers.clone(), entry.body.clone());
+    cache.push_back(entry);
+    Some(hit)
+}
+
+/// Cache a 2xx GET response unless it sets cookies or varies by credentials.
+fn get_cache_store(state: &ProxyState, key: &str, status: reqwest::StatusCode, headers: &reqwest::header::HeaderMap, body: &Bytes) {
+    if !status.is_success() || body.len() > GET_CACHE_MAX_BODY_BYTES || headers.contains_key(reqwest::header::SET_COOKIE) {
+        return;
     }
-    if let Ok(s) = serde_json::to_string_pretty(keys) {
-        let _ = fs::write(&path, s);
+    let varies_by_auth = headers.get_all(reqwest::header::VARY).iter().any(|v| {
+        v.to_str().map(|v| {
+            v.split(',').any(|name| {
+                let name = name.trim();
+                name == "*" || name.eq_ignore_ascii_case("authorization") || name.eq_ignore_ascii_case("cookie")
+                    || name.eq_ignore_ascii_case("chatgpt-account-id")
+            })
+        }).unwrap_or(true)
+    });
+    let no_store = headers
+        .get(reqwest::header::CACHE_CONTROL)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| {
+            let v = v.to_ascii_lowercase();
+            v.contains("no-store") || v.contains("private")
+        });
+    if varies_by_auth || no_store {
+        return;
+    }
+    let mut cache = state.get_cache.lock().unwrap();
+    cache.retain(|entry| entry.key != key);
+    while cache.len() >= GET_CACHE_MAX_ENTRIES {
+        cache.pop_front();
     }
+    cache.push_back(GetCacheEntry {
+        key: key.to_string(),
+        status,
+        headers: headers.clone(),
+        body: body.clone(),
+        fetched_at: std::time::Instant::now(),
+    });
 }
 
-#[tauri::command]
-fn list_anthropic_keys() -> Result<Vec<AnthropicKeyEntry>, String> {
-    Ok(load_anthropic_keys())
+const IDEMPOTENCY_TTL_SECS: u64 = 60;
+const IDEMPOTENCY_MAX_ENTRIES: usize = 128;
+const IDEMPOTENCY_WAIT_SECS: u64 = 120;
+
+/// Replay key for an `Idempotency-Key` request. The client's key fingerprint
+/// and a body hash are part of it, so another client reusing the key, or the
+/// same key sent with a different body, never gets the first response.
+fn idempotency_cache_key(method: &str, upstream_path: &str, client_key: &str, idempotency_key: &str, body: &[u8]) -> String {
+    let body_hash: String = Sha256::digest(body).iter().take(16).map(|b| format!("{b:02x}")).collect();
+    format!("{method} {upstream_path} {client_key} {body_hash} {idempotency_key}")
 }
 
-#[tauri::command]
-fn add_anthropic_key(label: Option<String>, key: String) -> Result<AnthropicKeyEntry, String> {
-    if key.trim().is_empty() {
-        return Err("Key cannot be empty".into());
+fn idempotency_lookup(state: &ProxyState, key: &str) -> Option<(reqwest::StatusCode, reqwest::header::HeaderMap, Bytes)> {
+    let mut cache = state.idempotency_cache.lock().unwrap();
+    cache.retain(|entry| entry.fetched_at.elapsed().as_secs() < IDEMPOTENCY_TTL_SECS);
+    cache
+        .iter()
+        .find(|entry| entry.key == key)
+        .map(|entry| (entry.status, entry.headers.clone(), entry.body.clone()))
+}
+
+fn idempotency_store(state: &ProxyState, key: &str, status: reqwest::StatusCode, headers: &reqwest::header::HeaderMap, body: &Bytes) {
+    if !status.is_success() {
+        return;
+    }
+    let mut cache = state.idempotency_cache.lock().unwrap();
+    cache.retain(|entry| entry.key != key);
+    while cache.len() >= IDEMPOTENCY_MAX_ENTRIES {
+        cache.pop_front();
+    }
+    cache.push_back(GetCacheEntry {
+        key: key.to_string(),
+        status,
+        headers: headers.clone(),
+        body: body.clone(),
+        fetched_at: std::time::Instant::now(),
+    });
+}
+
+/// Held by the first request for an `Idempotency-Key`. Dropping it (on any
+/// return path, after the response was cached) wakes waiting duplicates.
+struct IdempotencyGuard {
+    state: Arc<ProxyState>,
+    key: String,
+}
+
+impl Drop for IdempotencyGuard {
+    fn drop(&mut self) {
+        if let Some(notify) = self.state.idempotency_inflight.lock().unwrap().remove(&self.key) {
+            notify.notify_waiters();
+        }
+    }
+}
+
+/// For a request carrying `key`: a cached response if one exists (waiting for
+/// an in-flight first request to finish), otherwise a guard marking this
+/// request as the one that goes upstream. Duplicates whose leader produced
+/// nothing cacheable go upstream themselves, unguarded.
+async fn idempotency_begin(
+    state: &Arc<ProxyState>,
+    key: &str,
+) -> Result<Option<IdempotencyGuard>, (reqwest::StatusCode, reqwest::header::HeaderMap, Bytes)> {
+    if let Some(hit) = idempotency_lookup(state, key) {
+        return Err(hit);
+    }
+    let leader = {
+        let mut inflight = state.idempotency_inflight.lock().unwrap();
+        match inflight.get(key) {
+            Some(notify) => Some(notify.clone()),
+            None => {
+                inflight.insert(key.to_string(), Arc::new(Notify::new()));
+                None
+            }
+        }
+    };
+    let Some(notify) = leader else {
+        return Ok(Some(IdempotencyGuard { state: state.clone(), key: key.to_string() }));
+    };
+    // `notified()` sees `notify_waiters` from creation on, so re-check after creating it
+    let notified = notify.notified();
+    let still_running = state.idempotency_inflight.lock().unwrap().contains_key(key);
+    if still_running {
+        let _ = tokio::time::timeout(std::time::Duration::from_secs(IDEMPOTENCY_WAIT_SECS), notified).await;
+    }
+    match idempotency_lookup(state, key) {
+        Some(hit) => Err(hit),
+        None => Ok(None),
+    }
+}
+
+fn is_models_path(path: &str) -> bool {
+    path == "/v1/models" || path.starts_with("/v1/models?")
+}
+
+fn store_models_cache(state: &ProxyState, key: &str, headers: &reqwest::header::HeaderMap, body: &Bytes) {
+    let content_type = headers
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("application/json")
+        .to_string();
+    *state.models_cache.lock().unwrap() = Some(ModelsCacheEntry {
+        key: key.to_string(),
+        content_type,
+        body: body.clone(),
+        fetched_at: std::time::Instant::now(),
+    });
+}
+
+/// Refetch `/v1/models` with the first active account and update the cache.
+fn spawn_models_cache_refresh(state: Arc<ProxyState>, path: String) {
+    if state.models_refreshing.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tokio::spawn(async move {
+        let account = {
+            let accounts_lock = state.accounts.read().unwrap();
+            accounts_lock
+                .iter()
+                .enumerate()
+                .find(|(_, a)| a.health == AccountHealth::Active)
+                .map(|(idx, a)| (idx, a.access_token.clone(), a.account_id.clone(), a.org.clone()))
+        };
+        if let Some((idx, token, account_id, org)) = account {
+            let mut headers = reqwest::header::HeaderMap::new();
+            let incoming_headers = axum::http::HeaderMap::new();
+            apply_upstream_headers(&mut headers, &token, account_id.as_deref(), Some(&org), &incoming_headers, false, false);
+            match account_client(&state, idx).get(build_upstream_url(&path)).headers(headers).send().await {
+                Ok(resp) if resp.status().is_success() => {
+                    let resp_headers = resp.headers().clone();
+                    if let Ok(body) = read_capped_bytes(resp).await {
+                        store_models_cache(&state, &path, &resp_headers, &body);
+                    }
+                }
+                Ok(resp) => log_proxy(&format!("models cache refresh failed: HTTP {}", resp.status().as_u16())),
+                Err(e) => log_proxy(&format!("models cache refresh failed: {e}")),
+            }
+        }
+        state.models_refreshing.store(false, Ordering::SeqCst);
+    });
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct HealthEvent {
+    id: i64,
+    account_id: String,
+    from_state: String,
+    to_state: String,
+    reason: String,
+    status_code: Option<u16>,
+    timestamp: String,
+}
+
+fn health_state_label(health: &AccountHealth) -> &'static str {
+    match health {
+        AccountHealth::Active => "active",
+        AccountHealth::Cooldown(_) => "cooldown",
+        AccountHealth::Blocked => "blocked",
+    }
+}
+
+fn record_health_event(
+    account_id: &str,
+    from: &AccountHealth,
+    to: &AccountHealth,
+    reason: &str,
+    status_code: Option<u16>,
+) {
+    let (from_state, to_state) = (health_state_label(from), health_state_label(to));
+    if from_state == to_state {
+        return;
+    }
+    insert_health_event(account_id, from_state, to_state, reason, status_code);
+}
+
+fn insert_health_event(account_id: &str, from_state: &str, to_state: &str, reason: &str, status_code: Option<u16>) {
+    let result = proxy_log_db().and_then(|conn| {
+        conn.execute(
+            "INSERT INTO health_events (account_id, from_state, to_state, reason, status_code, timestamp)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                account_id,
+                from_state,
+                to_state,
+                reason,
+                status_code.map(|v| v as i64),
+                chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            ],
+        )
+        .map_err(|e| e.to_string())
+    });
+    if let Err(err) = result {
+        log_proxy(&format!("health event write failed for {account_id}: {err}"));
+    }
+}
+
+/// Set the health of the pool account at `idx` and record the transition, if any.
+fn transition_account_health(
+    state: &ProxyState,
+    idx: usize,
+    to: AccountHealth,
+    reason: &str,
+    status_code: Option<u16>,
+) {
+    let change = {
+        let mut accounts_lock = state.accounts.write().unwrap();
+        accounts_lock.get_mut(idx).map(|acc| {
+            let from = std::mem::replace(&mut acc.health, to.clone());
+            (acc.id.clone(), from)
+        })
+    };
+    if let Some((id, from)) = change {
+        record_health_event(&id, &from, &to, reason, status_code);
+    }
+}
+
+/// Move accounts whose cooldown has elapsed back to `Active`.
+/// Returns the revived ids so callers can record events once the lock is released.
+fn revive_elapsed_cooldowns(accounts: &mut [ProxyAccount], now: std::time::Instant) -> Vec<String> {
+    let mut revived = Vec::new();
+    for acc in accounts.iter_mut() {
+        if let AccountHealth::Cooldown(until) = &acc.health {
+            if now >= *until {
+                acc.health = AccountHealth::Active;
+                revived.push(acc.id.clone());
+            }
+        }
+    }
+    revived
+}
+
+type PickedAccount = (String, Option<String>, usize, String, Option<String>, AccountUpstreamIdentity);
+
+/// Next Active account per `selection_strategy` as (token, account_id, idx,
+/// id, refresh_token, org), reviving elapsed cooldowns first. Also returns the
+/// pool size so callers can tell an empty pool from an all-unavailable one.
+fn pick_account(state: &ProxyState) -> (usize, Option<PickedAccount>) {
+    let lru = normalize_selection_strategy(&proxy_config_snapshot().selection_strategy) == "lru";
+    let (pool_size, found, revived) = {
+        let now = std::time::Instant::now();
+        let mut accounts_lock = state.accounts.write().unwrap();
+        let pool_size = accounts_lock.len();
+        if pool_size == 0 {
+            return (0, None);
+        }
+
+        // Revive any accounts whose cooldown has elapsed
+        let revived = revive_elapsed_cooldowns(&mut accounts_lock, now);
+
+        let chosen = if lru {
+            // Never-used accounts (None) sort first
+            (0..pool_size)
+                .filter(|idx| accounts_lock[*idx].health == AccountHealth::Active)
+                .min_by_key(|idx| accounts_lock[*idx].last_used_at)
+        } else {
+            let start_count = state.req_counter.fetch_add(1, Ordering::SeqCst);
+            (0..pool_size)
+                .map(|i| (start_count + i) % pool_size)
+                .find(|idx| accounts_lock[*idx].health == AccountHealth::Active)
+        };
+        let found = chosen.map(|idx| {
+            let acc = &mut accounts_lock[idx];
+            acc.last_used_at = Some(now);
+            (
+                acc.access_token.clone(),
+                acc.account_id.clone(),
+                idx,
+                acc.id.clone(),
+                acc.refresh_token.clone(),
+                acc.org.clone(),
+            )
+        });
+        (pool_size, found, revived)
+    };
+    record_revived_accounts(&revived);
+    (pool_size, found)
+}
+
+/// Bump the account's cooldown streak and return when its cooldown should end:
+/// `COOLDOWN_SECS * 2^(streak-1)`, capped at `COOLDOWN_MAX_SECS`.
+fn backoff_cooldown_until(state: &ProxyState, idx: usize) -> std::time::Instant {
+    let streak = {
+        let mut accounts_lock = state.accounts.write().unwrap();
+        match accounts_lock.get_mut(idx) {
+            Some(acc) => {
+                acc.cooldown_streak = acc.cooldown_streak.saturating_add(1);
+                acc.cooldown_streak
+            }
+            None => 1,
+        }
+    };
+    let factor = 1u64.checked_shl(streak.saturating_sub(1)).unwrap_or(u64::MAX);
+    let secs = COOLDOWN_SECS.saturating_mul(factor).min(COOLDOWN_MAX_SECS);
+    std::time::Instant::now() + std::time::Duration::from_secs(secs)
+}
+
+fn reset_cooldown_streak(state: &ProxyState, idx: usize) {
+    let mut accounts_lock = state.accounts.write().unwrap();
+    if let Some(acc) = accounts_lock.get_mut(idx) {
+        acc.cooldown_streak = 0;
+    }
+}
+
+const LAST_USED_WRITE_INTERVAL_SECS: u64 = 60;
+
+// `last_used_at` (unix ms) per account id, waiting for `flush_last_used`
+static LAST_USED_PENDING: OnceLock<Mutex<HashMap<String, u64>>> = OnceLock::new();
+
+/// Note that the pool account at `idx` just served a request. Only an
+/// in-memory map is touched here; `flush_last_used` persists it.
+fn note_account_used(state: &ProxyState, idx: usize) {
+    let id = match state.accounts.read().unwrap().get(idx) {
+        Some(acc) => acc.id.clone(),
+        None => return,
+    };
+    LAST_USED_PENDING
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap()
+        .insert(id, now_unix_millis());
+}
+
+/// Write pending `last_used_at` values to accounts_meta.json in one pass.
+/// Called only from the proxy's last-used writer task, so these writes never
+/// race each other. Accounts deleted in the meantime are skipped.
+fn flush_last_used() {
+    let pending = match LAST_USED_PENDING.get() {
+        Some(pending) => std::mem::take(&mut *pending.lock().unwrap()),
+        None => return,
+    };
+    if pending.is_empty() {
+        return;
+    }
+    let mut meta = read_meta();
+    for (id, used_at) in pending {
+        if !accounts_dir().join(&id).join("auth.json").exists() {
+            continue;
+        }
+        let entry = meta.entry(id).or_insert_with(|| MetaEntry::new(used_at));
+        entry.last_used_at = Some(entry.last_used_at.map_or(used_at, |v| v.max(used_at)));
+    }
+    write_meta(&meta);
+}
+
+/// Retire `id` for the rest of this proxy run. It stays in the pool, Blocked,
+/// so indices held by in-flight requests keep pointing at the right account;
+/// reloads leave it out and `reset_proxy_rotation` won't revive it. The
+/// account on disk is untouched.
+fn drop_pool_account(state: &ProxyState, id: &str) {
+    let from = {
+        let mut accounts_lock = state.accounts.write().unwrap();
+        accounts_lock
+            .iter_mut()
+            .find(|a| a.id == id)
+            .map(|acc| std::mem::replace(&mut acc.health, AccountHealth::Blocked))
+    };
+    let newly_dropped = {
+        let mut dropped = state.dropped_accounts.lock().unwrap();
+        let newly_dropped = !dropped.iter().any(|d| d == id);
+        if newly_dropped {
+            dropped.push(id.to_string());
+        }
+        newly_dropped
+    };
+    if let (Some(from), true) = (from, newly_dropped) {
+        insert_health_event(id, health_state_label(&from), "dropped", "refresh token rejected", Some(401));
+    }
+}
+
+/// `load_proxy_accounts` minus anything dropped during this run.
+fn without_dropped_accounts(state: &ProxyState, accounts: Vec<ProxyAccount>) -> Vec<ProxyAccount> {
+    let dropped = state.dropped_accounts.lock().unwrap();
+    accounts.into_iter().filter(|a| !dropped.contains(&a.id)).collect()
+}
+
+/// Sorted ids of the account dirs holding an auth.json; `auto_reload_secs`
+/// compares successive listings to notice accounts added or removed on disk.
+fn account_dir_listing() -> Vec<String> {
+    let mut ids: Vec<String> = fs::read_dir(accounts_dir())
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|entry| entry.path().join("auth.json").exists())
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    ids.sort();
+    ids
+}
+
+/// Only the accounts carrying `tag`; errors when none do, so the caller keeps
+/// its current pool.
+fn accounts_tagged(accounts: Vec<ProxyAccount>, tag: &str) -> Result<Vec<ProxyAccount>, String> {
+    let tagged = account_ids_with_tag(tag);
+    let accounts: Vec<ProxyAccount> = accounts.into_iter().filter(|acc| tagged.contains(&acc.id)).collect();
+    if accounts.is_empty() {
+        return Err(format!("No enabled accounts tagged {}", tag.trim()));
+    }
+    Ok(accounts)
+}
+
+/// Replace the pool with the accounts on disk, carrying health, backoff and
+/// last use over for accounts that were already in it. A pool narrowed by
+/// `reload_proxy_accounts_by_tag` stays narrowed to that tag.
+fn reload_pool_preserving_health(state: &ProxyState) -> Result<usize, String> {
+    let mut new_accounts = without_dropped_accounts(state, load_proxy_accounts()?);
+    let pool_tag = state.pool_tag.lock().unwrap().clone();
+    if let Some(tag) = pool_tag {
+        new_accounts = accounts_tagged(new_accounts, &tag)?;
+    }
+    let count = new_accounts.len();
+    let mut accounts_lock = state.accounts.write().unwrap();
+    for acc in new_accounts.iter_mut() {
+        if let Some(old) = accounts_lock.iter().find(|old| old.id == acc.id) {
+            acc.health = old.health.clone();
+            acc.cooldown_streak = old.cooldown_streak;
+            acc.last_used_at = old.last_used_at;
+        }
+    }
+    if accounts_lock.len() != count {
+        state.req_counter.store(round_robin_seed(count), Ordering::SeqCst);
+    }
+    *accounts_lock = new_accounts;
+    Ok(count)
+}
+
+/// HTTP client for the pool account at `idx`: a cached client routed through
+/// the account's `outbound_proxy` when one is set, otherwise the shared client.
+fn account_client(state: &ProxyState, idx: usize) -> reqwest::Client {
+    let (id, proxy_url) = {
+        let accounts_lock = state.accounts.read().unwrap();
+        match accounts_lock.get(idx) {
+            Some(acc) => (acc.id.clone(), acc.outbound_proxy.clone()),
+            None => return state.client.clone(),
+        }
+    };
+    let Some(proxy_url) = proxy_url else {
+        return state.client.clone();
+    };
+    let mut clients = state.account_clients.lock().unwrap();
+    if let Some((url, client)) = clients.get(&id) {
+        if *url == proxy_url {
+            return client.clone();
+        }
+    }
+    let built = reqwest::Proxy::all(&proxy_url).and_then(|proxy| {
+        reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(120))
+            .connect_timeout(upstream_connect_timeout())
+            .proxy(proxy)
+            .build()
+    });
+    match built {
+        Ok(client) => {
+            clients.insert(id, (proxy_url, client.clone()));
+            client
+        }
+        Err(err) => {
+            log_proxy(&format!("outbound proxy for {id} unusable, using shared client: {err}"));
+            state.client.clone()
+        }
+    }
+}
+
+/// Seconds until the soonest cooldown in the pool ends, rounded up and clamped
+/// to `1..=COOLDOWN_MAX_SECS`. With no account cooling down (all Blocked), 60.
+fn pool_retry_after_secs(state: &ProxyState) -> u64 {
+    let now = std::time::Instant::now();
+    let accounts_lock = state.accounts.read().unwrap();
+    let soonest = accounts_lock
+        .iter()
+        .filter_map(|acc| match &acc.health {
+            AccountHealth::Cooldown(until) => Some(until.saturating_duration_since(now)),
+            _ => None,
+        })
+        .min();
+    match soonest {
+        Some(wait) => {
+            let secs = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+            secs.clamp(1, COOLDOWN_MAX_SECS)
+        }
+        None => 60,
+    }
+}
+
+/// A second `Active` account (never `exclude`) for a hedged request, in
+/// round-robin order. Does not advance `req_counter`.
+fn pick_hedge_account(
+    state: &ProxyState,
+    exclude: usize,
+) -> Option<PickedAccount> {
+    let accounts_lock = state.accounts.read().unwrap();
+    let pool_size = accounts_lock.len();
+    let start = state.req_counter.load(Ordering::SeqCst);
+    (0..pool_size)
+        .map(|i| (start + i) % pool_size)
+        .find(|idx| *idx != exclude && accounts_lock[*idx].health == AccountHealth::Active)
+        .map(|idx| {
+            let acc = &accounts_lock[idx];
+            (
+                acc.access_token.clone(),
+                acc.account_id.clone(),
+                idx,
+                acc.id.clone(),
+                acc.refresh_token.clone(),
+                acc.org.clone(),
+            )
+        })
+}
+
+/// Hedging re-sends the same body to a second account, so only requests
+/// without server-side effects qualify: GET/HEAD, or POSTs that explicitly set
+/// `"store": false`. The Responses API stores by default, so a POST without the
+/// field is not hedged.
+fn is_hedgeable_request(method: &reqwest::Method, body: &[u8]) -> bool {
+    if *method == reqwest::Method::GET || *method == reqwest::Method::HEAD {
+        return true;
+    }
+    if *method != reqwest::Method::POST {
+        return false;
+    }
+    serde_json::from_slice::<Value>(body)
+        .ok()
+        .and_then(|v| v.get("store").and_then(|s| s.as_bool()))
+        == Some(false)
+}
+
+fn record_revived_accounts(ids: &[String]) {
+    let from = AccountHealth::Cooldown(std::time::Instant::now());
+    for id in ids {
+        record_health_event(id, &from, &AccountHealth::Active, "cooldown elapsed", None);
+    }
+}
+
+// Global proxy shutdown sender and live state
+static PROXY_SHUTDOWN: Mutex<Option<oneshot::Sender<()>>> = Mutex::new(None);
+static PROXY_PORT: Mutex<Option<u16>> = Mutex::new(None);
+// Shared live proxy state for status queries and hot-reload
+static PROXY_STATE: Mutex<Option<Arc<ProxyState>>> = Mutex::new(None);
+
+// ─── Anthropic proxy globals ─────────────────────────────────────────────────
+
+
+fn anthropic_config_path() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".into());
+    PathBuf::from(home).join(".codex-manager").join("anthropic_keys.json")
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct AnthropicKeyEntry {
+    id: String,
+    label: Option<String>,
+    // Either a raw sk-ant- API key or a Claude Code OAuth access_token
+    key: String,
+    added_at: u64,
+}
+
+fn load_anthropic_keys() -> Vec<AnthropicKeyEntry> {
+    let path = anthropic_config_path();
+    if !path.exists() {
+        return vec![];
+    }
+    fs::read_to_string(&path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn save_anthropic_keys(keys: &[AnthropicKeyEntry]) {
+    let path = anthropic_config_path();
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Ok(s) = serde_json::to_string_pretty(keys) {
+        let _ = fs::write(&path, s);
+    }
+}
+
+#[tauri::command]
+fn list_anthropic_keys() -> Result<Vec<AnthropicKeyEntry>, String> {
+    Ok(load_anthropic_keys())
+}
+
+#[tauri::command]
+fn add_anthropic_key(label: Option<String>, key: String) -> Result<AnthropicKeyEntry, String> {
+    if key.trim().is_empty() {
+        return Err("Key cannot be empty".into());
     }
     let mut keys = load_anthropic_keys();
     let entry = AnthropicKeyEntry {
@@ -2090,11 +3321,13 @@ fn extract_responses_usage(value: &Value) -> (i64, i64, i64) {
     (adjusted_input, output_tokens, cached_tokens)
 }
 
+static SYNTH_RESPONSE_SEQ: AtomicUsize = AtomicUsize::new(1);
+
 fn synth_response_id(prefix: &str) -> String {
     format!(
         "{prefix}_{:x}_{}",
         chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default(),
-        PROXY_REQ_ID.fetch_add(1, Ordering::SeqCst)
+        SYNTH_RESPONSE_SEQ.fetch_add(1, Ordering::SeqCst)
     )
 }
 
@@ -2369,11 +3602,11 @@ fn parse_tool_arguments_json(args: &str) -> Result<Value, String> {
     let repaired = repair_tool_arguments_json(args).ok_or_else(|| {
         format!(
             "invalid tool arguments JSON: raw={}",
-            truncate_body(args.trim().as_bytes())
+            cap_body_text(args.trim().as_bytes()).0
         )
     })?;
     serde_json::from_str::<Value>(&repaired)
-        .map_err(|e| format!("invalid tool arguments JSON: {e}; raw={}", truncate_body(repaired.as_bytes())))
+        .map_err(|e| format!("invalid tool arguments JSON: {e}; raw={}", cap_body_text(repaired.as_bytes()).0))
 }
 
 fn build_local_models_response(models: &[String]) -> Bytes {
@@ -2647,9 +3880,10 @@ fn convert_responses_request_to_chat_completions(
 }
 
 fn convert_chat_completions_non_stream_to_responses(
+    cfg: &ProxyConfig,
     request_json: &Value,
     body_bytes: &[u8],
-) -> Result<(Bytes, Option<i64>, Option<i64>, Option<String>), String> {
+) -> Result<(Bytes, Option<i64>, Option<i64>, Option<String>, bool), String> {
     let root: Value = serde_json::from_slice(body_bytes).map_err(|e| e.to_string())?;
     let response_id = root
         .get("id")
@@ -2761,8 +3995,8 @@ fn convert_chat_completions_non_stream_to_responses(
     }
 
     let bytes = serde_json::to_vec(&response).map_err(|e| e.to_string())?;
-    let body_text = Some(truncate_body(&bytes));
-    Ok((Bytes::from(bytes), input_tokens, output_tokens, body_text))
+    let (body_text, body_truncated) = truncate_body(cfg, &bytes);
+    Ok((Bytes::from(bytes), input_tokens, output_tokens, Some(body_text), body_truncated))
 }
 
 fn convert_codex_non_stream_to_claude(
@@ -2914,18 +4148,15 @@ fn convert_codex_response_bytes_to_claude(
 }
 
 fn build_claude_response_body(
+    cfg: &ProxyConfig,
     bytes: &Bytes,
     reverse_tool_map: &HashMap<String, String>,
     request_model: &str,
-) -> Result<(Bytes, Option<i64>, Option<i64>, Option<String>), String> {
+) -> Result<(Bytes, Option<i64>, Option<i64>, Option<String>, bool), String> {
     let (converted, input_tokens, output_tokens) =
         convert_codex_response_bytes_to_claude(bytes, reverse_tool_map, request_model)?;
-    let response_body_text = if converted.is_empty() {
-        None
-    } else {
-        Some(truncate_body(&converted))
-    };
-    Ok((converted, input_tokens, output_tokens, response_body_text))
+    let (response_body_text, response_body_truncated) = log_body_field(cfg, &converted);
+    Ok((converted, input_tokens, output_tokens, response_body_text, response_body_truncated))
 }
 
 struct CodexToClaudeStreamState {
@@ -3158,6 +4389,93 @@ fn process_codex_sse_chunk(state: &mut CodexToClaudeStreamState, chunk: &str) {
     }
 }
 
+const ECHO_MAX_TEXT_BYTES: usize = 4096;
+
+/// Synthetic Responses-API body for `echo_mode`: the request body comes back as
+/// the assistant text, with a rough bytes/4 token estimate as `usage`.
+fn echo_mode_response(request_id: usize, model: &str, request_body: &[u8]) -> Value {
+    let mut text = String::from_utf8_lossy(request_body).to_string();
+    if text.len() > ECHO_MAX_TEXT_BYTES {
+        let mut cut = ECHO_MAX_TEXT_BYTES;
+        while !text.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        text.truncate(cut);
+    }
+    let input_tokens = (request_body.len() as i64 + 3) / 4;
+    let output_tokens = (text.len() as i64 + 3) / 4;
+    serde_json::json!({
+        "id": format!("resp_echo_{request_id}"),
+        "object": "response",
+        "created_at": chrono::Utc::now().timestamp(),
+        "status": "completed",
+        "model": model,
+        "output": [{
+            "type": "message",
+            "id": format!("msg_echo_{request_id}"),
+            "status": "completed",
+            "role": "assistant",
+            "content": [{ "type": "output_text", "text": text, "annotations": [] }],
+        }],
+        "usage": {
+            "input_tokens": input_tokens,
+            "output_tokens": output_tokens,
+            "total_tokens": input_tokens + output_tokens,
+        },
+    })
+}
+
+fn sse_event(event: &str, data: &Value) -> String {
+    format!("event: {event}\ndata: {data}\n\n")
+}
+
+/// Replay an `echo_mode_response` as the Responses-API event stream.
+fn echo_mode_responses_sse(response: &Value) -> String {
+    let text = response["output"][0]["content"][0]["text"].as_str().unwrap_or_default();
+    let mut created = response.clone();
+    created["status"] = Value::from("in_progress");
+    created["output"] = Value::Array(Vec::new());
+    let mut out = sse_event("response.created", &serde_json::json!({ "type": "response.created", "response": created }));
+    out.push_str(&sse_event(
+        "response.output_text.delta",
+        &serde_json::json!({ "type": "response.output_text.delta", "output_index": 0, "content_index": 0, "delta": text }),
+    ));
+    out.push_str(&sse_event(
+        "response.completed",
+        &serde_json::json!({ "type": "response.completed", "response": response }),
+    ));
+    out
+}
+
+/// Replay a Claude message body as the Anthropic Messages event stream.
+fn echo_mode_claude_sse(message: &Value) -> String {
+    let text = message["content"][0]["text"].as_str().unwrap_or_default();
+    let mut start = message.clone();
+    start["content"] = Value::Array(Vec::new());
+    start["stop_reason"] = Value::Null;
+    start["usage"]["output_tokens"] = Value::from(0);
+    let mut out = sse_event("message_start", &serde_json::json!({ "type": "message_start", "message": start }));
+    out.push_str(&sse_event(
+        "content_block_start",
+        &serde_json::json!({ "type": "content_block_start", "index": 0, "content_block": { "type": "text", "text": "" } }),
+    ));
+    out.push_str(&sse_event(
+        "content_block_delta",
+        &serde_json::json!({ "type": "content_block_delta", "index": 0, "delta": { "type": "text_delta", "text": text } }),
+    ));
+    out.push_str(&sse_event("content_block_stop", &serde_json::json!({ "type": "content_block_stop", "index": 0 })));
+    out.push_str(&sse_event(
+        "message_delta",
+        &serde_json::json!({
+            "type": "message_delta",
+            "delta": { "stop_reason": message["stop_reason"], "stop_sequence": null },
+            "usage": { "output_tokens": message["usage"]["output_tokens"] },
+        }),
+    ));
+    out.push_str(&sse_event("message_stop", &serde_json::json!({ "type": "message_stop" })));
+    out
+}
+
 async fn build_anthropic_stream_response(
     upstream_resp: reqwest::Response,
     reverse_tool_map: HashMap<String, String>,
@@ -3182,7 +4500,7 @@ async fn build_anthropic_stream_response(
         log_entry: Some(log_entry),
     };
 
-    let mut upstream_stream = upstream_resp.bytes_stream();
+    let mut upstream_stream = capped_bytes_stream(upstream_resp);
     let stream = futures_util::stream::unfold((upstream_stream, state), |(mut upstream_stream, mut state)| async move {
         loop {
             if let Some(bytes) = state.pending.pop_front() {
@@ -4041,7 +5359,7 @@ async fn build_custom_openai_stream_response(
         total_tokens: None,
     };
 
-    let mut upstream_stream = upstream_resp.bytes_stream();
+    let mut upstream_stream = capped_bytes_stream(upstream_resp);
     let stream = futures_util::stream::unfold((upstream_stream, state), |(mut upstream_stream, mut state)| async move {
         loop {
             if let Some(bytes) = state.pending.pop_front() {
@@ -4407,7 +5725,7 @@ async fn build_openai_chat_to_claude_stream_response(
         cached_tokens: None,
     };
 
-    let mut upstream_stream = upstream_resp.bytes_stream();
+    let mut upstream_stream = capped_bytes_stream(upstream_resp);
     let stream = futures_util::stream::unfold((upstream_stream, state), |(mut upstream_stream, mut state)| async move {
         loop {
             if let Some(bytes) = state.pending.pop_front() {
@@ -4461,16 +5779,43 @@ async fn build_openai_chat_to_claude_stream_response(
 
 // ─── JWT / auth helpers ───────────────────────────────────────────────────────
 
-fn decode_jwt(token: &str) -> Value {
+fn try_decode_jwt(token: &str) -> Option<Value> {
     let parts: Vec<&str> = token.split('.').collect();
     if parts.len() < 2 {
-        return Value::Object(Default::default());
+        return None;
     }
     URL_SAFE_NO_PAD
-        .decode(parts[1])
+        .decode(parts[1].trim_end_matches('='))
         .ok()
-        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
-        .unwrap_or_else(|| Value::Object(Default::default()))
+        .and_then(|bytes| serde_json::from_slice::<Value>(&bytes).ok())
+        .filter(|v| v.is_object())
+}
+
+static REFRESHING_ACCOUNTS: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+
+fn refreshing_accounts() -> &'static Mutex<HashSet<String>> {
+    REFRESHING_ACCOUNTS.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+/// `exp` claim of a JWT access token, or None for opaque tokens.
+fn access_token_exp(token: &str) -> Option<i64> {
+    try_decode_jwt(token)?.get("exp")?.as_i64()
+}
+
+fn decode_jwt(token: &str) -> Value {
+    try_decode_jwt(token).unwrap_or_else(|| Value::Object(Default::default()))
+}
+
+/// "jwt" for a decodable JWT, "opaque" for any other non-empty token
+/// (e.g. API-key style), "none" when there is no token.
+fn token_kind(token: &str) -> &'static str {
+    if token.trim().is_empty() {
+        "none"
+    } else if try_decode_jwt(token).is_some() {
+        "jwt"
+    } else {
+        "opaque"
+    }
 }
 
 fn read_meta() -> HashMap<String, MetaEntry> {
@@ -4490,6 +5835,16 @@ fn write_meta(meta: &HashMap<String, MetaEntry>) {
     }
 }
 
+/// The refresh token from an auth.json, or "" when absent.
+fn auth_refresh_token(auth_data: &Value) -> &str {
+    auth_data
+        .get("tokens")
+        .and_then(|t| t.get("refresh_token"))
+        .or_else(|| auth_data.get("refresh_token"))
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+}
+
 fn parse_auth_data(auth_data: &Value, account_id: &str) -> CodexAccount {
     let tokens = auth_data.get("tokens");
     let empty = Value::Object(Default::default());
@@ -4506,11 +5861,7 @@ fn parse_auth_data(auth_data: &Value, account_id: &str) -> CodexAccount {
         .and_then(|v| v.as_str())
         .unwrap_or("");
 
-    let refresh_token = tokens
-        .and_then(|t| t.get("refresh_token"))
-        .or_else(|| auth_data.get("refresh_token"))
-        .and_then(|v| v.as_str())
-        .unwrap_or("");
+    let refresh_token = auth_refresh_token(auth_data);
 
     let stored_account_id = tokens
         .and_then(|t| t.get("account_id"))
@@ -4579,6 +5930,15 @@ fn parse_auth_data(auth_data: &Value, account_id: &str) -> CodexAccount {
         label: None,
         added_at: 0,
         proxy_enabled: true,
+        organization: None,
+        project: None,
+        tags: Vec::new(),
+        outbound_proxy: None,
+        last_used_at: None,
+        client_profile: None,
+        no_refresh: false,
+        base_url: None,
+        token_kind: token_kind(access_token).to_string(),
     }
 }
 
@@ -4603,10 +5963,10 @@ fn extract_auth_tokens(auth_data: &Value) -> (String, Option<String>, Option<Str
     (access_token, refresh_token, account_id)
 }
 
-fn load_any_auth_data() -> Result<(Value, bool), String> {
+fn load_any_auth_data() -> Result<(Value, Option<String>), String> {
     if let Ok(content) = fs::read_to_string(auth_file()) {
         if let Ok(auth_data) = serde_json::from_str::<Value>(&content) {
-            return Ok((auth_data, false));
+            return Ok((auth_data, None));
         }
     }
     let accounts_path = accounts_dir();
@@ -4619,7 +5979,7 @@ fn load_any_auth_data() -> Result<(Value, bool), String> {
             }
             if let Ok(content) = fs::read_to_string(&auth_path) {
                 if let Ok(auth_data) = serde_json::from_str::<Value>(&content) {
-                    return Ok((auth_data, true));
+                    return Ok((auth_data, Some(entry.file_name().to_string_lossy().to_string())));
                 }
             }
         }
@@ -4634,6 +5994,38 @@ const AUTH0_DOMAIN: &str = "auth.openai.com";
 const CLIENT_ID: &str = "app_EMoamEEZ73f0CkXaXp7hrann";
 const SCOPE: &str = "openid profile email offline_access";
 const OAUTH_CALLBACK_PORT: u16 = 1455;
+const OAUTH_BIND_ATTEMPTS: u32 = 3;
+const OAUTH_BIND_RETRY_MS: u64 = 200;
+const OAUTH_LOGIN_TIMEOUT_SECS: u64 = 180;
+const OAUTH_LOGIN_MAX_TIMEOUT_SECS: u64 = 600;
+
+/// `key` from the environment, trimmed, or `default` when unset or blank.
+fn oauth_env(key: &str, default: &str) -> String {
+    std::env::var(key)
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| default.to_string())
+}
+
+// CODEXMANAGER_AUTH0_DOMAIN / _CLIENT_ID / _OAUTH_SCOPE point the OAuth flow at
+// another issuer, e.g. a staging auth domain
+fn oauth_domain() -> String {
+    oauth_env("CODEXMANAGER_AUTH0_DOMAIN", AUTH0_DOMAIN)
+}
+
+fn oauth_client_id() -> String {
+    oauth_env("CODEXMANAGER_CLIENT_ID", CLIENT_ID)
+}
+
+fn oauth_scope() -> String {
+    oauth_env("CODEXMANAGER_OAUTH_SCOPE", SCOPE)
+}
+
+// OpenAI's flow sends no audience, so there is only an override
+fn oauth_audience() -> Option<String> {
+    Some(oauth_env("CODEXMANAGER_OAUTH_AUDIENCE", "")).filter(|v| !v.is_empty())
+}
 
 fn pkce_verifier() -> String {
     let mut bytes = [0u8; 64];
@@ -4648,11 +6040,11 @@ fn pkce_challenge(verifier: &str) -> String {
 }
 
 fn build_auth_url(redirect_uri: &str, code_challenge: &str, state: &str) -> String {
-    let domain = AUTH0_DOMAIN;
-    format!(
+    let domain = oauth_domain();
+    let mut url = format!(
         "https://{domain}/oauth/authorize\
          ?response_type=code\
-         &client_id={CLIENT_ID}\
+         &client_id={client_id}\
          &redirect_uri={redirect_uri}\
          &scope={scope}\
          &code_challenge={code_challenge}\
@@ -4661,12 +6053,20 @@ fn build_auth_url(redirect_uri: &str, code_challenge: &str, state: &str) -> Stri
          &codex_cli_simplified_flow=true\
          &state={state}\
          &originator=codex_cli_rs",
+        client_id = percent_encoding::utf8_percent_encode(&oauth_client_id(), percent_encoding::NON_ALPHANUMERIC),
         redirect_uri = percent_encoding::utf8_percent_encode(
             redirect_uri,
             percent_encoding::NON_ALPHANUMERIC
         ),
-        scope = percent_encoding::utf8_percent_encode(SCOPE, percent_encoding::NON_ALPHANUMERIC),
-    )
+        scope = percent_encoding::utf8_percent_encode(&oauth_scope(), percent_encoding::NON_ALPHANUMERIC),
+    );
+    if let Some(audience) = oauth_audience() {
+        url.push_str(&format!(
+            "&audience={}",
+            percent_encoding::utf8_percent_encode(&audience, percent_encoding::NON_ALPHANUMERIC)
+        ));
+    }
+    url
 }
 
 async fn exchange_code(
@@ -4675,15 +6075,16 @@ async fn exchange_code(
     verifier: &str,
 ) -> Result<Value, String> {
     let client = reqwest::Client::new();
+    let client_id = oauth_client_id();
     let params = [
         ("grant_type", "authorization_code"),
-        ("client_id", CLIENT_ID),
+        ("client_id", client_id.as_str()),
         ("code", code),
         ("redirect_uri", redirect_uri),
         ("code_verifier", verifier),
     ];
     let resp = client
-        .post(format!("https://{AUTH0_DOMAIN}/oauth/token"))
+        .post(format!("https://{}/oauth/token", oauth_domain()))
         .form(&params)
         .send()
         .await
@@ -4699,13 +6100,14 @@ async fn exchange_code(
 
 async fn do_token_refresh(refresh_token: &str) -> Result<Value, String> {
     let client = reqwest::Client::new();
+    let client_id = oauth_client_id();
     let params = [
         ("grant_type", "refresh_token"),
-        ("client_id", CLIENT_ID),
+        ("client_id", client_id.as_str()),
         ("refresh_token", refresh_token),
     ];
     let resp = client
-        .post(format!("https://{AUTH0_DOMAIN}/oauth/token"))
+        .post(format!("https://{}/oauth/token", oauth_domain()))
         .form(&params)
         .send()
         .await
@@ -4776,7 +6178,8 @@ fn save_oauth_tokens(token_response: &Value) -> Result<CodexAccount, String> {
 // ─── Tauri commands: account management ──────────────────────────────────────
 
 #[tauri::command]
-fn list_accounts() -> Result<Vec<CodexAccount>, String> {
+fn list_accounts(tag: Option<String>) -> Result<Vec<CodexAccount>, String> {
+    let tag = tag.map(|t| t.trim().to_string()).filter(|t| !t.is_empty());
     let accounts_path = accounts_dir();
     if !accounts_path.exists() {
         fs::create_dir_all(&accounts_path).map_err(|e| e.to_string())?;
@@ -4803,9 +6206,12 @@ fn list_accounts() -> Result<Vec<CodexAccount>, String> {
         let mut account = parse_auth_data(&auth_data, &dir_name);
         account.id = dir_name.clone();
         if let Some(m) = meta.get(&dir_name) {
-            account.label = m.label.clone();
-            account.added_at = m.added_at;
-            account.proxy_enabled = m.proxy_enabled;
+            m.apply_to(&mut account);
+        }
+        if let Some(tag) = &tag {
+            if !account.tags.contains(tag) {
+                continue;
+            }
         }
         accounts.push(account);
     }
@@ -4813,6 +6219,209 @@ fn list_accounts() -> Result<Vec<CodexAccount>, String> {
     Ok(accounts)
 }
 
+/// Access-token expiry for every account, without the full `CodexAccount` payload.
+/// `expires_at` is in milliseconds like `CodexAccount`; `expires_in_secs` is negative once expired.
+#[tauri::command]
+fn get_accounts_expiry() -> Result<Vec<Value>, String> {
+    let accounts_path = accounts_dir();
+    if !accounts_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let mut out = Vec::new();
+    let entries = fs::read_dir(&accounts_path).map_err(|e| e.to_string())?;
+    for entry in entries.flatten() {
+        let dir_name = entry.file_name().to_string_lossy().to_string();
+        let content = match fs::read_to_string(entry.path().join("auth.json")) {
+            Ok(c) => c,
+            Err(_) => continue,
+        };
+        let auth_data: Value = match serde_json::from_str(&content) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        let access_token = auth_data
+            .get("tokens")
+            .and_then(|t| t.get("access_token"))
+            .or_else(|| auth_data.get("access_token"))
+            .and_then(|v| v.as_str())
+            .unwrap_or("");
+        let exp = decode_jwt(access_token)
+            .get("exp")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(0);
+        out.push(serde_json::json!({
+            "id": dir_name,
+            "expires_at": exp * 1000,
+            "expires_in_secs": if exp > 0 { Some(exp - now) } else { None },
+            "token_kind": token_kind(access_token),
+        }));
+    }
+
+    Ok(out)
+}
+
+/// Ids of accounts whose auth.json has no refresh token and so can't be
+/// healed after a 401 ("re-login required").
+#[tauri::command]
+fn get_accounts_without_refresh() -> Result<Vec<String>, String> {
+    let accounts_path = accounts_dir();
+    if !accounts_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut out = Vec::new();
+    let entries = fs::read_dir(&accounts_path).map_err(|e| e.to_string())?;
+    for entry in entries.flatten() {
+        let content = match fs::read_to_string(entry.path().join("auth.json")) {
+            Ok(c) => c,
+            Err(_) => continue,
+        };
+        let auth_data: Value = match serde_json::from_str(&content) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        if auth_refresh_token(&auth_data).trim().is_empty() {
+            out.push(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+    out.sort();
+    Ok(out)
+}
+
+/// One diagnostic object per account directory: whether auth.json parses, which
+/// tokens it holds, whether the access token has expired, whether the directory
+/// name matches the id derived from the token and whether it has a meta entry.
+/// `problems` lists the failed checks so the UI can show them directly.
+#[tauri::command]
+fn audit_accounts() -> Result<Vec<Value>, String> {
+    let accounts_path = accounts_dir();
+    if !accounts_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let meta = read_meta();
+    let now_ms = chrono::Utc::now().timestamp_millis();
+    let mut out = Vec::new();
+    let entries = fs::read_dir(&accounts_path).map_err(|e| e.to_string())?;
+    for entry in entries.flatten() {
+        if !entry.path().is_dir() {
+            continue;
+        }
+        let id = entry.file_name().to_string_lossy().to_string();
+        let in_meta = meta.contains_key(&id);
+        let mut problems: Vec<&str> = Vec::new();
+        if !in_meta {
+            problems.push("missing_meta");
+        }
+
+        let auth_data = fs::read_to_string(entry.path().join("auth.json"))
+            .ok()
+            .and_then(|c| serde_json::from_str::<Value>(&c).ok());
+        let Some(auth_data) = auth_data else {
+            problems.insert(0, "parse_failed");
+            out.push(serde_json::json!({
+                "id": id,
+                "parses_ok": false,
+                "has_access_token": false,
+                "has_refresh_token": false,
+                "token_expired": null,
+                "id_matches_token": null,
+                "in_meta": in_meta,
+                "problems": problems,
+            }));
+            continue;
+        };
+
+        let (access_token, _, _) = extract_auth_tokens(&auth_data);
+        let parsed = parse_auth_data(&auth_data, &id);
+        let has_access_token = !access_token.trim().is_empty();
+        let has_refresh_token = !auth_refresh_token(&auth_data).trim().is_empty();
+        // Opaque tokens carry no expiry, so there's nothing to check
+        let token_expired = (parsed.token_kind == "jwt").then(|| parsed.expires_at <= now_ms);
+        let id_matches_token = canonical_account_id(&auth_data).map(|canonical| canonical == id);
+
+        if !has_access_token {
+            problems.push("no_access_token");
+        }
+        if !has_refresh_token {
+            problems.push("no_refresh_token");
+        }
+        if token_expired == Some(true) {
+            problems.push("token_expired");
+        }
+        if id_matches_token == Some(false) {
+            problems.push("id_mismatch");
+        }
+        out.push(serde_json::json!({
+            "id": id,
+            "parses_ok": true,
+            "has_access_token": has_access_token,
+            "has_refresh_token": has_refresh_token,
+            "token_expired": token_expired,
+            "id_matches_token": id_matches_token,
+            "in_meta": in_meta,
+            "problems": problems,
+        }));
+    }
+    out.sort_by(|a, b| a["id"].as_str().cmp(&b["id"].as_str()));
+    Ok(out)
+}
+
+/// Return one account's auth.json as text, e.g. to paste into the `codex` CLI
+/// on another machine. `pretty` re-indents it; otherwise the file is returned as stored.
+#[tauri::command]
+fn export_account(id: String, pretty: Option<bool>) -> Result<String, String> {
+    if id.is_empty() || !id.chars().all(is_safe_account_id_char) {
+        return Err(format!("无效的账号 ID: {id}"));
+    }
+    let auth_path = accounts_dir().join(&id).join("auth.json");
+    if !auth_path.exists() {
+        return Err(format!("Account {id} not found"));
+    }
+    let content = fs::read_to_string(&auth_path).map_err(|e| e.to_string())?;
+    if !pretty.unwrap_or(false) {
+        return Ok(content);
+    }
+    let auth_data: Value = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+    serde_json::to_string_pretty(&auth_data).map_err(|e| e.to_string())
+}
+
+/// List every managed account with the path of its auth.json, so scripts can
+/// point `CODEX_HOME`-style tooling at one directly. No tokens are included.
+#[tauri::command]
+fn export_accounts_manifest() -> Result<Value, String> {
+    let accounts_path = accounts_dir();
+    if !accounts_path.exists() {
+        return Ok(Value::Array(Vec::new()));
+    }
+    let mut out = Vec::new();
+    let entries = fs::read_dir(&accounts_path).map_err(|e| e.to_string())?;
+    for entry in entries.flatten() {
+        let auth_path = entry.path().join("auth.json");
+        let Some(auth_data) = fs::read_to_string(&auth_path)
+            .ok()
+            .and_then(|c| serde_json::from_str::<Value>(&c).ok())
+        else {
+            continue;
+        };
+        let id = entry.file_name().to_string_lossy().to_string();
+        let parsed = parse_auth_data(&auth_data, &id);
+        let (_, _, account_id) = extract_auth_tokens(&auth_data);
+        out.push(serde_json::json!({
+            "id": id,
+            "email": parsed.email,
+            "plan": parsed.plan,
+            "account_id": account_id,
+            "auth_json_path": auth_path.to_string_lossy(),
+            "expires_at": parsed.expires_at,
+        }));
+    }
+    out.sort_by(|a, b| a["id"].as_str().cmp(&b["id"].as_str()));
+    Ok(Value::Array(out))
+}
+
 #[tauri::command]
 fn get_current_account() -> Result<Option<CodexAccount>, String> {
     let auth_path = auth_file();
@@ -4850,9 +6459,7 @@ fn get_current_account() -> Result<Option<CodexAccount>, String> {
                         if matches {
                             parsed.id = dir_name.clone();
                             if let Some(m) = meta.get(&dir_name) {
-                                parsed.label = m.label.clone();
-                                parsed.added_at = m.added_at;
-                                parsed.proxy_enabled = m.proxy_enabled;
+                                m.apply_to(&mut parsed);
                             }
                             return Ok(Some(parsed));
                         }
@@ -4917,112 +6524,984 @@ fn delete_account(id: String) -> Result<bool, String> {
     Ok(true)
 }
 
+/// Remove meta entries whose account directory no longer exists (e.g. deleted
+/// by hand) and return their ids, sorted. A missing accounts directory prunes
+/// nothing, so a misconfigured HOME can't wipe the meta store.
 #[tauri::command]
-fn update_label(id: String, label: String) -> Result<bool, String> {
+fn prune_orphan_meta() -> Result<Vec<String>, String> {
+    let accounts_path = accounts_dir();
+    if !accounts_path.is_dir() {
+        return Ok(Vec::new());
+    }
     let mut meta = read_meta();
-    let entry = meta.entry(id).or_insert_with(|| MetaEntry {
-        label: None,
-        added_at: std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis() as u64,
-        proxy_enabled: true,
-    });
-    entry.label = if label.is_empty() { None } else { Some(label) };
+    let mut pruned: Vec<String> = meta
+        .keys()
+        .filter(|id| !accounts_path.join(id).is_dir())
+        .cloned()
+        .collect();
+    if pruned.is_empty() {
+        return Ok(pruned);
+    }
+    for id in &pruned {
+        meta.remove(id);
+    }
     write_meta(&meta);
-    Ok(true)
+    pruned.sort();
+    Ok(pruned)
 }
 
+/// accounts_meta.json exactly as stored, or `{}` when it doesn't exist yet.
 #[tauri::command]
-fn update_proxy_enabled(id: String, enabled: bool) -> Result<bool, String> {
-    let mut meta = read_meta();
-    let entry = meta.entry(id).or_insert_with(|| MetaEntry {
-        label: None,
-        added_at: std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis() as u64,
-        proxy_enabled: true,
-    });
-    entry.proxy_enabled = enabled;
-    write_meta(&meta);
-    emit_accounts_updated("proxy_enabled_changed");
-    Ok(true)
+fn get_accounts_meta() -> Result<Value, String> {
+    let path = meta_file();
+    if !path.exists() {
+        return Ok(Value::Object(Default::default()));
+    }
+    let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&content).map_err(|e| e.to_string())
 }
 
+/// Replace accounts_meta.json wholesale. `value` must map account ids to
+/// entries with at least `added_at`; entries with a wrong type, an unknown
+/// key, or a `base_url` / `outbound_proxy` the per-account setters would
+/// refuse are rejected and nothing is written. The running pool picks up
+/// changes on the next reload.
 #[tauri::command]
-fn import_current(label: Option<String>) -> Result<Value, String> {
-    let auth_path = auth_file();
-    if !auth_path.exists() {
-        return Err("No auth.json found. Please login first.".into());
+fn set_accounts_meta(value: Value) -> Result<bool, String> {
+    let entries = value.as_object().ok_or("accounts_meta 必须是 JSON 对象")?;
+    for (id, entry) in entries {
+        if id.is_empty() || !id.chars().all(is_safe_account_id_char) {
+            return Err(format!("无效的账号 ID: {id}"));
+        }
+        let fields = entry.as_object().ok_or_else(|| format!("{id}: 条目必须是 JSON 对象"))?;
+        if !fields.contains_key("added_at") {
+            return Err(format!("{id}: 缺少 added_at"));
+        }
+        let parsed: MetaEntry = serde_json::from_value(entry.clone()).map_err(|e| format!("{id}: {e}"))?;
+        let known = serde_json::to_value(&parsed).map_err(|e| e.to_string())?;
+        if let Some(key) = fields.keys().find(|key| known.get(key.as_str()).is_none()) {
+            return Err(format!("{id}: 未知字段 {key}"));
+        }
     }
-    let content = fs::read_to_string(&auth_path).map_err(|e| e.to_string())?;
-    let auth_data: Value = serde_json::from_str(&content).map_err(|e| e.to_string())?;
-    let parsed = parse_auth_data(&auth_data, "tmp");
+    let mut meta: HashMap<String, MetaEntry> = serde_json::from_value(value).map_err(|e| e.to_string())?;
+    for (id, entry) in meta.iter_mut() {
+        entry.base_url = normalize_account_base_url(entry.base_url.take()).map_err(|e| format!("{id}: {e}"))?;
+        entry.outbound_proxy = normalize_outbound_proxy(entry.outbound_proxy.take()).map_err(|e| format!("{id}: {e}"))?;
+    }
+    write_meta(&meta);
+    emit_accounts_updated("meta_changed");
+    Ok(true)
+}
 
-    let empty = Value::Object(Default::default());
-    let tokens = auth_data.get("tokens").unwrap_or(&empty);
-    let account_id = tokens
-        .get("account_id")
+/// Directory name an account's tokens map to: `tokens.account_id`, falling back
+/// to the chatgpt user id, with unsafe characters replaced by `_`.
+fn canonical_account_id(auth_data: &Value) -> Option<String> {
+    let parsed = parse_auth_data(auth_data, "tmp");
+    let account_id = auth_data
+        .get("tokens")
+        .and_then(|t| t.get("account_id"))
         .and_then(|v| v.as_str())
-        .unwrap_or_else(|| {
-            if parsed.user_id.is_empty() {
-                "acc_tmp"
-            } else {
-                &parsed.user_id
-            }
-        })
-        .to_string();
+        .map(|s| s.to_string())
+        .or_else(|| if parsed.user_id.is_empty() { None } else { Some(parsed.user_id) })?;
+    Some(
+        account_id
+            .chars()
+            .map(|c| if is_safe_account_id_char(c) { c } else { '_' })
+            .collect(),
+    )
+}
 
-    let safe_id: String = account_id
-        .chars()
-        .map(|c| {
-            if c.is_alphanumeric() || c == '_' || c == '-' {
-                c
-            } else {
-                '_'
-            }
-        })
-        .collect();
+/// Characters allowed in an account directory name.
+fn is_safe_account_id_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
 
-    let dest_dir = accounts_dir().join(&safe_id);
-    fs::create_dir_all(&dest_dir).map_err(|e| e.to_string())?;
-    fs::copy(&auth_path, dest_dir.join("auth.json")).map_err(|e| e.to_string())?;
+/// Rename an account directory and move its metadata to the new id.
+#[tauri::command]
+fn rename_account(old_id: String, new_id: String) -> Result<bool, String> {
+    if old_id.is_empty() || !old_id.chars().all(is_safe_account_id_char) {
+        return Err(format!("无效的账号 ID: {old_id}"));
+    }
+    let new_id = new_id.trim().to_string();
+    if new_id.is_empty() || !new_id.chars().all(is_safe_account_id_char) {
+        return Err("Invalid account id: only letters, digits, '_' and '-' are allowed".into());
+    }
+    if new_id == old_id {
+        return Ok(true);
+    }
+    let old_dir = accounts_dir().join(&old_id);
+    if !old_dir.exists() {
+        return Err("Account not found".into());
+    }
+    let new_dir = accounts_dir().join(&new_id);
+    if new_dir.exists() {
+        return Err(format!("Account {new_id} already exists"));
+    }
+    move_account_dir(&old_id, &new_id)?;
+    emit_accounts_updated("account_renamed");
+    Ok(true)
+}
 
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_millis() as u64;
+/// Rename `old_id`'s directory to `new_id`, carry its meta entry over (filling
+/// gaps in an existing entry) and repoint the running pool.
+fn move_account_dir(old_id: &str, new_id: &str) -> Result<(), String> {
+    fs::rename(accounts_dir().join(old_id), accounts_dir().join(new_id)).map_err(|e| e.to_string())?;
 
     let mut meta = read_meta();
-    meta.insert(
-        safe_id.clone(),
-        MetaEntry {
-            label,
-            added_at: now,
-            proxy_enabled: true,
-        },
-    );
-    write_meta(&meta);
+    if let Some(old) = meta.remove(old_id) {
+        match meta.get_mut(new_id) {
+            Some(existing) => existing.merge(old),
+            None => {
+                meta.insert(new_id.to_string(), old);
+            }
+        }
+        write_meta(&meta);
+    }
 
-    Ok(serde_json::json!({
-        "success": true,
-        "id": safe_id,
-        "email": parsed.email
-    }))
+    // Keep the running pool pointing at the renamed directory for token refresh
+    let state = PROXY_STATE.lock().unwrap().clone();
+    if let Some(state) = state {
+        let mut accounts_lock = state.accounts.write().unwrap();
+        for acc in accounts_lock.iter_mut().filter(|a| a.id == old_id) {
+            acc.id = new_id.to_string();
+        }
+    }
+    Ok(())
 }
 
+/// Rename account directories whose name doesn't match the canonical id derived
+/// from their tokens. Collisions are skipped and reported, never overwritten.
 #[tauri::command]
-fn get_config() -> Result<Value, String> {
-    let config_path = codex_dir().join("config.toml");
-    if !config_path.exists() {
-        return Ok(serde_json::json!({ "raw": "" }));
+fn normalize_account_ids() -> Result<Vec<Value>, String> {
+    let accounts_path = accounts_dir();
+    if !accounts_path.exists() {
+        return Ok(Vec::new());
     }
-    let raw = fs::read_to_string(&config_path).map_err(|e| e.to_string())?;
-    Ok(serde_json::json!({ "raw": raw }))
-}
 
-// ─── Tauri commands: OAuth PKCE login ────────────────────────────────────────
+    let mut report = Vec::new();
+    let entries = fs::read_dir(&accounts_path).map_err(|e| e.to_string())?;
+    for entry in entries.flatten() {
+        let dir_name = entry.file_name().to_string_lossy().to_string();
+        let content = match fs::read_to_string(entry.path().join("auth.json")) {
+            Ok(c) => c,
+            Err(_) => continue,
+        };
+        let auth_data: Value = match serde_json::from_str(&content) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        let Some(canonical) = canonical_account_id(&auth_data) else {
+            report.push(serde_json::json!({
+                "id": dir_name,
+                "status": "skipped",
+                "reason": "no account id in tokens",
+            }));
+            continue;
+        };
+        if canonical == dir_name {
+            continue;
+        }
+        if accounts_path.join(&canonical).exists() {
+            report.push(serde_json::json!({
+                "id": dir_name,
+                "new_id": canonical,
+                "status": "skipped",
+                "reason": "target id already exists",
+            }));
+            continue;
+        }
+        match move_account_dir(&dir_name, &canonical) {
+            Ok(()) => report.push(serde_json::json!({
+                "id": dir_name,
+                "new_id": canonical,
+                "status": "renamed",
+            })),
+            Err(err) => report.push(serde_json::json!({
+                "id": dir_name,
+                "new_id": canonical,
+                "status": "failed",
+                "reason": err,
+            })),
+        }
+    }
+
+    if report.iter().any(|r| r["status"] == "renamed") {
+        emit_accounts_updated("account_renamed");
+    }
+    Ok(report)
+}
+
+// ─── state snapshot / restore ────────────────────────────────────────────────
+
+const SNAPSHOT_VERSION: u64 = 1;
+const SNAPSHOT_LOG_TABLES: [&str; 3] = ["request_logs", "ai_cache_entries", "health_events"];
+
+/// Temp file for a snapshot or restore; the random suffix keeps concurrent
+/// calls from sharing a file.
+fn snapshot_temp_path(kind: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "codex-manager-{kind}-{}-{:016x}.db",
+        std::process::id(),
+        rand::thread_rng().next_u64()
+    ))
+}
+
+fn append_snapshot_file(
+    builder: &mut tar::Builder<Vec<u8>>,
+    path: &str,
+    data: &[u8],
+) -> Result<(), String> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o600);
+    header.set_mtime(chrono::Utc::now().timestamp().max(0) as u64);
+    header.set_cksum();
+    builder.append_data(&mut header, path, data).map_err(|e| e.to_string())
+}
+
+/// Pack every account's auth.json, accounts_meta.json, proxy_config.json and,
+/// optionally, a consistent copy of the logs database into a tar archive.
+#[tauri::command]
+fn snapshot_state(include_logs: Option<bool>) -> Result<Vec<u8>, String> {
+    let include_logs = include_logs.unwrap_or(false);
+    let mut builder = tar::Builder::new(Vec::new());
+    let mut account_ids = Vec::new();
+
+    let accounts_path = accounts_dir();
+    if accounts_path.exists() {
+        let entries = fs::read_dir(&accounts_path).map_err(|e| e.to_string())?;
+        for entry in entries.flatten() {
+            let id = entry.file_name().to_string_lossy().to_string();
+            let auth_path = entry.path().join("auth.json");
+            if !auth_path.exists() {
+                continue;
+            }
+            let data = fs::read(&auth_path).map_err(|e| e.to_string())?;
+            append_snapshot_file(&mut builder, &format!("accounts/{id}/auth.json"), &data)?;
+            account_ids.push(id);
+        }
+    }
+    if let Ok(data) = fs::read(meta_file()) {
+        append_snapshot_file(&mut builder, "accounts_meta.json", &data)?;
+    }
+    if let Ok(data) = fs::read(proxy_config_path()) {
+        append_snapshot_file(&mut builder, "proxy_config.json", &data)?;
+    }
+    if include_logs {
+        // VACUUM INTO gives a consistent copy even while WAL writers are active
+        let tmp = snapshot_temp_path("snapshot");
+        {
+            let conn = proxy_log_db()?;
+            conn.execute("VACUUM INTO ?1", params![tmp.to_string_lossy().to_string()])
+                .map_err(|e| e.to_string())?;
+        }
+        let data = fs::read(&tmp).map_err(|e| e.to_string());
+        let _ = fs::remove_file(&tmp);
+        append_snapshot_file(&mut builder, "proxy_logs.db", &data?)?;
+    }
+
+    account_ids.sort();
+    let manifest = serde_json::json!({
+        "version": SNAPSHOT_VERSION,
+        "created_at": chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        "accounts": account_ids,
+        "include_logs": include_logs,
+    });
+    append_snapshot_file(&mut builder, "manifest.json", manifest.to_string().as_bytes())?;
+    builder.into_inner().map_err(|e| e.to_string())
+}
+
+/// Restore a `snapshot_state` archive. The whole archive, logs database included,
+/// is read and validated before anything is written; with `overwrite` false,
+/// existing accounts are a conflict and nothing is restored.
+#[tauri::command]
+fn restore_state(bytes: Vec<u8>, overwrite: bool) -> Result<Value, String> {
+    use std::io::Read;
+
+    let mut files: HashMap<String, Vec<u8>> = HashMap::new();
+    let mut archive = tar::Archive::new(bytes.as_slice());
+    let entries = archive.entries().map_err(|e| format!("备份文件损坏: {e}"))?;
+    for entry in entries {
+        let mut entry = entry.map_err(|e| format!("备份文件损坏: {e}"))?;
+        let path = entry
+            .path()
+            .map_err(|e| format!("备份文件损坏: {e}"))?
+            .to_string_lossy()
+            .to_string();
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data).map_err(|e| format!("备份文件损坏: {e}"))?;
+        files.insert(path, data);
+    }
+
+    let manifest: Value = files
+        .get("manifest.json")
+        .and_then(|data| serde_json::from_slice(data).ok())
+        .ok_or("备份文件缺少 manifest.json")?;
+    if manifest.get("version").and_then(|v| v.as_u64()) != Some(SNAPSHOT_VERSION) {
+        return Err("不支持的备份版本".into());
+    }
+
+    // Only known paths are accepted; anything else (including `..`) fails the restore
+    let mut accounts: Vec<(String, &Vec<u8>)> = Vec::new();
+    for (path, data) in &files {
+        match path.as_str() {
+            "manifest.json" | "proxy_logs.db" => {}
+            "accounts_meta.json" => {
+                serde_json::from_slice::<HashMap<String, MetaEntry>>(data)
+                    .map_err(|e| format!("accounts_meta.json 无效: {e}"))?;
+            }
+            "proxy_config.json" => {
+                serde_json::from_slice::<ProxyConfig>(data)
+                    .map_err(|e| format!("proxy_config.json 无效: {e}"))?;
+            }
+            other => {
+                let id = other
+                    .strip_prefix("accounts/")
+                    .and_then(|rest| rest.strip_suffix("/auth.json"))
+                    .filter(|id| !id.is_empty() && id.chars().all(is_safe_account_id_char))
+                    .ok_or_else(|| format!("备份包含未知文件: {other}"))?;
+                serde_json::from_slice::<Value>(data)
+                    .map_err(|e| format!("{other} 无效: {e}"))?;
+                accounts.push((id.to_string(), data));
+            }
+        }
+    }
+    let expected: Vec<String> = manifest
+        .get("accounts")
+        .and_then(|v| v.as_array())
+        .map(|ids| ids.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect())
+        .unwrap_or_default();
+    let mut restored_ids: Vec<String> = accounts.iter().map(|(id, _)| id.clone()).collect();
+    restored_ids.sort();
+    let mut expected_sorted = expected.clone();
+    expected_sorted.sort();
+    if restored_ids != expected_sorted {
+        return Err("备份文件不完整: 账号列表与 manifest 不一致".into());
+    }
+
+    if !overwrite {
+        let conflicts: Vec<&String> = restored_ids
+            .iter()
+            .filter(|id| accounts_dir().join(id.as_str()).exists())
+            .collect();
+        if !conflicts.is_empty() {
+            return Err(format!("以下账号已存在: {conflicts:?}"));
+        }
+    }
+
+    let log_snapshot = match files.get("proxy_logs.db") {
+        Some(data) => Some(stage_log_snapshot(data)?),
+        None => None,
+    };
+    let applied = (|| -> Result<(), String> {
+        for (id, data) in &accounts {
+            let dir = accounts_dir().join(id);
+            fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+            fs::write(dir.join("auth.json"), data).map_err(|e| e.to_string())?;
+        }
+        if let Some(data) = files.get("accounts_meta.json") {
+            // Merged per id so accounts outside the snapshot keep their meta; a
+            // snapshot entry replaces an existing one only with `overwrite`
+            let restored: HashMap<String, MetaEntry> = serde_json::from_slice(data).map_err(|e| e.to_string())?;
+            let mut meta = read_meta();
+            for (id, entry) in restored {
+                if overwrite || !meta.contains_key(&id) {
+                    meta.insert(id, entry);
+                }
+            }
+            write_meta(&meta);
+        }
+        if let Some(data) = files.get("proxy_config.json") {
+            let path = proxy_config_path();
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            fs::write(&path, data).map_err(|e| e.to_string())?;
+            *proxy_config().lock().unwrap() = load_proxy_config();
+        }
+        if let Some(tmp) = &log_snapshot {
+            restore_log_tables(tmp, overwrite)?;
+        }
+        Ok(())
+    })();
+    if let Some(tmp) = &log_snapshot {
+        let _ = fs::remove_file(tmp);
+    }
+    applied?;
+    let logs_restored = log_snapshot.is_some();
+
+    emit_accounts_updated("state_restored");
+    Ok(serde_json::json!({
+        "success": true,
+        "accounts": restored_ids,
+        "logs_restored": logs_restored,
+    }))
+}
+
+/// Write the snapshot's logs database to a temp file and check it: it has to
+/// pass `integrity_check` and hold every table in SNAPSHOT_LOG_TABLES.
+fn stage_log_snapshot(data: &[u8]) -> Result<PathBuf, String> {
+    let tmp = snapshot_temp_path("restore");
+    fs::write(&tmp, data).map_err(|e| e.to_string())?;
+    let checked = (|| -> Result<(), String> {
+        let conn = Connection::open(&tmp).map_err(|e| format!("proxy_logs.db 无效: {e}"))?;
+        let integrity: String = conn
+            .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+            .map_err(|e| format!("proxy_logs.db 无效: {e}"))?;
+        if integrity != "ok" {
+            return Err(format!("proxy_logs.db 无效: {integrity}"));
+        }
+        for table in SNAPSHOT_LOG_TABLES {
+            let found: i64 = conn
+                .query_row(
+                    "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+                    params![table],
+                    |row| row.get(0),
+                )
+                .map_err(|e| format!("proxy_logs.db 无效: {e}"))?;
+            if found == 0 {
+                return Err(format!("proxy_logs.db 缺少表 {table}"));
+            }
+        }
+        Ok(())
+    })();
+    if let Err(err) = checked {
+        let _ = fs::remove_file(&tmp);
+        return Err(err);
+    }
+    Ok(tmp)
+}
+
+/// Copy the log tables of a staged snapshot database into the live one. With
+/// `overwrite` the snapshot rows replace the live ones; otherwise they are added
+/// under new ids and live cache entries win on conflict. The live connection
+/// stays open, so the snapshot is attached and copied column by column.
+fn restore_log_tables(snapshot: &std::path::Path, overwrite: bool) -> Result<(), String> {
+    let conn = proxy_log_db()?;
+    conn.execute("ATTACH DATABASE ?1 AS restored", params![snapshot.to_string_lossy().to_string()])
+        .map_err(|e| e.to_string())?;
+    let copy = (|| {
+        let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+        for table in SNAPSHOT_LOG_TABLES {
+            let columns = |schema: &str| -> Result<Vec<String>, String> {
+                let mut stmt = tx
+                    .prepare(&format!("PRAGMA {schema}.table_info({table})"))
+                    .map_err(|e| e.to_string())?;
+                let rows = stmt
+                    .query_map([], |row| row.get::<_, String>(1))
+                    .map_err(|e| e.to_string())?;
+                rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+            };
+            let live = columns("main")?;
+            let shared: Vec<String> = columns("restored")?
+                .into_iter()
+                .filter(|c| live.contains(c) && (overwrite || c != "id"))
+                .collect();
+            if overwrite {
+                tx.execute(&format!("DELETE FROM main.{table}"), []).map_err(|e| e.to_string())?;
+            }
+            if shared.is_empty() {
+                continue;
+            }
+            let cols = shared.join(", ");
+            tx.execute(
+                &format!("INSERT OR IGNORE INTO main.{table} ({cols}) SELECT {cols} FROM restored.{table}"),
+                [],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        tx.commit().map_err(|e| e.to_string())
+    })();
+    let _ = conn.execute("DETACH DATABASE restored", []);
+    if copy.is_ok() {
+        seed_request_ids(&conn);
+    }
+    copy
+}
+
+#[tauri::command]
+fn update_label(id: String, label: String) -> Result<bool, String> {
+    let mut meta = read_meta();
+    let entry = meta.entry(id).or_insert_with(|| MetaEntry::new(now_unix_millis()));
+    entry.label = if label.is_empty() { None } else { Some(label) };
+    write_meta(&meta);
+    Ok(true)
+}
+
+#[tauri::command]
+fn update_proxy_enabled(id: String, enabled: bool) -> Result<bool, String> {
+    let mut meta = read_meta();
+    let entry = meta.entry(id).or_insert_with(|| MetaEntry::new(now_unix_millis()));
+    entry.proxy_enabled = enabled;
+    write_meta(&meta);
+    emit_accounts_updated("proxy_enabled_changed");
+    Ok(true)
+}
+
+/// Mark an account as never refreshed by the manager. The running pool is
+/// updated in place: its refresh token is dropped or restored from auth.json.
+#[tauri::command]
+fn set_account_no_refresh(id: String, no_refresh: bool) -> Result<bool, String> {
+    let mut meta = read_meta();
+    let entry = meta.entry(id.clone()).or_insert_with(|| MetaEntry::new(now_unix_millis()));
+    entry.no_refresh = no_refresh;
+    write_meta(&meta);
+
+    let state = PROXY_STATE.lock().unwrap().clone();
+    if let Some(state) = state {
+        let refresh_token = if no_refresh {
+            None
+        } else {
+            fs::read_to_string(accounts_dir().join(&id).join("auth.json"))
+                .ok()
+                .and_then(|s| serde_json::from_str::<Value>(&s).ok())
+                .and_then(|v| v.pointer("/tokens/refresh_token").and_then(|t| t.as_str()).map(|t| t.to_string()))
+        };
+        let mut accounts_lock = state.accounts.write().unwrap();
+        if let Some(acc) = accounts_lock.iter_mut().find(|a| a.id == id) {
+            acc.refresh_token = refresh_token;
+        }
+    }
+    emit_accounts_updated("no_refresh_changed");
+    Ok(true)
+}
+
+/// Set or clear the OpenAI organization / project sent upstream for an account.
+#[tauri::command]
+fn set_account_org(id: String, organization: Option<String>, project: Option<String>) -> Result<bool, String> {
+    let organization = organization.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    let project = project.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    let mut meta = read_meta();
+    let entry = meta.entry(id.clone()).or_insert_with(|| MetaEntry::new(now_unix_millis()));
+    entry.organization = organization.clone();
+    entry.project = project.clone();
+    write_meta(&meta);
+
+    // Apply to the running pool so the next request picks it up
+    let state = PROXY_STATE.lock().unwrap().clone();
+    if let Some(state) = state {
+        let mut accounts_lock = state.accounts.write().unwrap();
+        if let Some(acc) = accounts_lock.iter_mut().find(|a| a.id == id) {
+            acc.org.organization = organization;
+            acc.org.project = project;
+        }
+    }
+    emit_accounts_updated("org_changed");
+    Ok(true)
+}
+
+/// Trimmed outbound proxy URL, `None` when empty; errors if reqwest can't use it.
+fn normalize_outbound_proxy(proxy: Option<String>) -> Result<Option<String>, String> {
+    let proxy = proxy.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    if let Some(url) = &proxy {
+        reqwest::Proxy::all(url).map_err(|e| format!("代理地址无效: {e}"))?;
+    }
+    Ok(proxy)
+}
+
+/// Trimmed per-account base URL without a trailing `/`, `None` when empty;
+/// errors unless it is an http(s) URL that isn't the running proxy itself.
+fn normalize_account_base_url(base_url: Option<String>) -> Result<Option<String>, String> {
+    let base_url = base_url
+        .map(|v| v.trim().trim_end_matches('/').to_string())
+        .filter(|v| !v.is_empty());
+    if let Some(url) = &base_url {
+        let parsed = reqwest::Url::parse(url).map_err(|e| format!("上游地址无效: {e}"))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(format!("上游地址必须是 http 或 https: {url}"));
+        }
+        if upstream_loops_back_to_proxy(url) {
+            return Err(format!("上游地址指向代理自身: {url}"));
+        }
+    }
+    Ok(base_url)
+}
+
+/// Route an account's upstream traffic through `proxy` (http, https or socks5
+/// URL); `None` or empty goes back to the shared direct client.
+#[tauri::command]
+fn set_account_outbound_proxy(id: String, proxy: Option<String>) -> Result<bool, String> {
+    let proxy = normalize_outbound_proxy(proxy)?;
+    let mut meta = read_meta();
+    let entry = meta.entry(id.clone()).or_insert_with(|| MetaEntry::new(now_unix_millis()));
+    entry.outbound_proxy = proxy.clone();
+    write_meta(&meta);
+
+    // Apply to the running pool; `account_client` rebuilds when the URL changes
+    let state = PROXY_STATE.lock().unwrap().clone();
+    if let Some(state) = state {
+        let mut accounts_lock = state.accounts.write().unwrap();
+        if let Some(acc) = accounts_lock.iter_mut().find(|a| a.id == id) {
+            acc.outbound_proxy = proxy.clone();
+        }
+        if proxy.is_none() {
+            state.account_clients.lock().unwrap().remove(&id);
+        }
+    }
+    emit_accounts_updated("outbound_proxy_changed");
+    Ok(true)
+}
+
+/// Route an account to its own upstream base URL (http/https); `None` or
+/// empty goes back to the global base. Applies to the running pool at once.
+#[tauri::command]
+fn set_account_base_url(id: String, base_url: Option<String>) -> Result<bool, String> {
+    let base_url = normalize_account_base_url(base_url)?;
+    let mut meta = read_meta();
+    let entry = meta.entry(id.clone()).or_insert_with(|| MetaEntry::new(now_unix_millis()));
+    entry.base_url = base_url.clone();
+    write_meta(&meta);
+
+    let state = PROXY_STATE.lock().unwrap().clone();
+    if let Some(state) = state {
+        let mut accounts_lock = state.accounts.write().unwrap();
+        if let Some(acc) = accounts_lock.iter_mut().find(|a| a.id == id) {
+            acc.base_url = base_url;
+        }
+    }
+    emit_accounts_updated("base_url_changed");
+    Ok(true)
+}
+
+/// Assign an account to a named client profile; `None` or empty uses "default".
+#[tauri::command]
+fn set_account_client_profile(id: String, profile: Option<String>) -> Result<bool, String> {
+    let profile = profile.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    if let Some(name) = &profile {
+        if name != "default" && !proxy_config_snapshot().client_profiles.contains_key(name) {
+            return Err(format!("客户端配置不存在: {name}"));
+        }
+    }
+    let mut meta = read_meta();
+    let entry = meta.entry(id.clone()).or_insert_with(|| MetaEntry::new(now_unix_millis()));
+    entry.client_profile = profile.clone();
+    write_meta(&meta);
+
+    let state = PROXY_STATE.lock().unwrap().clone();
+    if let Some(state) = state {
+        let mut accounts_lock = state.accounts.write().unwrap();
+        if let Some(acc) = accounts_lock.iter_mut().find(|a| a.id == id) {
+            acc.org.client_profile = profile;
+        }
+    }
+    emit_accounts_updated("client_profile_changed");
+    Ok(true)
+}
+
+#[tauri::command]
+fn set_account_tags(id: String, tags: Vec<String>) -> Result<Vec<String>, String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().to_string();
+        if !tag.is_empty() && !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    let mut meta = read_meta();
+    let entry = meta.entry(id).or_insert_with(|| MetaEntry::new(now_unix_millis()));
+    entry.tags = normalized.clone();
+    write_meta(&meta);
+    emit_accounts_updated("tags_changed");
+    Ok(normalized)
+}
+
+fn account_ids_with_tag(tag: &str) -> Vec<String> {
+    let tag = tag.trim();
+    let mut ids: Vec<String> = read_meta()
+        .into_iter()
+        .filter(|(id, m)| m.tags.iter().any(|t| t == tag) && accounts_dir().join(id).join("auth.json").exists())
+        .map(|(id, _)| id)
+        .collect();
+    ids.sort();
+    ids
+}
+
+/// Refresh tokens for every account carrying `tag`, one at a time.
+#[tauri::command]
+async fn refresh_accounts_by_tag(tag: String) -> Result<Vec<Value>, String> {
+    let ids = account_ids_with_tag(&tag);
+    if ids.is_empty() {
+        return Err(format!("No accounts tagged {}", tag.trim()));
+    }
+    let meta = read_meta();
+    let mut results = Vec::new();
+    for id in ids {
+        if meta.get(&id).is_some_and(|m| m.no_refresh) {
+            results.push(serde_json::json!({ "id": id, "success": false, "skipped": true, "error": "no_refresh" }));
+            continue;
+        }
+        match refresh_account_token(id.clone()).await {
+            Ok(_) => results.push(serde_json::json!({ "id": id, "success": true })),
+            Err(err) => results.push(serde_json::json!({ "id": id, "success": false, "error": err })),
+        }
+    }
+    Ok(results)
+}
+
+/// Expand `import_label_template` (e.g. `"{email} ({plan})"`) for a newly
+/// imported account. Supports `{email}`, `{plan}`, `{id}` and `{user_id}`;
+/// None when no template is set or it expands to nothing.
+fn default_import_label(account: &CodexAccount, id: &str) -> Option<String> {
+    let template = proxy_config_snapshot().import_label_template?;
+    let label = template
+        .replace("{email}", &account.email)
+        .replace("{plan}", &account.plan)
+        .replace("{id}", id)
+        .replace("{user_id}", &account.user_id);
+    let label = label.trim().to_string();
+    if label.is_empty() {
+        None
+    } else {
+        Some(label)
+    }
+}
+
+#[tauri::command]
+fn import_current(label: Option<String>) -> Result<Value, String> {
+    let auth_path = auth_file();
+    if !auth_path.exists() {
+        return Err("No auth.json found. Please login first.".into());
+    }
+    let content = fs::read_to_string(&auth_path).map_err(|e| e.to_string())?;
+    let auth_data: Value = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+    let parsed = parse_auth_data(&auth_data, "tmp");
+
+    let safe_id = canonical_account_id(&auth_data).unwrap_or_else(|| "acc_tmp".to_string());
+    let label = label.or_else(|| default_import_label(&parsed, &safe_id));
+
+    let dest_dir = accounts_dir().join(&safe_id);
+    fs::create_dir_all(&dest_dir).map_err(|e| e.to_string())?;
+    fs::copy(&auth_path, dest_dir.join("auth.json")).map_err(|e| e.to_string())?;
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+
+    let mut meta = read_meta();
+    meta.insert(
+        safe_id.clone(),
+        MetaEntry {
+            label,
+            ..MetaEntry::new(now)
+        },
+    );
+    write_meta(&meta);
+
+    Ok(serde_json::json!({
+        "success": true,
+        "id": safe_id,
+        "email": parsed.email
+    }))
+}
+
+/// Like `import_current`, but an existing account directory is only updated
+/// when it holds the same account (same refresh token, or same user id after a
+/// token rotation). Fields already in the stored auth.json are kept unless the
+/// new file replaces them; label and meta are preserved.
+#[tauri::command]
+fn import_current_merge(label: Option<String>) -> Result<Value, String> {
+    let auth_path = auth_file();
+    if !auth_path.exists() {
+        return Err("No auth.json found. Please login first.".into());
+    }
+    let content = fs::read_to_string(&auth_path).map_err(|e| e.to_string())?;
+    let auth_data: Value = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+    let safe_id = canonical_account_id(&auth_data).unwrap_or_else(|| "acc_tmp".to_string());
+    let dest_auth = accounts_dir().join(&safe_id).join("auth.json");
+    if !dest_auth.exists() {
+        return import_current(label);
+    }
+
+    let existing_content = fs::read_to_string(&dest_auth).map_err(|e| e.to_string())?;
+    let mut existing: Value = serde_json::from_str(&existing_content)
+        .map_err(|e| format!("现有账号 {safe_id} 的 auth.json 无效: {e}"))?;
+    let incoming = parse_auth_data(&auth_data, &safe_id);
+    let stored = parse_auth_data(&existing, &safe_id);
+    let incoming_refresh = auth_refresh_token(&auth_data);
+    let same_refresh = !incoming_refresh.is_empty() && incoming_refresh == auth_refresh_token(&existing);
+    let same_user = !incoming.user_id.is_empty() && incoming.user_id == stored.user_id;
+    if !same_refresh && !same_user {
+        return Err(format!(
+            "账号 {safe_id} 已存在且属于其他账号 ({})，已取消导入",
+            if stored.email.is_empty() { "unknown" } else { stored.email.as_str() }
+        ));
+    }
+
+    match (existing.as_object_mut(), auth_data.as_object()) {
+        (Some(dst), Some(src)) => {
+            for (key, value) in src {
+                match (dst.get_mut(key), value) {
+                    (Some(Value::Object(dst_tokens)), Value::Object(src_tokens)) if key == "tokens" => {
+                        for (k, v) in src_tokens {
+                            dst_tokens.insert(k.clone(), v.clone());
+                        }
+                    }
+                    _ => {
+                        dst.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        _ => existing = auth_data.clone(),
+    }
+    let pretty = serde_json::to_string_pretty(&existing).map_err(|e| e.to_string())?;
+    fs::write(&dest_auth, pretty).map_err(|e| e.to_string())?;
+
+    if let Some(label) = label {
+        let mut meta = read_meta();
+        if let Some(entry) = meta.get_mut(&safe_id) {
+            entry.label = Some(label);
+            write_meta(&meta);
+        }
+    }
+
+    Ok(serde_json::json!({
+        "success": true,
+        "id": safe_id,
+        "email": incoming.email,
+        "merged": true
+    }))
+}
+
+/// Bulk-import auth.json files from `dir`: a loose `auth.json` (or `*.json`)
+/// in the directory itself, or `auth.json` inside each direct subdirectory.
+/// Files whose refresh token matches an already-managed account are skipped.
+/// Returns one `{path, status, id?, email?, error?}` object per file.
+#[tauri::command]
+fn import_accounts_from_dir(dir: String, label_prefix: Option<String>) -> Result<Vec<Value>, String> {
+    let root = PathBuf::from(dir.trim());
+    if !root.is_dir() {
+        return Err(format!("目录不存在: {}", root.display()));
+    }
+    let label_prefix = label_prefix.map(|p| p.trim().to_string()).filter(|p| !p.is_empty());
+
+    let mut candidates: Vec<PathBuf> = Vec::new();
+    for entry in fs::read_dir(&root).map_err(|e| e.to_string())?.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            let nested = path.join("auth.json");
+            if nested.is_file() {
+                candidates.push(nested);
+            }
+        } else if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json")) {
+            candidates.push(path);
+        }
+    }
+    candidates.sort();
+
+    // Refresh tokens already under management, so re-running the import is a no-op
+    let mut known_refresh: HashSet<String> = HashSet::new();
+    if let Ok(entries) = fs::read_dir(accounts_dir()) {
+        for entry in entries.flatten() {
+            if let Some(auth_data) = fs::read_to_string(entry.path().join("auth.json"))
+                .ok()
+                .and_then(|c| serde_json::from_str::<Value>(&c).ok())
+            {
+                let refresh = auth_refresh_token(&auth_data).trim();
+                if !refresh.is_empty() {
+                    known_refresh.insert(refresh.to_string());
+                }
+            }
+        }
+    }
+
+    let mut meta = read_meta();
+    let mut results = Vec::new();
+    let mut imported = 0usize;
+    for path in candidates {
+        let path_text = path.to_string_lossy().to_string();
+        let auth_data = match fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|c| serde_json::from_str::<Value>(&c).map_err(|e| e.to_string()))
+        {
+            Ok(v) => v,
+            Err(err) => {
+                results.push(serde_json::json!({ "path": path_text, "status": "error", "error": err }));
+                continue;
+            }
+        };
+        let (access_token, _, _) = extract_auth_tokens(&auth_data);
+        if access_token.trim().is_empty() {
+            results.push(serde_json::json!({ "path": path_text, "status": "error", "error": "missing access_token" }));
+            continue;
+        }
+        let Some(safe_id) = canonical_account_id(&auth_data) else {
+            results.push(serde_json::json!({ "path": path_text, "status": "error", "error": "cannot derive account id" }));
+            continue;
+        };
+        let parsed = parse_auth_data(&auth_data, &safe_id);
+        let refresh = auth_refresh_token(&auth_data).trim().to_string();
+        let dest_dir = accounts_dir().join(&safe_id);
+        if (!refresh.is_empty() && known_refresh.contains(&refresh)) || dest_dir.join("auth.json").exists() {
+            results.push(serde_json::json!({
+                "path": path_text,
+                "status": "duplicate",
+                "id": safe_id,
+                "email": parsed.email,
+            }));
+            continue;
+        }
+
+        if let Err(err) = fs::create_dir_all(&dest_dir).and_then(|_| fs::copy(&path, dest_dir.join("auth.json"))) {
+            results.push(serde_json::json!({ "path": path_text, "status": "error", "id": safe_id, "error": err.to_string() }));
+            continue;
+        }
+        if !refresh.is_empty() {
+            known_refresh.insert(refresh);
+        }
+        let label = label_prefix
+            .as_ref()
+            .map(|prefix| {
+                let suffix = if parsed.email.is_empty() { safe_id.as_str() } else { parsed.email.as_str() };
+                format!("{prefix} {suffix}")
+            })
+            .or_else(|| default_import_label(&parsed, &safe_id));
+        meta.insert(
+            safe_id.clone(),
+            MetaEntry {
+                label,
+                ..MetaEntry::new(now_unix_millis())
+            },
+        );
+        imported += 1;
+        results.push(serde_json::json!({
+            "path": path_text,
+            "status": "imported",
+            "id": safe_id,
+            "email": parsed.email,
+        }));
+    }
+
+    if imported > 0 {
+        write_meta(&meta);
+        emit_accounts_updated("accounts_imported");
+    }
+    Ok(results)
+}
+
+#[tauri::command]
+fn get_config() -> Result<Value, String> {
+    let config_path = codex_dir().join("config.toml");
+    if !config_path.exists() {
+        return Ok(serde_json::json!({ "raw": "" }));
+    }
+    let raw = fs::read_to_string(&config_path).map_err(|e| e.to_string())?;
+    Ok(serde_json::json!({ "raw": raw }))
+}
+
+#[tauri::command]
+fn set_config(raw: String) -> Result<bool, String> {
+    // Refuse to overwrite config.toml with content the Codex CLI cannot parse.
+    raw.parse::<toml::Table>()
+        .map_err(|e| format!("config.toml 格式错误: {e}"))?;
+    let dir = codex_dir();
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    fs::write(dir.join("config.toml"), raw).map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+// ─── Tauri commands: OAuth PKCE login ────────────────────────────────────────
 
 #[tauri::command]
 fn launch_codex_login() -> Result<Value, String> {
@@ -5097,20 +7576,51 @@ async fn complete_oauth_manual(callback_url: String, label: Option<String>) -> R
 
     let token_resp = exchange_code(code, &pending.redirect_uri, &pending.verifier).await?;
     let account = save_oauth_tokens(&token_resp)?;
-    let import_result = import_current(label)?;
+    Ok(import_after_oauth(&account, label))
+}
 
-    Ok(serde_json::json!({
-        "success": true,
-        "email": account.email,
-        "plan": account.plan,
-        "id": import_result["id"]
-    }))
+/// Import the account `save_oauth_tokens` just made active, retrying once. A
+/// second failure comes back as `imported: false` rather than an error: the
+/// login succeeded and auth.json already holds the tokens, so the UI can offer
+/// "import current account" instead of sending the user through login again.
+fn import_after_oauth(account: &CodexAccount, label: Option<String>) -> Value {
+    import_after_oauth_with(account, label, import_current)
+}
+
+/// `import_after_oauth` with the importer passed in, so the result mapping
+/// can be exercised without touching ~/.codex.
+fn import_after_oauth_with(
+    account: &CodexAccount,
+    label: Option<String>,
+    mut import: impl FnMut(Option<String>) -> Result<Value, String>,
+) -> Value {
+    match import(label.clone()).or_else(|_| import(label)) {
+        Ok(import_result) => serde_json::json!({
+            "success": true,
+            "imported": true,
+            "email": account.email,
+            "plan": account.plan,
+            "id": import_result["id"]
+        }),
+        Err(err) => serde_json::json!({
+            "success": true,
+            "imported": false,
+            "email": account.email,
+            "plan": account.plan,
+            "id": null,
+            "import_error": err
+        }),
+    }
 }
 
 /// Start in-app OAuth login flow. Opens browser, waits for callback,
 /// exchanges code, saves auth.json, returns the new account.
 #[tauri::command]
-async fn oauth_login(label: Option<String>) -> Result<Value, String> {
+async fn oauth_login(label: Option<String>, timeout_secs: Option<u64>) -> Result<Value, String> {
+    let timeout_secs = timeout_secs
+        .filter(|v| *v > 0)
+        .unwrap_or(OAUTH_LOGIN_TIMEOUT_SECS)
+        .min(OAUTH_LOGIN_MAX_TIMEOUT_SECS);
     let port = OAUTH_CALLBACK_PORT;
     let redirect_uri = format!("http://localhost:{port}/auth/callback");
     let verifier = pkce_verifier();
@@ -5126,11 +7636,33 @@ async fn oauth_login(label: Option<String>) -> Result<Value, String> {
     // Open browser
     open::that(&auth_url).map_err(|e| format!("Cannot open browser: {e}"))?;
 
-    // Bind on both IPv4 and IPv6 — macOS may resolve `localhost` to ::1
-    let listener_v4 = tokio::net::TcpListener::bind(format!("127.0.0.1:{port}"))
-        .await
-        .map_err(|e| e.to_string())?;
-    let listener_v6 = tokio::net::TcpListener::bind(format!("[::1]:{port}")).await.ok();
+    // Bind on both IPv4 and IPv6 — macOS may resolve `localhost` to ::1.
+    // A previous login attempt may still be releasing the port, so retry briefly.
+    let mut listener_v4 = None;
+    let mut listener_v6 = None;
+    let mut last_err = String::new();
+    for attempt in 0..OAUTH_BIND_ATTEMPTS {
+        if attempt > 0 {
+            tokio::time::sleep(std::time::Duration::from_millis(OAUTH_BIND_RETRY_MS)).await;
+        }
+        if listener_v4.is_none() {
+            match tokio::net::TcpListener::bind(format!("127.0.0.1:{port}")).await {
+                Ok(l) => listener_v4 = Some(l),
+                Err(e) => last_err = e.to_string(),
+            }
+        }
+        if listener_v6.is_none() {
+            listener_v6 = tokio::net::TcpListener::bind(format!("[::1]:{port}")).await.ok();
+        }
+        if listener_v4.is_some() {
+            break;
+        }
+    }
+    if listener_v4.is_none() && listener_v6.is_none() {
+        return Err(format!(
+            "Port {port} is in use (a previous login may still be running). Please wait a moment and try again. ({last_err})"
+        ));
+    }
 
     // We only need one request; use a channel to get the query string
     let (tx, rx) = tokio::sync::oneshot::channel::<String>();
@@ -5191,7 +7723,11 @@ async fn oauth_login(label: Option<String>) -> Result<Value, String> {
         let tx6 = tx.clone();
         let state4 = state.clone();
         let state6 = state.clone();
-        let v4 = tokio::spawn(handle_listener(listener_v4, tx4, state4));
+        let v4 = if let Some(l) = listener_v4 {
+            tokio::spawn(handle_listener(l, tx4, state4))
+        } else {
+            tokio::spawn(async {})
+        };
         let v6 = if let Some(l) = listener_v6 {
             tokio::spawn(handle_listener(l, tx6, state6))
         } else {
@@ -5200,14 +7736,13 @@ async fn oauth_login(label: Option<String>) -> Result<Value, String> {
         (v4, v6)
     };
 
-    // Wait for callback (max 3 minutes)
-    let qs = tokio::time::timeout(std::time::Duration::from_secs(180), rx)
-        .await
-        .map_err(|_| "Login timed out (3 min). Please try again.")?
-        .map_err(|_| "Login cancelled")?;
-
+    // Wait for callback (default 3 minutes)
+    let waited = tokio::time::timeout(std::time::Duration::from_secs(timeout_secs), rx).await;
     server.0.abort();
     server.1.abort();
+    let qs = waited
+        .map_err(|_| format!("Login timed out ({timeout_secs}s). Please try again."))?
+        .map_err(|_| "Login cancelled")?;
 
     // Parse code from query string
     let params: HashMap<_, _> = qs
@@ -5226,14 +7761,55 @@ async fn oauth_login(label: Option<String>) -> Result<Value, String> {
     let account = save_oauth_tokens(&token_resp)?;
 
     // Import into managed accounts
-    let import_result = import_current(label)?;
+    Ok(import_after_oauth(&account, label))
+}
 
-    Ok(serde_json::json!({
-        "success": true,
-        "email": account.email,
-        "plan": account.plan,
-        "id": import_result["id"]
-    }))
+/// Check that an account's stored refresh token is still accepted, without
+/// writing the returned tokens anywhere. `new_expires_at` is in milliseconds.
+/// If the server rotates refresh tokens, `refresh_token_rotated` is true and
+/// the stored token may no longer be valid afterwards.
+#[tauri::command]
+async fn verify_refresh_token(id: String) -> Result<Value, String> {
+    let auth_path = accounts_dir().join(&id).join("auth.json");
+    if !auth_path.exists() {
+        return Err(format!("Account {id} not found"));
+    }
+    // Verifying is a real refresh and may rotate the token out from under the owner
+    if read_meta().get(&id).is_some_and(|m| m.no_refresh) {
+        return Err(format!("Account {id} is marked no_refresh"));
+    }
+    let content = fs::read_to_string(&auth_path).map_err(|e| e.to_string())?;
+    let auth_data: Value = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+    let refresh_token = auth_data
+        .get("tokens")
+        .and_then(|t| t.get("refresh_token"))
+        .and_then(|v| v.as_str())
+        .ok_or("No refresh token stored for this account")?;
+
+    match do_token_refresh(refresh_token).await {
+        Ok(token_resp) => {
+            let exp = token_resp
+                .get("access_token")
+                .and_then(|v| v.as_str())
+                .map(|t| decode_jwt(t).get("exp").and_then(|v| v.as_i64()).unwrap_or(0))
+                .unwrap_or(0);
+            let rotated = token_resp
+                .get("refresh_token")
+                .and_then(|v| v.as_str())
+                .map(|rt| rt != refresh_token)
+                .unwrap_or(false);
+            Ok(serde_json::json!({
+                "ok": true,
+                "new_expires_at": if exp > 0 { Some(exp * 1000) } else { None },
+                "refresh_token_rotated": rotated,
+            }))
+        }
+        Err(err) => Ok(serde_json::json!({
+            "ok": false,
+            "new_expires_at": null,
+            "error": err,
+        })),
+    }
 }
 
 /// Refresh tokens for a specific managed account by account id.
@@ -5243,6 +7819,9 @@ async fn refresh_account_token(id: String) -> Result<Value, String> {
     if !auth_path.exists() {
         return Err(format!("Account {id} not found"));
     }
+    if read_meta().get(&id).is_some_and(|m| m.no_refresh) {
+        return Err(format!("Account {id} is marked no_refresh"));
+    }
     let content = fs::read_to_string(&auth_path).map_err(|e| e.to_string())?;
     let auth_data: Value = serde_json::from_str(&content).map_err(|e| e.to_string())?;
 
@@ -5253,7 +7832,9 @@ async fn refresh_account_token(id: String) -> Result<Value, String> {
         .and_then(|v| v.as_str())
         .ok_or("No refresh token stored for this account")?;
 
-    let token_resp = do_token_refresh(refresh_token).await?;
+    let token_resp = do_token_refresh(refresh_token).await;
+    record_refresh_attempt(&id, token_resp.is_ok());
+    let token_resp = token_resp?;
 
     // Merge new tokens, keep refresh_token if not returned
     let new_access = token_resp
@@ -5323,6 +7904,7 @@ async fn refresh_account_token(id: String) -> Result<Value, String> {
 // ─── Tauri commands: API reverse proxy ───────────────────────────────────────
 
 const COOLDOWN_SECS: u64 = 60; // 429 cooldown window
+const COOLDOWN_MAX_SECS: u64 = 15 * 60; // cap for repeated 429 backoff
 const DEFAULT_FRONT_PROXY_MAX_BODY_BYTES: usize = 16 * 1024 * 1024;
 const DEFAULT_UPSTREAM_BASE_URL: &str = "https://chatgpt.com/backend-api/codex";
 const DEFAULT_MODELS_CLIENT_VERSION: &str = "0.98.0";
@@ -5333,6 +7915,15 @@ const CODEX_ORIGINATOR: &str = "codex_cli_rs";
 const CLAUDE_CODE_SYSTEM_PROMPT: &str = "You are Claude Code, Anthropic's official CLI for Claude.";
 static PROXY_REQ_ID: AtomicUsize = AtomicUsize::new(1);
 
+/// Id for a proxied request (`x-codex-manager-request-id`, `request_logs.request_id`).
+/// The log DB is opened first so the counter is seeded from the stored ids.
+fn next_request_id() -> usize {
+    if PROXY_LOG_DB.get().is_none() {
+        let _ = proxy_log_db();
+    }
+    PROXY_REQ_ID.fetch_add(1, Ordering::SeqCst)
+}
+
 fn front_proxy_max_body_bytes() -> usize {
     std::env::var("CODEXMANAGER_FRONT_PROXY_MAX_BODY_BYTES")
         .ok()
@@ -5364,6 +7955,42 @@ fn build_upstream_url(path_and_query: &str) -> String {
     build_upstream_url_with_base(&upstream_base_url(), path_and_query)
 }
 
+/// Upstream URL for pool account `idx`: its own `base_url` when set,
+/// otherwise the global base.
+fn account_upstream_url(state: &ProxyState, idx: usize, path_and_query: &str) -> String {
+    let base = state.accounts.read().unwrap().get(idx).and_then(|a| a.base_url.clone());
+    match base {
+        Some(base) => build_upstream_url_with_base(&base, path_and_query),
+        None => build_upstream_url(path_and_query),
+    }
+}
+
+/// True when `target` resolves to a loopback/unspecified address on `port`,
+/// i.e. the upstream would be this proxy itself.
+fn upstream_url_targets_local_port(target: &str, port: u16) -> bool {
+    let Ok(url) = reqwest::Url::parse(target) else {
+        return false;
+    };
+    if url.port_or_known_default() != Some(port) {
+        return false;
+    }
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    match host.parse::<std::net::IpAddr>() {
+        Ok(ip) => ip.is_loopback() || ip.is_unspecified(),
+        Err(_) => host.eq_ignore_ascii_case("localhost"),
+    }
+}
+
+fn upstream_loops_back_to_proxy(target: &str) -> bool {
+    match *PROXY_PORT.lock().unwrap() {
+        Some(port) => upstream_url_targets_local_port(target, port),
+        None => false,
+    }
+}
+
 fn custom_openai_base_url(cfg: &ProxyConfig) -> Option<String> {
     normalized_custom_base_url(cfg.custom_openai_base_url.as_ref())
 }
@@ -5442,10 +8069,55 @@ fn random_session_id() -> String {
     )
 }
 
+/// Inbound session_id under `strict_session_affinity`: kept only when the
+/// request carries the same conversation_id, replaced by one derived from the
+/// conversation when they disagree, and None (so the caller falls back to the
+/// sticky/random id) when either header is missing.
+fn strict_inbound_session_id(session_id: Option<&str>, conversation_id: Option<&str>) -> Option<String> {
+    match (session_id, conversation_id) {
+        (Some(session), Some(conversation)) if session.trim() == conversation.trim() => Some(session.to_string()),
+        (Some(_), Some(conversation)) => derive_session_id(Some(conversation.trim()), "conversation"),
+        _ => None,
+    }
+}
+
+/// Streams always ask for SSE. Otherwise an explicit, non-wildcard client
+/// `Accept` is kept and `*/*` or a missing header falls back to JSON.
+fn upstream_accept_value(incoming: Option<&str>, is_stream: bool) -> reqwest::header::HeaderValue {
+    if is_stream {
+        return reqwest::header::HeaderValue::from_static("text/event-stream");
+    }
+    let explicit = incoming
+        .map(str::trim)
+        .filter(|v| !v.is_empty() && *v != "*/*")
+        .filter(|v| !v.to_ascii_lowercase().contains("text/event-stream"))
+        .and_then(|v| reqwest::header::HeaderValue::from_str(v).ok());
+    explicit.unwrap_or_else(|| reqwest::header::HeaderValue::from_static("application/json"))
+}
+
+fn default_client_profile() -> ClientProfile {
+    ClientProfile {
+        version: CODEX_CLIENT_VERSION.to_string(),
+        user_agent: CODEX_USER_AGENT.to_string(),
+        originator: CODEX_ORIGINATOR.to_string(),
+    }
+}
+
+/// The configured profile called `name`; unknown or unset names fall back to
+/// "default" (which may itself be overridden in `client_profiles`).
+fn resolve_client_profile(name: Option<&str>) -> ClientProfile {
+    let cfg = proxy_config_snapshot();
+    name.and_then(|n| cfg.client_profiles.get(n))
+        .or_else(|| cfg.client_profiles.get("default"))
+        .cloned()
+        .unwrap_or_else(default_client_profile)
+}
+
 fn apply_upstream_headers(
     headers: &mut reqwest::header::HeaderMap,
     auth_token: &str,
     account_id: Option<&str>,
+    org: Option<&AccountUpstreamIdentity>,
     incoming_headers: &axum::http::HeaderMap,
     has_body: bool,
     is_stream: bool,
@@ -5469,10 +8141,15 @@ fn apply_upstream_headers(
             "session",
         )
     };
+    let honored_session_id = if proxy_config_snapshot().strict_session_affinity {
+        strict_inbound_session_id(incoming_session_id, incoming_conversation_id)
+    } else {
+        incoming_session_id.map(|v| v.to_string())
+    };
     let resolved_session_id = if strip_session_affinity {
         random_session_id()
-    } else if let Some(value) = incoming_session_id {
-        value.to_string()
+    } else if let Some(value) = honored_session_id {
+        value
     } else if let Some(value) = &fallback_session_id {
         value.clone()
     } else {
@@ -5492,19 +8169,20 @@ fn apply_upstream_headers(
     }
     headers.insert(
         reqwest::header::ACCEPT,
-        reqwest::header::HeaderValue::from_static(if is_stream {
-            "text/event-stream"
-        } else {
-            "application/json"
-        }),
+        upstream_accept_value(
+            incoming_headers.get(axum::http::header::ACCEPT).and_then(|v| v.to_str().ok()),
+            is_stream,
+        ),
     );
     headers.insert(
         reqwest::header::CONNECTION,
         reqwest::header::HeaderValue::from_static("Keep-Alive"),
     );
+    let profile = resolve_client_profile(org.and_then(|o| o.client_profile.as_deref()));
     headers.insert(
         reqwest::header::HeaderName::from_static("version"),
-        reqwest::header::HeaderValue::from_static(CODEX_CLIENT_VERSION),
+        reqwest::header::HeaderValue::from_str(&profile.version)
+            .unwrap_or_else(|_| reqwest::header::HeaderValue::from_static(CODEX_CLIENT_VERSION)),
     );
     headers.insert(
         reqwest::header::HeaderName::from_static("openai-beta"),
@@ -5512,11 +8190,13 @@ fn apply_upstream_headers(
     );
     headers.insert(
         reqwest::header::USER_AGENT,
-        reqwest::header::HeaderValue::from_static(CODEX_USER_AGENT),
+        reqwest::header::HeaderValue::from_str(&profile.user_agent)
+            .unwrap_or_else(|_| reqwest::header::HeaderValue::from_static(CODEX_USER_AGENT)),
     );
     headers.insert(
         reqwest::header::HeaderName::from_static("originator"),
-        reqwest::header::HeaderValue::from_static(CODEX_ORIGINATOR),
+        reqwest::header::HeaderValue::from_str(&profile.originator)
+            .unwrap_or_else(|_| reqwest::header::HeaderValue::from_static(CODEX_ORIGINATOR)),
     );
     headers.insert(
         reqwest::header::HeaderName::from_static("session_id"),
@@ -5539,6 +8219,18 @@ fn apply_upstream_headers(
                 .unwrap_or_else(|_| reqwest::header::HeaderValue::from_static("")),
         );
     }
+    if let Some(org) = org {
+        if let Some(organization) = &org.organization {
+            if let Ok(value) = reqwest::header::HeaderValue::from_str(organization) {
+                headers.insert(reqwest::header::HeaderName::from_static("openai-organization"), value);
+            }
+        }
+        if let Some(project) = &org.project {
+            if let Ok(value) = reqwest::header::HeaderValue::from_str(project) {
+                headers.insert(reqwest::header::HeaderName::from_static("openai-project"), value);
+            }
+        }
+    }
     if let Some(cookie) = upstream_cookie() {
         headers.insert(
             reqwest::header::COOKIE,
@@ -5581,7 +8273,7 @@ async fn serve_proxy_on_listener(
     app: axum::Router,
     shutdown: Arc<Notify>,
 ) -> io::Result<()> {
-    axum::serve(listener, app)
+    axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>())
         .with_graceful_shutdown(async move {
             shutdown.notified().await;
         })
@@ -5589,11 +8281,19 @@ async fn serve_proxy_on_listener(
         .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
 }
 
+/// Bind `addr` and serve `app` until `shutdown`. When given, `bindings`
+/// receives the local address of every listener that bound successfully.
 async fn run_proxy_server(
     addr: &str,
     app: axum::Router,
     shutdown: Arc<Notify>,
+    bindings: Option<&Mutex<Vec<String>>>,
 ) -> io::Result<()> {
+    let record = |listener: &tokio::net::TcpListener| {
+        if let (Some(bindings), Ok(local)) = (bindings, listener.local_addr()) {
+            bindings.lock().unwrap().push(local.to_string());
+        }
+    };
     // Bind dual-stack when using localhost.
     let addr_trimmed = addr.trim();
     if addr_trimmed.len() > "localhost:".len()
@@ -5606,6 +8306,8 @@ async fn run_proxy_server(
         return match (v4, v6) {
             (Ok(v4_listener), Ok(v6_listener)) => {
                 log_proxy(&format!("bound listeners: 127.0.0.1:{port}, [::1]:{port}"));
+                record(&v4_listener);
+                record(&v6_listener);
                 let v4_task = serve_proxy_on_listener(v4_listener, app.clone(), shutdown.clone());
                 let v6_task = serve_proxy_on_listener(v6_listener, app, shutdown);
                 let (v4_result, v6_result) = tokio::join!(v4_task, v6_task);
@@ -5613,6 +8315,7 @@ async fn run_proxy_server(
             }
             (Ok(listener), Err(_)) | (Err(_), Ok(listener)) => {
                 log_proxy(&format!("bound listener: {addr_trimmed} (single stack)"));
+                record(&listener);
                 serve_proxy_on_listener(listener, app, shutdown).await
             }
             (Err(err), Err(_)) => Err(err),
@@ -5622,6 +8325,7 @@ async fn run_proxy_server(
     log_proxy(&format!("binding listener: {addr_trimmed}"));
     let listener = tokio::net::TcpListener::bind(addr_trimmed).await?;
     log_proxy(&format!("bound listener: {addr_trimmed}"));
+    record(&listener);
     serve_proxy_on_listener(listener, app, shutdown).await
 }
 
@@ -5651,20 +8355,40 @@ fn load_proxy_accounts() -> Result<Vec<ProxyAccount>, String> {
                 let empty = Value::Object(Default::default());
                 let tokens = auth_data.get("tokens").unwrap_or(&empty);
                 if let Some(access_token) = tokens.get("access_token").and_then(|v| v.as_str()) {
+                    // `no_refresh` accounts enter the pool without a refresh token,
+                    // so a 401 blocks them instead of rotating the token
+                    let no_refresh = meta.get(&id).is_some_and(|m| m.no_refresh);
                     let refresh_token = tokens
                         .get("refresh_token")
                         .and_then(|v| v.as_str())
+                        .filter(|_| !no_refresh)
                         .map(|s| s.to_string());
                     let account_id = tokens
                         .get("account_id")
                         .and_then(|v| v.as_str())
                         .map(|s| s.to_string());
+                    let org = meta
+                        .get(&id)
+                        .map(|m| AccountUpstreamIdentity {
+                            organization: m.organization.clone(),
+                            project: m.project.clone(),
+                            client_profile: m.client_profile.clone(),
+                        })
+                        .unwrap_or_default();
+                    let outbound_proxy = meta.get(&id).and_then(|m| m.outbound_proxy.clone());
+                    let base_url = meta.get(&id).and_then(|m| m.base_url.clone());
                     pool.push(ProxyAccount {
                         id,
                         account_id,
                         access_token: access_token.to_string(),
                         refresh_token,
                         health: AccountHealth::Active,
+                        org,
+                        cooldown_streak: 0,
+                        outbound_proxy,
+                        access_expires_at: access_token_exp(access_token),
+                        last_used_at: None,
+                        base_url,
                     });
                 }
             }
@@ -5687,6 +8411,287 @@ fn skip_request_header(name: &str) -> bool {
     )
 }
 
+/// Headers that are never forwarded upstream, even when listed in `forward_headers_override`.
+fn is_protected_request_header(name: &str) -> bool {
+    matches!(name.to_lowercase().as_str(),
+        "host" | "authorization" | "proxy-authorization" | "content-length" | "transfer-encoding"
+    )
+}
+
+/// `skip_request_header` plus the user-configured strip and force-forward lists.
+fn skip_request_header_with_config(name: &str, cfg: &ProxyConfig) -> bool {
+    let lower = name.to_lowercase();
+    if cfg.extra_stripped_headers.iter().any(|h| h == &lower) {
+        return true;
+    }
+    if !is_protected_request_header(&lower) && cfg.forward_headers_override.iter().any(|h| h == &lower) {
+        return false;
+    }
+    skip_request_header(&lower)
+}
+
+/// `X-Forwarded-For` with `peer` appended to any chain the client already sent.
+fn forwarded_for_value(incoming: &axum::http::HeaderMap, peer: std::net::IpAddr) -> String {
+    let chain: Vec<&str> = incoming
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .collect();
+    if chain.is_empty() {
+        peer.to_string()
+    } else {
+        format!("{}, {peer}", chain.join(", "))
+    }
+}
+
+fn normalize_header_list(values: Vec<String>) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for value in values {
+        let name = value.trim().to_ascii_lowercase();
+        if name.is_empty() || reqwest::header::HeaderName::from_bytes(name.as_bytes()).is_err() {
+            continue;
+        }
+        if !out.contains(&name) {
+            out.push(name);
+        }
+    }
+    out
+}
+
+/// Random starting offset for round-robin selection so a fresh pool doesn't
+/// always hand the first request to account 0. `CODEXMANAGER_RR_SEED` pins it.
+fn round_robin_seed(pool_size: usize) -> usize {
+    if let Some(seed) = std::env::var("CODEXMANAGER_RR_SEED")
+        .ok()
+        .and_then(|v| v.trim().parse::<usize>().ok())
+    {
+        return seed;
+    }
+    if pool_size == 0 {
+        return 0;
+    }
+    rand::thread_rng().next_u32() as usize % pool_size
+}
+
+fn proxy_max_response_bytes() -> Option<usize> {
+    proxy_config().lock().unwrap().max_response_bytes
+}
+
+/// Upstream body stream that ends with an error once `max_response_bytes` is exceeded.
+fn capped_bytes_stream(
+    upstream_resp: reqwest::Response,
+) -> std::pin::Pin<Box<dyn futures_util::Stream<Item = Result<Bytes, io::Error>> + Send>> {
+    let cap = proxy_max_response_bytes();
+    let upstream = upstream_resp.bytes_stream();
+    Box::pin(futures_util::stream::unfold(
+        (upstream, 0usize, false),
+        move |(mut upstream, mut total, stopped)| async move {
+            if stopped {
+                return None;
+            }
+            match upstream.next().await {
+                Some(Ok(chunk)) => {
+                    total += chunk.len();
+                    if let Some(cap) = cap {
+                        if total > cap {
+                            log_proxy(&format!("response truncated: exceeded max_response_bytes={cap}"));
+                            let err = io::Error::other(format!("response exceeded {cap} bytes"));
+                            return Some((Err(err), (upstream, total, true)));
+                        }
+                    }
+                    Some((Ok(chunk), (upstream, total, false)))
+                }
+                Some(Err(err)) => Some((Err(io::Error::other(err)), (upstream, total, true))),
+                None => None,
+            }
+        },
+    ))
+}
+
+/// Interleave `: keep-alive` SSE comments into `stream` after `interval` of silence.
+/// Heartbeats are only emitted on an event boundary (after a blank line) so a
+/// partially forwarded event is never split.
+fn with_sse_heartbeat(
+    stream: std::pin::Pin<Box<dyn futures_util::Stream<Item = Result<Bytes, io::Error>> + Send>>,
+    interval: std::time::Duration,
+) -> std::pin::Pin<Box<dyn futures_util::Stream<Item = Result<Bytes, io::Error>> + Send>> {
+    // State: (stream, last byte was '\n', at event boundary)
+    Box::pin(futures_util::stream::unfold(
+        (stream, false, true),
+        move |(mut stream, ends_nl, at_boundary)| async move {
+            loop {
+                match tokio::time::timeout(interval, stream.next()).await {
+                    Ok(Some(Ok(chunk))) => {
+                        if chunk.is_empty() {
+                            return Some((Ok(chunk), (stream, ends_nl, at_boundary)));
+                        }
+                        let boundary = chunk.ends_with(b"\n\n") || (ends_nl && chunk.as_ref() == b"\n");
+                        let last_nl = chunk.ends_with(b"\n");
+                        return Some((Ok(chunk), (stream, last_nl, boundary)));
+                    }
+                    Ok(Some(Err(err))) => return Some((Err(err), (stream, ends_nl, at_boundary))),
+                    Ok(None) => return None,
+                    Err(_) if at_boundary => {
+                        return Some((Ok(Bytes::from_static(b": keep-alive\n\n")), (stream, true, true)));
+                    }
+                    Err(_) => continue,
+                }
+            }
+        },
+    ))
+}
+
+/// Running totals for an SSE body on its way to the client.
+#[derive(Default)]
+struct StreamMetrics {
+    events: i64,
+    bytes: i64,
+    // First bytes of the current line, enough to spot a `data:` prefix across chunks
+    line_start: Vec<u8>,
+}
+
+impl StreamMetrics {
+    fn observe(&mut self, chunk: &[u8]) {
+        self.bytes += chunk.len() as i64;
+        for &b in chunk {
+            if b == b'\n' {
+                self.line_start.clear();
+            } else if self.line_start.len() < 5 {
+                self.line_start.push(b);
+                if self.line_start == b"data:" {
+                    self.events += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Wrap an SSE response body so its event count and size are written to the
+/// log row for `request_id` once the client has received the whole stream.
+fn with_stream_metrics(body: axum::body::Body, request_id: u64) -> axum::body::Body {
+    axum::body::Body::from_stream(futures_util::stream::unfold(
+        (body.into_data_stream(), StreamMetrics::default(), false),
+        move |(mut stream, mut metrics, failed)| async move {
+            if failed {
+                return None;
+            }
+            match stream.next().await {
+                Some(Ok(chunk)) => {
+                    metrics.observe(&chunk);
+                    Some((Ok(chunk), (stream, metrics, false)))
+                }
+                Some(Err(err)) => Some((Err(err), (stream, metrics, true))),
+                None => {
+                    let _ = update_proxy_log_stream_metrics(request_id, metrics.events, metrics.bytes);
+                    None
+                }
+            }
+        },
+    ))
+}
+
+/// Status and error code for a failed upstream send: a timeout is 504 so
+/// clients know the request may still have run, and a connect timeout is 504
+/// too; a refused/failed connection is 502 like any other transport error.
+fn classify_upstream_error(err: &reqwest::Error) -> (axum::http::StatusCode, &'static str) {
+    if err.is_timeout() && err.is_connect() {
+        (axum::http::StatusCode::GATEWAY_TIMEOUT, "upstream_connect_timeout")
+    } else if err.is_timeout() {
+        (axum::http::StatusCode::GATEWAY_TIMEOUT, "upstream_timeout")
+    } else if err.is_connect() {
+        (axum::http::StatusCode::BAD_GATEWAY, "upstream_connect_error")
+    } else {
+        (axum::http::StatusCode::BAD_GATEWAY, "upstream_error")
+    }
+}
+
+const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 10;
+
+/// TCP/TLS connect budget for upstream clients (`connect_timeout_secs`,
+/// default 10s), so an unreachable host fails well before the total timeout.
+fn upstream_connect_timeout() -> std::time::Duration {
+    let secs = proxy_config_snapshot().connect_timeout_secs.unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECS);
+    std::time::Duration::from_secs(secs.max(1))
+}
+
+/// Read a whole upstream body, honouring `max_response_bytes`.
+async fn read_capped_bytes(upstream_resp: reqwest::Response) -> Result<Bytes, String> {
+    let Some(cap) = proxy_max_response_bytes() else {
+        return upstream_resp.bytes().await.map_err(|e| e.to_string());
+    };
+    if upstream_resp.content_length().is_some_and(|len| len > cap as u64) {
+        log_proxy(&format!("response rejected: content-length exceeds max_response_bytes={cap}"));
+        return Err(format!("response exceeded {cap} bytes"));
+    }
+    let mut stream = upstream_resp.bytes_stream();
+    let mut buf = bytes::BytesMut::new();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| e.to_string())?;
+        if buf.len() + chunk.len() > cap {
+            log_proxy(&format!("response truncated: exceeded max_response_bytes={cap}"));
+            return Err(format!("response exceeded {cap} bytes"));
+        }
+        buf.extend_from_slice(&chunk);
+    }
+    Ok(buf.freeze())
+}
+
+/// Decode a client request body sent with `content-encoding` so it can be
+/// inspected and rewritten before forwarding. The decoded size is capped at `limit`.
+fn decode_request_body(encoding: Option<&str>, body: Bytes, limit: usize) -> Result<Bytes, String> {
+    use std::io::Read;
+
+    let encoding = encoding.map(|e| e.trim().to_ascii_lowercase()).unwrap_or_default();
+    let mut decoded = Vec::new();
+    let read = match encoding.as_str() {
+        "" | "identity" => return Ok(body),
+        "gzip" | "x-gzip" => flate2::read::GzDecoder::new(body.as_ref())
+            .take(limit as u64 + 1)
+            .read_to_end(&mut decoded),
+        "deflate" => flate2::read::ZlibDecoder::new(body.as_ref())
+            .take(limit as u64 + 1)
+            .read_to_end(&mut decoded),
+        other => return Err(format!("Unsupported content-encoding: {other}")),
+    };
+    read.map_err(|e| format!("Invalid {encoding} request body: {e}"))?;
+    if decoded.len() > limit {
+        return Err("Request body too large".to_string());
+    }
+    Ok(Bytes::from(decoded))
+}
+
+/// `origin` matches an `allowed_origins` entry: an exact origin, `*`, or a
+/// leading-`*` wildcard matched by suffix (e.g. `*.example.com`).
+fn origin_allowed(origin: &str, allowed: &[String]) -> bool {
+    let origin = origin.trim().to_ascii_lowercase();
+    allowed.iter().any(|entry| match entry.strip_prefix('*') {
+        Some(suffix) => origin.ends_with(suffix),
+        None => *entry == origin,
+    })
+}
+
+/// Rewrite `Access-Control-Allow-Origin` for `allowed_origins`. An empty list
+/// keeps the default `*`; otherwise a matching `Origin` is echoed back and any
+/// other origin gets no CORS header at all.
+fn apply_cors_policy(
+    headers: &mut axum::http::HeaderMap,
+    origin: Option<&axum::http::HeaderValue>,
+    cfg: &ProxyConfig,
+) {
+    if cfg.allowed_origins.is_empty() {
+        return;
+    }
+    headers.remove(axum::http::header::ACCESS_CONTROL_ALLOW_ORIGIN);
+    headers.append(axum::http::header::VARY, axum::http::HeaderValue::from_static("Origin"));
+    if let Some(origin) = origin {
+        if origin.to_str().is_ok_and(|o| origin_allowed(o, &cfg.allowed_origins)) {
+            headers.insert(axum::http::header::ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone());
+        }
+    }
+}
+
 /// Headers that should NOT be forwarded back to client
 fn skip_response_header(name: &str) -> bool {
     matches!(name.to_lowercase().as_str(),
@@ -5695,11 +8700,114 @@ fn skip_response_header(name: &str) -> bool {
     )
 }
 
+/// `skip_response_header` plus the user-configured `strip_response_headers`,
+/// minus `passthrough_response_headers` (e.g. `content-encoding`/`content-type`
+/// for clients that need them exactly as upstream sent them). The defaults
+/// always apply, so a stale `content-length` never reaches the client.
+fn skip_response_header_with_config(name: &str, cfg: &ProxyConfig) -> bool {
+    let lower = name.to_lowercase();
+    if skip_response_header(&lower) {
+        return true;
+    }
+    if cfg.passthrough_response_headers.iter().any(|h| h == &lower) {
+        return false;
+    }
+    cfg.strip_response_headers.iter().any(|h| h == &lower)
+}
+
+/// Upstream response headers that reach the client, shared by the streamed and
+/// buffered response builders.
+fn forwarded_response_headers(
+    headers: &reqwest::header::HeaderMap,
+    cfg: &ProxyConfig,
+) -> Vec<(axum::http::HeaderName, axum::http::HeaderValue)> {
+    headers
+        .iter()
+        .filter(|(k, _)| !skip_response_header_with_config(k.as_str(), cfg))
+        .filter_map(|(k, v)| {
+            let name = axum::http::HeaderName::from_bytes(k.as_str().as_bytes()).ok()?;
+            let val = axum::http::HeaderValue::from_bytes(v.as_bytes()).ok()?;
+            Some((name, val))
+        })
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+struct RefreshStats {
+    attempts: u64,
+    successes: u64,
+    last_attempt_at: Option<String>,
+    last_refresh_at: Option<String>,
+}
+
+static REFRESH_STATS: OnceLock<Mutex<HashMap<String, RefreshStats>>> = OnceLock::new();
+
+fn refresh_stats() -> &'static Mutex<HashMap<String, RefreshStats>> {
+    REFRESH_STATS.get_or_init(|| {
+        let stats = fs::read_to_string(refresh_stats_path())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Mutex::new(stats)
+    })
+}
+
+/// Count a token refresh attempt for `account_id` and persist the counters.
+/// Refreshes are rare enough that rewriting the file each time is fine.
+fn record_refresh_attempt(account_id: &str, success: bool) {
+    let now_iso = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string();
+    let mut stats = refresh_stats().lock().unwrap();
+    let entry = stats.entry(account_id.to_string()).or_default();
+    entry.attempts += 1;
+    entry.last_attempt_at = Some(now_iso.clone());
+    if success {
+        entry.successes += 1;
+        entry.last_refresh_at = Some(now_iso);
+    }
+    let path = refresh_stats_path();
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Ok(content) = serde_json::to_string_pretty(&*stats) {
+        let _ = fs::write(path, content);
+    }
+}
+
+/// Per-account token refresh attempt/success counts, lowest success rate first,
+/// so accounts whose refresh tokens are degrading stand out before they fail.
+#[tauri::command]
+fn get_refresh_stats() -> Result<Vec<Value>, String> {
+    let stats = refresh_stats().lock().unwrap();
+    let mut rows: Vec<(f64, Value)> = stats
+        .iter()
+        .map(|(id, s)| {
+            let success_rate = if s.attempts > 0 { s.successes as f64 / s.attempts as f64 } else { 1.0 };
+            let row = serde_json::json!({
+                "id": id,
+                "attempts": s.attempts,
+                "successes": s.successes,
+                "success_rate": success_rate,
+                "last_attempt_at": s.last_attempt_at,
+                "last_refresh_at": s.last_refresh_at,
+            });
+            (success_rate, row)
+        })
+        .collect();
+    rows.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+    Ok(rows.into_iter().map(|(_, row)| row).collect())
+}
+
 /// Try to refresh the token for an account and persist to disk, returns new access_token on success
-async fn try_refresh_account(account_id: &str, refresh_token: &str) -> Option<String> {
-    let token_resp = do_token_refresh(refresh_token).await.ok()?;
+async fn try_refresh_account(account_id: &str, refresh_token: &str) -> Result<String, String> {
+    let token_resp = do_token_refresh(refresh_token).await;
+    record_refresh_attempt(account_id, token_resp.is_ok());
+    let token_resp = token_resp?;
 
-    let new_access = token_resp.get("access_token").and_then(|v| v.as_str())?.to_string();
+    let new_access = token_resp
+        .get("access_token")
+        .and_then(|v| v.as_str())
+        .ok_or("Refresh response missing access_token")?
+        .to_string();
     let new_refresh = token_resp
         .get("refresh_token")
         .and_then(|v| v.as_str())
@@ -5751,7 +8859,117 @@ async fn try_refresh_account(account_id: &str, refresh_token: &str) -> Option<St
         }
     }
 
-    Some(new_access)
+    Ok(new_access)
+}
+
+/// A refresh the auth server rejected outright (revoked / reused refresh token),
+/// as opposed to a network blip or 5xx that may succeed later.
+fn is_definitive_refresh_failure(err: &str) -> bool {
+    err.contains("invalid_grant")
+        || err.starts_with("Refresh failed (400")
+        || err.starts_with("Refresh failed (401")
+}
+
+fn set_pool_token(state: &ProxyState, idx: usize, token: &str) {
+    let mut accounts_lock = state.accounts.write().unwrap();
+    if let Some(acc) = accounts_lock.get_mut(idx) {
+        acc.access_token = token.to_string();
+        acc.access_expires_at = access_token_exp(token);
+    }
+}
+
+/// Proactive refresh: a token expiring within `refresh_lead_secs` is refreshed
+/// before the send. Returns the new token, or None to go on with the current one.
+async fn refresh_before_send(
+    state: &ProxyState,
+    request_id: usize,
+    idx: usize,
+    id: &str,
+    refresh: Option<&String>,
+) -> Option<String> {
+    let cfg = proxy_config_snapshot();
+    let (Some(lead), Some(rt)) = (cfg.refresh_lead_secs.filter(|_| cfg.auto_refresh), refresh) else {
+        return None;
+    };
+    let expires_at = state.accounts.read().unwrap().get(idx).and_then(|a| a.access_expires_at);
+    let due = expires_at.is_some_and(|exp| exp - chrono::Utc::now().timestamp() <= lead as i64);
+    // Refresh tokens rotate, so only one request per account may refresh at a time
+    if !due || !refreshing_accounts().lock().unwrap().insert(id.to_string()) {
+        return None;
+    }
+    log_proxy(&format!("req#{request_id} proactive refresh for {id} (exp={})", expires_at.unwrap_or_default()));
+    let refreshed = try_refresh_account(id, rt).await;
+    refreshing_accounts().lock().unwrap().remove(id);
+    match refreshed {
+        Ok(new_token) => {
+            set_pool_token(state, idx, &new_token);
+            Some(new_token)
+        }
+        // Go on with the old token; a 401 still goes through `refresh_after_unauthorized`
+        Err(err) => {
+            log_proxy(&format!("req#{request_id} proactive refresh failed for {id}: {err}"));
+            None
+        }
+    }
+}
+
+/// After an upstream 401: refresh the token and return it, or block the account
+/// (dropping it under `drop_dead_accounts` when the refresh token was rejected).
+async fn refresh_after_unauthorized(
+    state: &ProxyState,
+    request_id: usize,
+    idx: usize,
+    id: &str,
+    refresh: Option<&String>,
+) -> Option<String> {
+    let cfg = proxy_config_snapshot();
+    if !cfg.auto_refresh {
+        log_proxy(&format!("req#{request_id} 401 on {id}, auto_refresh disabled, passing through"));
+    }
+    let mut refresh_error = None;
+    if let Some(rt) = refresh.filter(|_| cfg.auto_refresh) {
+        match try_refresh_account(id, rt).await {
+            Ok(new_token) => {
+                set_pool_token(state, idx, &new_token);
+                transition_account_health(state, idx, AccountHealth::Active, "token refreshed", Some(401));
+                return Some(new_token);
+            }
+            Err(err) => {
+                log_proxy(&format!("req#{request_id} token refresh failed for {id}: {err}"));
+                refresh_error = Some(err);
+            }
+        }
+    }
+    let reason = if !cfg.auto_refresh {
+        "unauthorized (auto refresh disabled)"
+    } else if refresh.is_none() {
+        "unauthorized (no refresh token)"
+    } else {
+        "token refresh failed"
+    };
+    transition_account_health(state, idx, AccountHealth::Blocked, reason, Some(401));
+    if cfg.drop_dead_accounts && refresh_error.as_deref().is_some_and(is_definitive_refresh_failure) {
+        drop_pool_account(state, id);
+        log_proxy(&format!("req#{request_id} dropped {id} from the pool: refresh token rejected"));
+    }
+    None
+}
+
+/// After an upstream 429: a usage-limit body keeps the account out until its
+/// reset time (`apply_usage_limit_policy`), anything else gets the backoff
+/// cooldown. Returns whether it was a usage limit.
+fn record_rate_limit(state: &Arc<ProxyState>, idx: usize, id: &str, body: &[u8]) -> bool {
+    match parse_usage_limit_error(body) {
+        Some((resets_at, resets_in_seconds)) => {
+            apply_usage_limit_policy(state, idx, id, usage_limit_cooldown_until(resets_at, resets_in_seconds));
+            true
+        }
+        None => {
+            let until = backoff_cooldown_until(state, idx);
+            transition_account_health(state, idx, AccountHealth::Cooldown(until), "rate limited", Some(429));
+            false
+        }
+    }
 }
 
 /// Start a local HTTP server that proxies OpenAI API requests with multi-account round-robin,
@@ -5801,6 +9019,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
     log_proxy("building reqwest client");
     let client = reqwest::Client::builder()
         .timeout(std::time::Duration::from_secs(120))
+        .connect_timeout(upstream_connect_timeout())
         .build()
         .map_err(|e| {
             log_proxy(&format!("reqwest client build failed: {e}"));
@@ -5812,7 +9031,23 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
     let proxy_state = Arc::new(ProxyState {
         client,
         accounts: Arc::new(RwLock::new(accounts)),
-        req_counter: AtomicUsize::new(0),
+        req_counter: AtomicUsize::new(round_robin_seed(account_count)),
+        models_cache: Mutex::new(None),
+        models_refreshing: AtomicBool::new(false),
+        total_requests: AtomicUsize::new(0),
+        total_errors: AtomicUsize::new(0),
+        started_at: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        last_request_at: Mutex::new(std::time::Instant::now()),
+        account_clients: Mutex::new(HashMap::new()),
+        dropped_accounts: Mutex::new(Vec::new()),
+        pool_tag: Mutex::new(None),
+        get_cache: Mutex::new(std::collections::VecDeque::new()),
+        get_cache_hits: AtomicUsize::new(0),
+        get_cache_misses: AtomicUsize::new(0),
+        circuit: Mutex::new(CircuitBreaker::default()),
+        idempotency_cache: Mutex::new(std::collections::VecDeque::new()),
+        idempotency_inflight: Mutex::new(HashMap::new()),
+        bindings: Mutex::new(Vec::new()),
     });
     log_proxy("proxy state ready");
 
@@ -5821,7 +9056,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
         request_id: usize,
         initial_idx: usize,
         method: &reqwest::Method,
-        target: &str,
+        upstream_path: &str,
         forward_headers: &reqwest::header::HeaderMap,
         req_headers: &axum::http::HeaderMap,
         upstream_body_bytes: &Bytes,
@@ -5829,6 +9064,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
         is_anthropic: bool,
         request_model: &Option<String>,
         request_body_text: &Option<String>,
+        request_body_truncated: bool,
         request_headers_json: &Option<String>,
         request_url: &Option<String>,
         anthropic_reverse_tool_map: &Option<HashMap<String, String>>,
@@ -5836,11 +9072,12 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
         path: &str,
         started_at: std::time::Instant,
     ) -> Option<Response<Body>> {
+        let cfg = proxy_config_snapshot();
         let mut attempted: HashSet<usize> = HashSet::new();
         attempted.insert(initial_idx);
 
         loop {
-            let Some((fallback_idx, fallback_id, fallback_token, fallback_account_id)) =
+            let Some((fallback_idx, fallback_id, fallback_token, fallback_account_id, fallback_org)) =
                 select_highest_quota_account_excluding(state.clone(), &attempted).await
             else {
                 return None;
@@ -5852,13 +9089,19 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                 &mut retry_headers,
                 &fallback_token,
                 fallback_account_id.as_deref(),
+                Some(&fallback_org),
                 req_headers,
                 !upstream_body_bytes.is_empty(),
                 is_stream,
             );
 
-            let retry_resp = match state.client
-                .request(method.clone(), target)
+            let target = account_upstream_url(&state, fallback_idx, upstream_path);
+            if upstream_loops_back_to_proxy(&target) {
+                log_proxy(&format!("req#{request_id} usage-limit retry skipped {fallback_id}: {target} points back at this proxy"));
+                continue;
+            }
+            let retry_resp = match account_client(&state, fallback_idx)
+                .request(method.clone(), &target)
                 .headers(retry_headers)
                 .body(upstream_body_bytes.clone())
                 .send()
@@ -5873,6 +9116,10 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
 
             let retry_status = retry_resp.status();
             log_proxy(&format!("req#{request_id} usage-limit retry status: {}", retry_status.as_u16()));
+            if retry_status.is_success() {
+                reset_cooldown_streak(&state, fallback_idx);
+                note_account_used(&state, fallback_idx);
+            }
 
             if is_stream {
                 if is_anthropic {
@@ -5892,7 +9139,9 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                             ("content-type".to_string(), "text/event-stream".to_string()),
                         ]),
                         request_body: request_body_text.clone(),
+                        request_body_truncated,
                         response_body: None,
+                        response_body_truncated: false,
                         input_tokens: None,
                         output_tokens: None,
                     cache_status: None,
@@ -5901,6 +9150,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                     cache_bypass_reason: None,
                     local_cached_input_tokens: None,
                     provider_cached_input_tokens: None,
+                    request_id: Some(request_id as u64),
                     };
                     let reverse_map = anthropic_reverse_tool_map.clone().unwrap_or_default();
                     return Some(build_anthropic_stream_response(retry_resp, reverse_map, entry).await);
@@ -5920,7 +9170,9 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                     request_headers: request_headers_json.clone(),
                     response_headers: headers_to_json_string(sanitize_reqwest_headers(retry_resp.headers())),
                     request_body: request_body_text.clone(),
+                    request_body_truncated,
                     response_body: None,
+                    response_body_truncated: false,
                     input_tokens: None,
                     output_tokens: None,
                 cache_status: None,
@@ -5929,6 +9181,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                 cache_bypass_reason: None,
                 local_cached_input_tokens: None,
                 provider_cached_input_tokens: None,
+                request_id: Some(request_id as u64),
                 };
                 let _ = insert_proxy_log(&entry);
                 return Some(build_proxy_response(retry_resp).await);
@@ -5936,7 +9189,32 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
 
             let resp_hdrs_json = headers_to_json_string(sanitize_reqwest_headers(retry_resp.headers()));
             let headers = retry_resp.headers().clone();
-            let bytes = retry_resp.bytes().await.unwrap_or_default();
+            let bytes = match read_capped_bytes(retry_resp).await {
+                Ok(b) => b,
+                Err(e) => {
+                    log_proxy(&format!("req#{request_id} upstream body read failed: {e}"));
+                    let entry = ProxyLogEntry {
+                        timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                        method: method_label.to_string(),
+                        path: path.to_string(),
+                        request_url: request_url.clone(),
+                        status: StatusCode::BAD_GATEWAY.as_u16(),
+                        duration_ms: started_at.elapsed().as_millis() as u64,
+                        proxy_account_id: fallback_id.clone(),
+                        account_id: fallback_account_id.clone(),
+                        error: Some(e.clone()),
+                        model: request_model.clone(),
+                        request_headers: request_headers_json.clone(),
+                        response_headers: resp_hdrs_json.clone(),
+                        request_body: request_body_text.clone(),
+                        request_body_truncated,
+                        request_id: Some(request_id as u64),
+                        ..ProxyLogEntry::default()
+                    };
+                    let _ = insert_proxy_log(&entry);
+                    return Some(proxy_error_response(StatusCode::BAD_GATEWAY, "api_error", "upstream_error", format!("Upstream error: {e}")));
+                }
+            };
 
             if (retry_status == reqwest::StatusCode::BAD_REQUEST
                 || retry_status == reqwest::StatusCode::TOO_MANY_REQUESTS)
@@ -5955,8 +9233,8 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
             if is_anthropic {
                 let reverse_map = anthropic_reverse_tool_map.clone().unwrap_or_default();
                 let model_name = request_model.clone().unwrap_or_default();
-                let (converted, input_tokens, output_tokens, response_body_text) =
-                    match build_claude_response_body(&bytes, &reverse_map, &model_name) {
+                let (converted, input_tokens, output_tokens, response_body_text, response_body_truncated) =
+                    match build_claude_response_body(&cfg, &bytes, &reverse_map, &model_name) {
                         Ok(v) => v,
                         Err(err) => {
                             let entry = ProxyLogEntry {
@@ -5973,7 +9251,9 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                                 request_headers: request_headers_json.clone(),
                                 response_headers: resp_hdrs_json.clone(),
                                 request_body: request_body_text.clone(),
+                                request_body_truncated,
                                 response_body: None,
+                                response_body_truncated: false,
                                 input_tokens: None,
                                 output_tokens: None,
                             cache_status: None,
@@ -5982,13 +9262,10 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                             cache_bypass_reason: None,
                             local_cached_input_tokens: None,
                             provider_cached_input_tokens: None,
+                            request_id: Some(request_id as u64),
                             };
                             let _ = insert_proxy_log(&entry);
-                            return Some(Response::builder()
-                                .status(StatusCode::BAD_GATEWAY)
-                                .header("Access-Control-Allow-Origin", "*")
-                                .body(Body::from("Anthropic response conversion failed"))
-                                .unwrap());
+                            return Some(proxy_error_response(StatusCode::BAD_GATEWAY, "api_error", "response_conversion_failed", "Anthropic response conversion failed"));
                         }
                     };
                 let entry = ProxyLogEntry {
@@ -6007,9 +9284,12 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                         ("content-type".to_string(), "application/json".to_string()),
                     ]),
                     request_body: request_body_text.clone(),
+                    request_body_truncated,
                     response_body: response_body_text,
+                    response_body_truncated,
                     input_tokens,
                     output_tokens,
+                    request_id: Some(request_id as u64),
                     ..ProxyLogEntry::default()
                 };
                 let _ = insert_proxy_log(&entry);
@@ -6029,7 +9309,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                     }));
             }
 
-            let response_body_text = if bytes.is_empty() { None } else { Some(truncate_body(&bytes)) };
+            let (response_body_text, response_body_truncated) = log_body_field(&cfg, &bytes);
             let (input_tokens, output_tokens) = extract_usage(&bytes);
             let entry = ProxyLogEntry {
                 timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
@@ -6045,9 +9325,12 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                 request_headers: request_headers_json.clone(),
                 response_headers: resp_hdrs_json,
                 request_body: request_body_text.clone(),
+                request_body_truncated,
                 response_body: response_body_text,
+                response_body_truncated,
                 input_tokens,
                 output_tokens,
+                request_id: Some(request_id as u64),
                 ..ProxyLogEntry::default()
             };
             let _ = insert_proxy_log(&entry);
@@ -6059,7 +9342,39 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
         State(state): State<Arc<ProxyState>>,
         req: axum::http::Request<Body>,
     ) -> Response<Body> {
-        let request_id = PROXY_REQ_ID.fetch_add(1, Ordering::SeqCst);
+        let counted = req.method() != axum::http::Method::OPTIONS;
+        if counted {
+            state.total_requests.fetch_add(1, Ordering::SeqCst);
+            *state.last_request_at.lock().unwrap() = std::time::Instant::now();
+        }
+        let request_id = next_request_id();
+        let origin = req.headers().get(axum::http::header::ORIGIN).cloned();
+        let mut resp = handle_proxy_request(state.clone(), req, request_id).await;
+        if counted && (resp.status().is_client_error() || resp.status().is_server_error()) {
+            state.total_errors.fetch_add(1, Ordering::SeqCst);
+        }
+        apply_cors_policy(resp.headers_mut(), origin.as_ref(), &proxy_config_snapshot());
+        let is_sse = resp
+            .headers()
+            .get(axum::http::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.to_ascii_lowercase().contains("text/event-stream"));
+        if is_sse {
+            resp = resp.map(|body| with_stream_metrics(body, request_id as u64));
+        }
+        // Same id as the request_logs row, so clients can report it back
+        resp.headers_mut().insert(
+            "x-codex-manager-request-id",
+            axum::http::HeaderValue::from(request_id as u64),
+        );
+        resp
+    }
+
+    async fn handle_proxy_request(
+        state: Arc<ProxyState>,
+        req: axum::http::Request<Body>,
+        request_id: usize,
+    ) -> Response<Body> {
         // Handle CORS preflight
         if req.method() == axum::http::Method::OPTIONS {
             return Response::builder()
@@ -6091,8 +9406,9 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
 
         // Collect and filter incoming headers (pass them through, except hop-by-hop)
         let mut forward_headers = reqwest::header::HeaderMap::new();
+        let header_cfg = proxy_config_snapshot();
         for (k, v) in req.headers() {
-            if skip_request_header(k.as_str()) { continue; }
+            if skip_request_header_with_config(k.as_str(), &header_cfg) { continue; }
             if let (Ok(name), Ok(val)) = (
                 reqwest::header::HeaderName::from_bytes(k.as_str().as_bytes()),
                 reqwest::header::HeaderValue::from_bytes(v.as_bytes()),
@@ -6100,8 +9416,42 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                 forward_headers.insert(name, val);
             }
         }
+        if header_cfg.forward_client_ip {
+            let peer = req
+                .extensions()
+                .get::<axum::extract::ConnectInfo<std::net::SocketAddr>>()
+                .map(|info| info.0.ip());
+            if let Some(peer) = peer {
+                if let Ok(value) = reqwest::header::HeaderValue::from_str(&forwarded_for_value(req.headers(), peer)) {
+                    forward_headers.insert(reqwest::header::HeaderName::from_static("x-forwarded-for"), value);
+                }
+            }
+        }
         let request_headers_json = headers_to_json_string(sanitize_headers(&req_headers));
         let request_body_text = None;
+        let request_body_truncated = false;
+
+        // `allowed_paths` prefixes, matched against the path without its query string
+        if !header_cfg.allowed_paths.is_empty() {
+            let base_path = path.split('?').next().unwrap_or("");
+            if !header_cfg.allowed_paths.iter().any(|prefix| base_path.starts_with(prefix.as_str())) {
+                log_proxy(&format!("req#{request_id} rejected: {method_label} {base_path} not in allowed_paths"));
+                let entry = ProxyLogEntry {
+                    timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                    method: method_label.clone(),
+                    path: path.to_string(),
+                    request_url: request_url.clone(),
+                    status: StatusCode::NOT_FOUND.as_u16(),
+                    duration_ms: started_at.elapsed().as_millis() as u64,
+                    error: Some("path not allowed".to_string()),
+                    request_headers: request_headers_json.clone(),
+                    request_id: Some(request_id as u64),
+                    ..ProxyLogEntry::default()
+                };
+                let _ = insert_proxy_log(&entry);
+                return proxy_error_response(StatusCode::NOT_FOUND, "invalid_request_error", "path_not_allowed", format!("Path {base_path} is not allowed"));
+            }
+        }
 
         let max_body_bytes = front_proxy_max_body_bytes();
         if let Some(content_length) = req
@@ -6127,7 +9477,9 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                         ("content-type".to_string(), "text/plain".to_string()),
                     ]),
                     request_body: None,
+                    request_body_truncated: false,
                     response_body: Some("Request body too large".to_string()),
+                    response_body_truncated: false,
                     input_tokens: None,
                     output_tokens: None,
                 cache_status: None,
@@ -6136,6 +9488,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                 cache_bypass_reason: None,
                 local_cached_input_tokens: None,
                 provider_cached_input_tokens: None,
+                request_id: Some(request_id as u64),
                 };
                 let _ = insert_proxy_log(&entry);
                 log_proxy_error_detail(
@@ -6149,12 +9502,232 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                     &entry.response_body,
                     &entry.error,
                 );
-                return Response::builder()
-                    .status(StatusCode::PAYLOAD_TOO_LARGE)
-                    .header("Access-Control-Allow-Origin", "*")
-                    .body(Body::from("Request body too large"))
-                    .unwrap();
+                return proxy_error_response(StatusCode::PAYLOAD_TOO_LARGE, "invalid_request_error", "request_too_large", "Request body too large");
+            }
+        }
+
+        // Checked before the body is read, so an unauthenticated client can't
+        // reach the local cache or have its body streamed upstream
+        if !proxy_api_key_valid(&req_headers) {
+            let entry = ProxyLogEntry {
+                timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                method: method_label.clone(),
+                path: path.to_string(),
+                request_url: request_url.clone(),
+                status: StatusCode::UNAUTHORIZED.as_u16(),
+                duration_ms: started_at.elapsed().as_millis() as u64,
+                proxy_account_id: "".to_string(),
+                account_id: None,
+                error: Some("missing or invalid api key".to_string()),
+                model: None,
+                request_headers: request_headers_json.clone(),
+                response_headers: None,
+                request_body: None,
+                request_body_truncated: false,
+                response_body: None,
+                response_body_truncated: false,
+                input_tokens: None,
+                output_tokens: None,
+            cache_status: None,
+            cache_key: None,
+            cache_eligible: None,
+            cache_bypass_reason: None,
+            local_cached_input_tokens: None,
+            provider_cached_input_tokens: None,
+            request_id: Some(request_id as u64),
+            };
+            let _ = insert_proxy_log(&entry);
+            return proxy_error_response(StatusCode::UNAUTHORIZED, "authentication_error", "invalid_api_key", "Unauthorized");
+        }
+
+        // Streamed request bodies go straight upstream with no retry: without
+        // the bytes in hand there's nothing to resend on a 401 or 429. Account
+        // health is still updated through the same helpers as the buffered path
+        if can_stream_request_body(&header_cfg, &method, &upstream_path, &req_headers) {
+            let (pool_size, found) = pick_account(&state);
+            if pool_size == 0 {
+                return proxy_error_response(StatusCode::SERVICE_UNAVAILABLE, "server_error", "no_accounts_available", "No accounts in pool");
+            }
+            let Some((token, account_id, idx, id, refresh, org)) = found else {
+                let mut resp = proxy_error_response(
+                    StatusCode::TOO_MANY_REQUESTS,
+                    "rate_limit_error",
+                    "all_accounts_unavailable",
+                    "All accounts are rate-limited or blocked",
+                );
+                resp.headers_mut().insert("Retry-After", axum::http::HeaderValue::from(pool_retry_after_secs(&state)));
+                return resp;
+            };
+            target = account_upstream_url(&state, idx, &upstream_path);
+            request_url = Some(target.clone());
+            if upstream_loops_back_to_proxy(&target) {
+                let message = format!("Loop detected: upstream {target} points back at this proxy");
+                log_proxy(&format!("req#{request_id} {message}"));
+                let entry = ProxyLogEntry {
+                    timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                    method: method_label.clone(),
+                    path: path.to_string(),
+                    request_url: request_url.clone(),
+                    status: StatusCode::LOOP_DETECTED.as_u16(),
+                    duration_ms: started_at.elapsed().as_millis() as u64,
+                    proxy_account_id: id.clone(),
+                    account_id: account_id.clone(),
+                    error: Some(message.clone()),
+                    request_headers: request_headers_json.clone(),
+                    response_body: Some(message.clone()),
+                    request_id: Some(request_id as u64),
+                    ..ProxyLogEntry::default()
+                };
+                let _ = insert_proxy_log(&entry);
+                return proxy_error_response(StatusCode::LOOP_DETECTED, "server_error", "upstream_loop_detected", message);
+            }
+            if let Err(retry_after) = circuit_admit(&state) {
+                return circuit_open_response(retry_after);
+            }
+            let is_stream = forward_headers
+                .get(reqwest::header::ACCEPT)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|v| v.contains("text/event-stream"));
+            let token = refresh_before_send(&state, request_id, idx, &id, refresh.as_ref()).await.unwrap_or(token);
+            let mut upstream_headers = forward_headers.clone();
+            apply_upstream_headers(&mut upstream_headers, &token, account_id.as_deref(), Some(&org), &req_headers, true, is_stream);
+            // Enforce max_body_bytes while streaming, since content-length may be absent
+            let body_too_large = Arc::new(AtomicBool::new(false));
+            let too_large_flag = body_too_large.clone();
+            let body_stream = req.into_body().into_data_stream().scan(0usize, move |seen, chunk| {
+                let item = match chunk {
+                    Ok(chunk) => {
+                        *seen += chunk.len();
+                        if *seen > max_body_bytes {
+                            too_large_flag.store(true, Ordering::Relaxed);
+                            Err(io::Error::new(io::ErrorKind::InvalidData, "request body too large"))
+                        } else {
+                            Ok(chunk)
+                        }
+                    }
+                    Err(err) => Err(io::Error::new(io::ErrorKind::Other, err.to_string())),
+                };
+                futures_util::future::ready(Some(item))
+            });
+            log_proxy(&format!("req#{request_id} streaming request body via {id} -> {target}"));
+            let upstream_result = account_client(&state, idx)
+                .request(method.clone(), &target)
+                .headers(upstream_headers)
+                .body(reqwest::Body::wrap_stream(body_stream))
+                .send()
+                .await;
+            let upstream_resp = match upstream_result {
+                Ok(resp) => resp,
+                Err(_) if body_too_large.load(Ordering::Relaxed) => {
+                    // The client's fault, not upstream's: no circuit breaker failure
+                    let entry = ProxyLogEntry {
+                        timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                        method: method_label.clone(),
+                        path: path.to_string(),
+                        request_url: request_url.clone(),
+                        status: StatusCode::PAYLOAD_TOO_LARGE.as_u16(),
+                        duration_ms: started_at.elapsed().as_millis() as u64,
+                        proxy_account_id: id.clone(),
+                        account_id: account_id.clone(),
+                        error: Some("request body too large".to_string()),
+                        request_headers: request_headers_json.clone(),
+                        response_body: Some("Request body too large".to_string()),
+                        request_id: Some(request_id as u64),
+                        ..ProxyLogEntry::default()
+                    };
+                    let _ = insert_proxy_log(&entry);
+                    return proxy_error_response(StatusCode::PAYLOAD_TOO_LARGE, "invalid_request_error", "request_too_large", "Request body too large");
+                }
+                Err(e) => {
+                    circuit_record(&state, false);
+                    let (error_status, error_code) = classify_upstream_error(&e);
+                    log_proxy(&format!("req#{request_id} upstream error ({error_code}): {e}"));
+                    let entry = ProxyLogEntry {
+                        timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                        method: method_label.clone(),
+                        path: path.to_string(),
+                        request_url: request_url.clone(),
+                        status: error_status.as_u16(),
+                        duration_ms: started_at.elapsed().as_millis() as u64,
+                        proxy_account_id: id.clone(),
+                        account_id: account_id.clone(),
+                        error: Some(format!("{error_code}: {e}")),
+                        request_headers: request_headers_json.clone(),
+                        request_id: Some(request_id as u64),
+                        ..ProxyLogEntry::default()
+                    };
+                    let _ = insert_proxy_log(&entry);
+                    return proxy_error_response(error_status, "api_error", error_code, format!("Upstream error: {e}"));
+                }
+            };
+            let upstream_status = upstream_resp.status();
+            log_proxy(&format!("req#{request_id} upstream status: {}", upstream_status.as_u16()));
+            circuit_record(&state, !upstream_status.is_server_error());
+            if upstream_status.is_success() {
+                reset_cooldown_streak(&state, idx);
+                note_account_used(&state, idx);
+            } else if upstream_status == reqwest::StatusCode::UNAUTHORIZED {
+                // Nothing to retry with, but refresh now so the next request gets a working token
+                let _ = refresh_after_unauthorized(&state, request_id, idx, &id, refresh.as_ref()).await;
+            } else if upstream_status == reqwest::StatusCode::FORBIDDEN {
+                transition_account_health(&state, idx, AccountHealth::Blocked, "forbidden", Some(403));
+            } else if upstream_status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                // The response body is still ours to read, so usage limits are honoured
+                let headers = upstream_resp.headers().clone();
+                let response_headers = headers_to_json_string(sanitize_reqwest_headers(&headers));
+                let (status, error, response) = match read_capped_bytes(upstream_resp).await {
+                    Ok(bytes) => {
+                        if record_rate_limit(&state, idx, &id, &bytes) {
+                            log_proxy(&format!("req#{request_id} usage_limit_reached on {id} (streamed body, not retried)"));
+                        }
+                        (upstream_status.as_u16(), None, build_proxy_response_from_bytes(upstream_status, &headers, bytes))
+                    }
+                    Err(e) => {
+                        record_rate_limit(&state, idx, &id, &[]);
+                        log_proxy(&format!("req#{request_id} upstream body read failed: {e}"));
+                        (
+                            StatusCode::BAD_GATEWAY.as_u16(),
+                            Some(e.clone()),
+                            proxy_error_response(StatusCode::BAD_GATEWAY, "api_error", "upstream_error", format!("Upstream error: {e}")),
+                        )
+                    }
+                };
+                let entry = ProxyLogEntry {
+                    timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                    method: method_label.clone(),
+                    path: path.to_string(),
+                    request_url: request_url.clone(),
+                    status,
+                    duration_ms: started_at.elapsed().as_millis() as u64,
+                    proxy_account_id: id.clone(),
+                    account_id: account_id.clone(),
+                    error,
+                    model: default_request_model(&header_cfg, &method, &path, &req_headers),
+                    request_headers: request_headers_json.clone(),
+                    response_headers,
+                    request_id: Some(request_id as u64),
+                    ..ProxyLogEntry::default()
+                };
+                let _ = insert_proxy_log(&entry);
+                return response;
             }
+            let entry = ProxyLogEntry {
+                timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                method: method_label.clone(),
+                path: path.to_string(),
+                request_url: request_url.clone(),
+                status: upstream_status.as_u16(),
+                duration_ms: started_at.elapsed().as_millis() as u64,
+                proxy_account_id: id.clone(),
+                account_id: account_id.clone(),
+                model: default_request_model(&header_cfg, &method, &path, &req_headers),
+                request_headers: request_headers_json.clone(),
+                response_headers: headers_to_json_string(sanitize_reqwest_headers(upstream_resp.headers())),
+                request_id: Some(request_id as u64),
+                ..ProxyLogEntry::default()
+            };
+            let _ = insert_proxy_log(&entry);
+            return build_proxy_response(upstream_resp).await;
         }
 
         // Buffer body (up to max_body_bytes)
@@ -6177,7 +9750,9 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                         ("content-type".to_string(), "text/plain".to_string()),
                     ]),
                     request_body: None,
+                    request_body_truncated: false,
                     response_body: Some("Request body too large".to_string()),
+                    response_body_truncated: false,
                     input_tokens: None,
                     output_tokens: None,
                 cache_status: None,
@@ -6186,6 +9761,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                 cache_bypass_reason: None,
                 local_cached_input_tokens: None,
                 provider_cached_input_tokens: None,
+                request_id: Some(request_id as u64),
                 };
                 let _ = insert_proxy_log(&entry);
                 log_proxy_error_detail(
@@ -6199,25 +9775,44 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                     &entry.response_body,
                     &entry.error,
                 );
-                return Response::builder()
-                    .status(StatusCode::PAYLOAD_TOO_LARGE)
-                    .header("Access-Control-Allow-Origin", "*")
-                    .body(Body::from("Request body too large"))
-                    .unwrap();
+                return proxy_error_response(StatusCode::PAYLOAD_TOO_LARGE, "invalid_request_error", "request_too_large", "Request body too large");
             }
         };
 
-        let mut upstream_body_bytes = body_bytes.clone();
-        let mut request_body_text = if body_bytes.is_empty() {
-            None
-        } else {
-            Some(truncate_body(&body_bytes))
+        // Compressed bodies are decoded here; the body may be rewritten below,
+        // so upstream always receives it uncompressed.
+        let content_encoding = req_headers
+            .get(axum::http::header::CONTENT_ENCODING)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        forward_headers.remove(reqwest::header::CONTENT_ENCODING);
+        let body_bytes = match decode_request_body(content_encoding.as_deref(), body_bytes, max_body_bytes) {
+            Ok(b) => b,
+            Err(err) => {
+                let entry = ProxyLogEntry {
+                    timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                    method: method_label.clone(),
+                    path: path.to_string(),
+                    request_url: request_url.clone(),
+                    status: StatusCode::BAD_REQUEST.as_u16(),
+                    duration_ms: started_at.elapsed().as_millis() as u64,
+                    error: Some(err.clone()),
+                    request_headers: request_headers_json.clone(),
+                    request_id: Some(request_id as u64),
+                    ..ProxyLogEntry::default()
+                };
+                let _ = insert_proxy_log(&entry);
+                return proxy_error_response(StatusCode::BAD_REQUEST, "invalid_request_error", "invalid_content_encoding", err);
+            }
         };
+
+        let cfg = proxy_config_snapshot();
+        let mut upstream_body_bytes = body_bytes.clone();
+        let (mut request_body_text, mut request_body_truncated) = log_body_field(&cfg, &body_bytes);
         let mut request_model = extract_model(&body_bytes);
         let mut anthropic_reverse_tool_map: Option<HashMap<String, String>> = None;
         let mut anthropic_stream = None;
         let mut anthropic_body_json: Option<Value> = None;
-        let cfg = proxy_config_snapshot();
         let (cache_eligible, cache_key, cache_model, _cache_bypass_reason) = if cfg.enable_exact_cache {
             evaluate_local_cache_request(&method_label, &path, &body_bytes)
         } else {
@@ -6226,9 +9821,13 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
         if request_model.is_none() {
             request_model = cache_model.clone();
         }
+        if request_model.is_none() {
+            request_model = default_request_model(&cfg, &method, &path, &req_headers);
+        }
         if cache_eligible {
             if let Some(key) = cache_key.as_ref() {
                 if let Ok(Some(hit)) = lookup_local_cache(key) {
+                    let (response_body_text, response_body_truncated) = truncate_body(&cfg, &hit.body);
                     let entry = ProxyLogEntry {
                         timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
                         method: method_label.clone(),
@@ -6246,7 +9845,9 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                             ("x-codex-manager-cache".to_string(), "HIT".to_string()),
                         ]),
                         request_body: request_body_text.clone(),
-                        response_body: Some(truncate_body(&hit.body)),
+                        request_body_truncated,
+                        response_body: Some(response_body_text),
+                        response_body_truncated,
                         input_tokens: Some(hit.input_tokens),
                         output_tokens: Some(hit.output_tokens),
                         cache_status: Some("local_hit".to_string()),
@@ -6255,6 +9856,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                         cache_bypass_reason: None,
                         local_cached_input_tokens: Some(hit.input_tokens),
                         provider_cached_input_tokens: Some(hit.provider_cached_input_tokens),
+                        request_id: Some(request_id as u64),
                     };
                     let _ = insert_proxy_log(&entry);
                     return build_cached_response(hit.status, &hit.content_type, hit.body);
@@ -6280,7 +9882,9 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                         ("content-type".to_string(), "text/plain".to_string()),
                     ]),
                     request_body: request_body_text.clone(),
+                    request_body_truncated,
                     response_body: Some("Method not allowed".to_string()),
+                    response_body_truncated: false,
                     input_tokens: None,
                     output_tokens: None,
                 cache_status: None,
@@ -6289,6 +9893,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                 cache_bypass_reason: None,
                 local_cached_input_tokens: None,
                 provider_cached_input_tokens: None,
+                request_id: Some(request_id as u64),
                 };
                 let _ = insert_proxy_log(&entry);
                 log_proxy_error_detail(
@@ -6302,11 +9907,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                     &entry.response_body,
                     &entry.error,
                 );
-                return Response::builder()
-                    .status(StatusCode::METHOD_NOT_ALLOWED)
-                    .header("Access-Control-Allow-Origin", "*")
-                    .body(Body::from("Method not allowed"))
-                    .unwrap();
+                return proxy_error_response(StatusCode::METHOD_NOT_ALLOWED, "invalid_request_error", "method_not_allowed", "Method not allowed");
             }
             let body_json: Value = match serde_json::from_slice(&body_bytes) {
                 Ok(v) => v,
@@ -6327,7 +9928,9 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                             ("content-type".to_string(), "text/plain".to_string()),
                         ]),
                         request_body: request_body_text.clone(),
+                        request_body_truncated,
                         response_body: Some("Invalid JSON".to_string()),
+                        response_body_truncated: false,
                         input_tokens: None,
                         output_tokens: None,
                     cache_status: None,
@@ -6336,6 +9939,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                     cache_bypass_reason: None,
                     local_cached_input_tokens: None,
                     provider_cached_input_tokens: None,
+                    request_id: Some(request_id as u64),
                     };
                     let _ = insert_proxy_log(&entry);
                     log_proxy_error_detail(
@@ -6349,19 +9953,23 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                         &entry.response_body,
                         &entry.error,
                     );
-                    return Response::builder()
-                        .status(StatusCode::BAD_REQUEST)
-                        .header("Access-Control-Allow-Origin", "*")
-                        .body(Body::from("Invalid JSON"))
-                        .unwrap();
+                    return proxy_error_response(StatusCode::BAD_REQUEST, "invalid_request_error", "invalid_json", "Invalid JSON");
                 }
             };
             anthropic_body_json = Some(body_json.clone());
-            request_model = body_json.get("model").and_then(|v| v.as_str()).map(|s| s.to_string());
+            request_model = body_json
+                .get("model")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string())
+                .or_else(|| default_request_model(&cfg, &method, &path, &req_headers));
             if !is_count_tokens {
                 match convert_claude_to_codex(&body_json) {
                     Ok((codex_body, reverse_map, stream)) => {
-                        request_model = codex_body.get("model").and_then(|v| v.as_str()).map(|s| s.to_string());
+                        request_model = codex_body
+                            .get("model")
+                            .and_then(|v| v.as_str())
+                            .map(|s| s.to_string())
+                            .or_else(|| default_request_model(&cfg, &method, &path, &req_headers));
                         upstream_body_bytes = serde_json::to_vec(&codex_body).unwrap_or_default().into();
                         upstream_path = "/v1/responses".to_string();
                         target = build_upstream_url(&upstream_path);
@@ -6386,7 +9994,9 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                                 ("content-type".to_string(), "text/plain".to_string()),
                             ]),
                             request_body: request_body_text.clone(),
+                            request_body_truncated,
                             response_body: Some(err.clone()),
+                            response_body_truncated: false,
                             input_tokens: None,
                             output_tokens: None,
                         cache_status: None,
@@ -6395,6 +10005,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                         cache_bypass_reason: None,
                         local_cached_input_tokens: None,
                         provider_cached_input_tokens: None,
+                        request_id: Some(request_id as u64),
                         };
                         let _ = insert_proxy_log(&entry);
                         log_proxy_error_detail(
@@ -6408,11 +10019,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                             &entry.response_body,
                             &entry.error,
                         );
-                        return Response::builder()
-                            .status(StatusCode::BAD_REQUEST)
-                            .header("Access-Control-Allow-Origin", "*")
-                            .body(Body::from(err))
-                            .unwrap();
+                        return proxy_error_response(StatusCode::BAD_REQUEST, "invalid_request_error", "invalid_request", err);
                     }
                 }
             }
@@ -6423,63 +10030,49 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
 
         log_proxy(&format!("req#{request_id} start {method_label} {path} -> {target}"));
 
-        if !proxy_api_key_valid(&req_headers) {
+        if upstream_loops_back_to_proxy(&target) {
+            let message = format!("Loop detected: upstream {target} points back at this proxy");
+            log_proxy(&format!("req#{request_id} {message}"));
             let entry = ProxyLogEntry {
                 timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
                 method: method_label.clone(),
                 path: path.to_string(),
                 request_url: request_url.clone(),
-                status: StatusCode::UNAUTHORIZED.as_u16(),
+                status: StatusCode::LOOP_DETECTED.as_u16(),
                 duration_ms: started_at.elapsed().as_millis() as u64,
                 proxy_account_id: "".to_string(),
                 account_id: None,
-                error: Some("missing or invalid api key".to_string()),
+                error: Some(message.clone()),
                 model: request_model.clone(),
                 request_headers: request_headers_json.clone(),
                 response_headers: None,
                 request_body: request_body_text.clone(),
-                response_body: None,
-                input_tokens: None,
-                output_tokens: None,
-            cache_status: None,
-            cache_key: None,
-            cache_eligible: None,
-            cache_bypass_reason: None,
-            local_cached_input_tokens: None,
-            provider_cached_input_tokens: None,
+                request_body_truncated,
+                response_body: Some(message.clone()),
+                request_id: Some(request_id as u64),
+                ..ProxyLogEntry::default()
             };
             let _ = insert_proxy_log(&entry);
-            return Response::builder()
-                .status(StatusCode::UNAUTHORIZED)
-                .header("Access-Control-Allow-Origin", "*")
-                .body(Body::from("Unauthorized"))
-                .unwrap();
+            return proxy_error_response(StatusCode::LOOP_DETECTED, "server_error", "upstream_loop_detected", message);
         }
 
         if is_anthropic && is_count_tokens {
             let body_json = match anthropic_body_json.as_ref() {
                 Some(v) => v,
                 None => {
-                    return Response::builder()
-                        .status(StatusCode::BAD_REQUEST)
-                        .header("Access-Control-Allow-Origin", "*")
-                        .body(Body::from("Invalid JSON"))
-                        .unwrap();
+                    return proxy_error_response(StatusCode::BAD_REQUEST, "invalid_request_error", "invalid_json", "Invalid JSON");
                 }
             };
             let codex_body = match convert_claude_to_codex(body_json) {
                 Ok((v, _, _)) => v,
                 Err(err) => {
-                    return Response::builder()
-                        .status(StatusCode::BAD_REQUEST)
-                        .header("Access-Control-Allow-Origin", "*")
-                        .body(Body::from(err))
-                        .unwrap();
+                    return proxy_error_response(StatusCode::BAD_REQUEST, "invalid_request_error", "invalid_request", err);
                 }
             };
             let count = count_codex_input_tokens(&codex_body);
             let response_payload = serde_json::json!({ "input_tokens": count });
             let response_body = serde_json::to_vec(&response_payload).unwrap_or_else(|_| b"{}".to_vec());
+            let (response_body_text, response_body_truncated) = truncate_body(&cfg, &response_body);
             let entry = ProxyLogEntry {
                 timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
                 method: method_label.clone(),
@@ -6496,7 +10089,9 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                     ("content-type".to_string(), "application/json".to_string()),
                 ]),
                 request_body: request_body_text.clone(),
-                response_body: Some(truncate_body(&response_body)),
+                request_body_truncated,
+                response_body: Some(response_body_text),
+                response_body_truncated,
                 input_tokens: Some(count),
                 output_tokens: Some(0),
             cache_status: None,
@@ -6505,6 +10100,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
             cache_bypass_reason: None,
             local_cached_input_tokens: None,
             provider_cached_input_tokens: None,
+            request_id: Some(request_id as u64),
             };
             let _ = insert_proxy_log(&entry);
             return Response::builder()
@@ -6524,18 +10120,10 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
         let proxy_cfg = proxy_config_snapshot();
         if !is_anthropic && proxy_uses_custom_openai(&proxy_cfg) {
             let Some(custom_base_url) = custom_openai_base_url(&proxy_cfg) else {
-                return Response::builder()
-                    .status(StatusCode::SERVICE_UNAVAILABLE)
-                    .header("Access-Control-Allow-Origin", "*")
-                    .body(Body::from("自定义 OpenAI 地址未配置"))
-                    .unwrap();
+                return proxy_error_response(StatusCode::SERVICE_UNAVAILABLE, "server_error", "upstream_not_configured", "自定义 OpenAI 地址未配置");
             };
             let Some(custom_api_key) = custom_openai_api_key(&proxy_cfg) else {
-                return Response::builder()
-                    .status(StatusCode::SERVICE_UNAVAILABLE)
-                    .header("Access-Control-Allow-Origin", "*")
-                    .body(Body::from("自定义 OpenAI API Key 未配置"))
-                    .unwrap();
+                return proxy_error_response(StatusCode::SERVICE_UNAVAILABLE, "server_error", "upstream_not_configured", "自定义 OpenAI API Key 未配置");
             };
 
             let configured_models = configured_proxy_models(&proxy_cfg);
@@ -6555,6 +10143,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                         .unwrap();
                 }
                 let response_body = build_local_models_response(&configured_models);
+                let (response_body_text, response_body_truncated) = truncate_body(&cfg, &response_body);
                 let entry = ProxyLogEntry {
                     timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
                     method: method_label.clone(),
@@ -6571,7 +10160,9 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                         ("content-type".to_string(), "application/json".to_string()),
                     ]),
                     request_body: request_body_text.clone(),
-                    response_body: Some(truncate_body(&response_body)),
+                    request_body_truncated,
+                    response_body: Some(response_body_text),
+                    response_body_truncated,
                     input_tokens: None,
                     output_tokens: None,
                 cache_status: None,
@@ -6580,6 +10171,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                 cache_bypass_reason: None,
                 local_cached_input_tokens: None,
                 provider_cached_input_tokens: None,
+                request_id: Some(request_id as u64),
                 };
                 let _ = insert_proxy_log(&entry);
                 return Response::builder()
@@ -6602,39 +10194,30 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                 .unwrap_or(false);
             if custom_responses_path {
                 if method != reqwest::Method::POST {
-                    return Response::builder()
-                        .status(StatusCode::METHOD_NOT_ALLOWED)
-                        .header("Access-Control-Allow-Origin", "*")
-                        .body(Body::from("Method not allowed"))
-                        .unwrap();
+                    return proxy_error_response(StatusCode::METHOD_NOT_ALLOWED, "invalid_request_error", "method_not_allowed", "Method not allowed");
                 }
                 let body_json: Value = match serde_json::from_slice(&body_bytes) {
                     Ok(v) => v,
                     Err(err) => {
-                        return Response::builder()
-                            .status(StatusCode::BAD_REQUEST)
-                            .header("Access-Control-Allow-Origin", "*")
-                            .body(Body::from(format!("Invalid JSON: {err}")))
-                            .unwrap();
+                        return proxy_error_response(StatusCode::BAD_REQUEST, "invalid_request_error", "invalid_json", format!("Invalid JSON: {err}"));
                     }
                 };
                 match convert_responses_request_to_chat_completions(&body_json, &proxy_cfg) {
                     Ok((chat_body, stream)) => {
-                        request_model = chat_body.get("model").and_then(|v| v.as_str()).map(|v| v.to_string());
+                        request_model = chat_body
+                            .get("model")
+                            .and_then(|v| v.as_str())
+                            .map(|v| v.to_string())
+                            .or_else(|| default_request_model(&cfg, &method, &path, &req_headers));
                         custom_stream = stream;
-                        request_body_text = Some(truncate_body(
-                            &serde_json::to_vec(&chat_body).unwrap_or_default(),
-                        ));
+                        (request_body_text, request_body_truncated) =
+                            log_body_field(&cfg, &serde_json::to_vec(&chat_body).unwrap_or_default());
                         upstream_body_bytes = serde_json::to_vec(&chat_body).unwrap_or_default().into();
                         custom_request_json = Some(chat_body);
                         upstream_path = "/v1/chat/completions".to_string();
                     }
                     Err(err) => {
-                        return Response::builder()
-                            .status(StatusCode::BAD_REQUEST)
-                            .header("Access-Control-Allow-Origin", "*")
-                            .body(Body::from(err))
-                            .unwrap();
+                        return proxy_error_response(StatusCode::BAD_REQUEST, "invalid_request_error", "invalid_request", err);
                     }
                 }
             }
@@ -6642,6 +10225,11 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
             target = build_upstream_url_with_base(&custom_base_url, &upstream_path);
             request_url = Some(target.clone());
             log_proxy(&format!("req#{request_id} start {method_label} {path} -> {target}"));
+            if upstream_loops_back_to_proxy(&target) {
+                let message = format!("Loop detected: upstream {target} points back at this proxy");
+                log_proxy(&format!("req#{request_id} {message}"));
+                return proxy_error_response(StatusCode::LOOP_DETECTED, "server_error", "upstream_loop_detected", message);
+            }
 
             let mut custom_headers = reqwest::header::HeaderMap::new();
             apply_custom_openai_headers(
@@ -6660,21 +10248,24 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
             {
                 Ok(resp) => resp,
                 Err(err) => {
+                    let (error_status, error_code) = classify_upstream_error(&err);
                     let entry = ProxyLogEntry {
                         timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
                         method: method_label.clone(),
                         path: path.to_string(),
                         request_url: request_url.clone(),
-                        status: StatusCode::BAD_GATEWAY.as_u16(),
+                        status: error_status.as_u16(),
                         duration_ms: started_at.elapsed().as_millis() as u64,
                         proxy_account_id: "custom-openai".to_string(),
                         account_id: None,
-                        error: Some(err.to_string()),
+                        error: Some(format!("{error_code}: {err}")),
                         model: request_model.clone(),
                         request_headers: request_headers_json.clone(),
                         response_headers: None,
                         request_body: request_body_text.clone(),
+                        request_body_truncated,
                         response_body: None,
+                        response_body_truncated: false,
                         input_tokens: None,
                         output_tokens: None,
                     cache_status: None,
@@ -6683,13 +10274,10 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                     cache_bypass_reason: None,
                     local_cached_input_tokens: None,
                     provider_cached_input_tokens: None,
+                    request_id: Some(request_id as u64),
                     };
                     let _ = insert_proxy_log(&entry);
-                    return Response::builder()
-                        .status(StatusCode::BAD_GATEWAY)
-                        .header("Access-Control-Allow-Origin", "*")
-                        .body(Body::from(format!("Upstream error: {err}")))
-                        .unwrap();
+                    return proxy_error_response(error_status, "api_error", error_code, format!("Upstream error: {err}"));
                 }
             };
 
@@ -6713,7 +10301,9 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                         ("content-type".to_string(), "text/event-stream".to_string()),
                     ]),
                     request_body: request_body_text.clone(),
+                    request_body_truncated,
                     response_body: None,
+                    response_body_truncated: false,
                     input_tokens: None,
                     output_tokens: None,
                 cache_status: None,
@@ -6722,6 +10312,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                 cache_bypass_reason: None,
                 local_cached_input_tokens: None,
                 provider_cached_input_tokens: None,
+                request_id: Some(request_id as u64),
                 };
                 return build_custom_openai_stream_response(
                     upstream_resp,
@@ -6733,14 +10324,40 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
 
             let response_headers_json = headers_to_json_string(sanitize_reqwest_headers(upstream_resp.headers()));
             let headers = upstream_resp.headers().clone();
-            let bytes = upstream_resp.bytes().await.unwrap_or_default();
+            let bytes = match read_capped_bytes(upstream_resp).await {
+                Ok(b) => b,
+                Err(e) => {
+                    log_proxy(&format!("req#{request_id} upstream body read failed: {e}"));
+                    let entry = ProxyLogEntry {
+                        timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                        method: method_label.clone(),
+                        path: path.to_string(),
+                        request_url: request_url.clone(),
+                        status: StatusCode::BAD_GATEWAY.as_u16(),
+                        duration_ms: started_at.elapsed().as_millis() as u64,
+                        proxy_account_id: "custom-openai".to_string(),
+                        account_id: None,
+                        error: Some(e.clone()),
+                        model: request_model.clone(),
+                        request_headers: request_headers_json.clone(),
+                        response_headers: response_headers_json.clone(),
+                        request_body: request_body_text.clone(),
+                        request_body_truncated,
+                        request_id: Some(request_id as u64),
+                        ..ProxyLogEntry::default()
+                    };
+                    let _ = insert_proxy_log(&entry);
+                    return proxy_error_response(StatusCode::BAD_GATEWAY, "api_error", "upstream_error", format!("Upstream error: {e}"));
+                }
+            };
 
             if custom_responses_path && upstream_status.is_success() {
                 match convert_chat_completions_non_stream_to_responses(
+                    &cfg,
                     custom_request_json.as_ref().unwrap_or(&empty_request_json),
                     &bytes,
                 ) {
-                    Ok((converted, input_tokens, output_tokens, response_body_text)) => {
+                    Ok((converted, input_tokens, output_tokens, response_body_text, response_body_truncated)) => {
                         let entry = ProxyLogEntry {
                             timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
                             method: method_label.clone(),
@@ -6757,9 +10374,12 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                                 ("content-type".to_string(), "application/json".to_string()),
                             ]),
                             request_body: request_body_text.clone(),
+                            request_body_truncated,
                             response_body: response_body_text,
+                            response_body_truncated,
                             input_tokens,
                             output_tokens,
+                            request_id: Some(request_id as u64),
                             ..ProxyLogEntry::default()
                         };
                         let _ = insert_proxy_log(&entry);
@@ -6772,6 +10392,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                             .unwrap();
                     }
                     Err(err) => {
+                        let (response_body_text, response_body_truncated) = truncate_body(&cfg, &bytes);
                         let entry = ProxyLogEntry {
                             timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
                             method: method_label.clone(),
@@ -6786,7 +10407,9 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                             request_headers: request_headers_json.clone(),
                             response_headers: response_headers_json.clone(),
                             request_body: request_body_text.clone(),
-                            response_body: Some(truncate_body(&bytes)),
+                            request_body_truncated,
+                            response_body: Some(response_body_text),
+                            response_body_truncated,
                             input_tokens: None,
                             output_tokens: None,
                         cache_status: None,
@@ -6795,18 +10418,15 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                         cache_bypass_reason: None,
                         local_cached_input_tokens: None,
                         provider_cached_input_tokens: None,
+                        request_id: Some(request_id as u64),
                         };
                         let _ = insert_proxy_log(&entry);
-                        return Response::builder()
-                            .status(StatusCode::BAD_GATEWAY)
-                            .header("Access-Control-Allow-Origin", "*")
-                            .body(Body::from(err))
-                            .unwrap();
+                        return proxy_error_response(StatusCode::BAD_GATEWAY, "api_error", "response_conversion_failed", err);
                     }
                 }
             }
 
-            let response_body_text = if bytes.is_empty() { None } else { Some(truncate_body(&bytes)) };
+            let (response_body_text, response_body_truncated) = log_body_field(&cfg, &bytes);
             let (input_tokens, output_tokens) = extract_usage(&bytes);
             let entry = ProxyLogEntry {
                 timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
@@ -6822,66 +10442,153 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                 request_headers: request_headers_json.clone(),
                 response_headers: response_headers_json,
                 request_body: request_body_text.clone(),
+                request_body_truncated,
                 response_body: response_body_text,
+                response_body_truncated,
                 input_tokens,
                 output_tokens,
+                request_id: Some(request_id as u64),
                 ..ProxyLogEntry::default()
             };
             let _ = insert_proxy_log(&entry);
             return build_proxy_response_from_bytes(upstream_status, &headers, bytes);
         }
 
-        // Pick a healthy account (skip cooldown-expired accounts, revive if cooldown elapsed)
-        let (chosen_token, chosen_account_id, chosen_idx, chosen_id, chosen_refresh) = {
-            let now = std::time::Instant::now();
-            let mut accounts_lock = state.accounts.write().unwrap();
-            let pool_size = accounts_lock.len();
-
-            if pool_size == 0 {
+        // Serve /v1/models from cache; a stale entry is still served while it refreshes
+        let cache_models_path = proxy_cfg.cache_models
+            && method == reqwest::Method::GET
+            && is_models_path(&upstream_path);
+        if cache_models_path {
+            let cached = {
+                let cache = state.models_cache.lock().unwrap();
+                cache
+                    .as_ref()
+                    .filter(|entry| entry.key == upstream_path)
+                    .map(|entry| (entry.content_type.clone(), entry.body.clone(), entry.fetched_at.elapsed().as_secs()))
+            };
+            if let Some((content_type, body, age_secs)) = cached {
+                if age_secs >= MODELS_CACHE_TTL_SECS {
+                    spawn_models_cache_refresh(state.clone(), upstream_path.clone());
+                }
+                let (response_body_text, response_body_truncated) = truncate_body(&cfg, &body);
+                let entry = ProxyLogEntry {
+                    timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                    method: method_label.clone(),
+                    path: path.to_string(),
+                    request_url: Some("cache:/v1/models".to_string()),
+                    status: StatusCode::OK.as_u16(),
+                    duration_ms: started_at.elapsed().as_millis() as u64,
+                    proxy_account_id: "models-cache".to_string(),
+                    request_headers: request_headers_json.clone(),
+                    response_body: Some(response_body_text),
+                    response_body_truncated,
+                    request_id: Some(request_id as u64),
+                    ..ProxyLogEntry::default()
+                };
+                let _ = insert_proxy_log(&entry);
                 return Response::builder()
-                    .status(StatusCode::SERVICE_UNAVAILABLE)
+                    .status(StatusCode::OK)
+                    .header("Content-Type", content_type)
                     .header("Access-Control-Allow-Origin", "*")
-                    .body(Body::from("No accounts in pool"))
-                    .unwrap();
+                    .header("Access-Control-Allow-Headers", "*")
+                    .body(Body::from(body))
+                    .unwrap_or_else(|_| {
+                        Response::builder()
+                            .status(axum::http::StatusCode::INTERNAL_SERVER_ERROR)
+                            .body(Body::empty())
+                            .unwrap()
+                    });
             }
+        }
 
-            // Revive any accounts whose cooldown has elapsed
-            for acc in accounts_lock.iter_mut() {
-                if let AccountHealth::Cooldown(until) = &acc.health {
-                    if now >= *until {
-                        acc.health = AccountHealth::Active;
-                    }
+        // Duplicate sends with the same `Idempotency-Key`, client and body get the first response
+        let idempotency_key = req_headers
+            .get("idempotency-key")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim())
+            .filter(|v| proxy_cfg.idempotency_enabled && !v.is_empty())
+            .map(|v| idempotency_cache_key(&method_label, &upstream_path, &presented_client_key(&req_headers), v, &body_bytes));
+        let mut _idempotency_guard = None;
+        if let Some(key) = &idempotency_key {
+            match idempotency_begin(&state, key).await {
+                Ok(guard) => _idempotency_guard = guard,
+                Err((status, headers, body)) => {
+                    log_proxy(&format!("req#{request_id} idempotency hit: {key}"));
+                    let (response_body_text, response_body_truncated) = truncate_body(&cfg, &body);
+                    let entry = ProxyLogEntry {
+                        timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                        method: method_label.clone(),
+                        path: path.to_string(),
+                        request_url: Some(format!("idempotency:{upstream_path}")),
+                        status: status.as_u16(),
+                        duration_ms: started_at.elapsed().as_millis() as u64,
+                        proxy_account_id: "idempotency-cache".to_string(),
+                        request_headers: request_headers_json.clone(),
+                        response_body: Some(response_body_text),
+                        response_body_truncated,
+                        request_id: Some(request_id as u64),
+                        ..ProxyLogEntry::default()
+                    };
+                    let _ = insert_proxy_log(&entry);
+                    return build_proxy_response_from_bytes(status, &headers, body);
                 }
             }
+        }
 
-            let start_count = state.req_counter.fetch_add(1, Ordering::SeqCst);
-            let mut found = None;
-            for i in 0..pool_size {
-                let idx = (start_count + i) % pool_size;
-                if accounts_lock[idx].health == AccountHealth::Active {
-                    found = Some((
-                        accounts_lock[idx].access_token.clone(),
-                        accounts_lock[idx].account_id.clone(),
-                        idx,
-                        accounts_lock[idx].id.clone(),
-                        accounts_lock[idx].refresh_token.clone(),
-                    ));
-                    break;
-                }
+        // Pick a healthy account (skip cooldown-expired accounts, revive if cooldown elapsed)
+        let (pool_size, found) = pick_account(&state);
+        if pool_size == 0 {
+            return proxy_error_response(StatusCode::SERVICE_UNAVAILABLE, "server_error", "no_accounts_available", "No accounts in pool");
+        }
+        let (mut chosen_token, mut chosen_account_id, mut chosen_idx, mut chosen_id, mut chosen_refresh, mut chosen_org) = match found {
+            Some(f) => f,
+            None => {
+                let mut resp = proxy_error_response(
+                    StatusCode::TOO_MANY_REQUESTS,
+                    "rate_limit_error",
+                    "all_accounts_unavailable",
+                    "All accounts are rate-limited or blocked",
+                );
+                resp.headers_mut().insert("Retry-After", axum::http::HeaderValue::from(pool_retry_after_secs(&state)));
+                return resp;
             }
+        };
 
-            match found {
-                Some(f) => f,
-                None => {
-                    return Response::builder()
-                        .status(StatusCode::TOO_MANY_REQUESTS)
-                        .header("Access-Control-Allow-Origin", "*")
-                        .header("Retry-After", "60")
-                        .body(Body::from("All accounts are rate-limited or blocked"))
-                        .unwrap();
-                }
+        // Short-lived cache for other idempotent GETs
+        let get_cache_ttl = proxy_cfg
+            .get_cache_secs
+            .filter(|_| method == reqwest::Method::GET && !cache_models_path);
+        // Keyed by client and account: a cached body is never served to another
+        // client, nor for an account other than the one that fetched it
+        let client_key = presented_client_key(&req_headers);
+        if let Some(ttl) = get_cache_ttl {
+            let key = get_cache_key(&upstream_path, &chosen_id, &client_key);
+            if let Some((status, headers, body)) = get_cache_lookup(&state, &key, ttl) {
+                state.get_cache_hits.fetch_add(1, Ordering::Relaxed);
+                let (response_body_text, response_body_truncated) = truncate_body(&cfg, &body);
+                let entry = ProxyLogEntry {
+                    timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                    method: method_label.clone(),
+                    path: path.to_string(),
+                    request_url: Some(format!("cache:{upstream_path}")),
+                    status: status.as_u16(),
+                    duration_ms: started_at.elapsed().as_millis() as u64,
+                    proxy_account_id: "get-cache".to_string(),
+                    request_headers: request_headers_json.clone(),
+                    response_body: Some(response_body_text),
+                    response_body_truncated,
+                    request_id: Some(request_id as u64),
+                    ..ProxyLogEntry::default()
+                };
+                let _ = insert_proxy_log(&entry);
+                return build_proxy_response_from_bytes(status, &headers, body);
             }
-        };
+            state.get_cache_misses.fetch_add(1, Ordering::Relaxed);
+        }
+
+        if let Err(retry_after) = circuit_admit(&state) {
+            return circuit_open_response(retry_after);
+        }
 
         // Send request upstream with the chosen account's token
         let mut is_stream = forward_headers
@@ -6892,42 +10599,218 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
         if let Some(stream) = anthropic_stream {
             is_stream = stream;
         }
+        // A HEAD response never has a body to stream, whatever the client accepts
+        if method == reqwest::Method::HEAD {
+            is_stream = false;
+        }
+
+        // Echo mode: the account is picked as usual but nothing is sent upstream
+        if proxy_cfg.echo_mode {
+            let model_name = request_model.clone().unwrap_or_else(|| "echo".to_string());
+            let echo = echo_mode_response(request_id, &model_name, &upstream_body_bytes);
+            let echo_bytes = Bytes::from(serde_json::to_vec(&echo).unwrap_or_default());
+            let body_bytes = if is_anthropic {
+                let reverse_map = anthropic_reverse_tool_map.clone().unwrap_or_default();
+                match convert_codex_response_bytes_to_claude(&echo_bytes, &reverse_map, &model_name) {
+                    Ok((converted, _, _)) => converted,
+                    Err(err) => {
+                        return proxy_error_response(StatusCode::INTERNAL_SERVER_ERROR, "api_error", "echo_failed", err);
+                    }
+                }
+            } else {
+                echo_bytes
+            };
+            let (content_type, body_bytes) = if is_stream {
+                let message: Value = serde_json::from_slice(&body_bytes).unwrap_or(Value::Null);
+                let sse = if is_anthropic { echo_mode_claude_sse(&message) } else { echo_mode_responses_sse(&message) };
+                ("text/event-stream", Bytes::from(sse))
+            } else {
+                ("application/json", body_bytes)
+            };
+            let (input_tokens, output_tokens) = extract_usage(&echo_bytes);
+            let (response_body_text, response_body_truncated) = truncate_body(&cfg, &body_bytes);
+            let entry = ProxyLogEntry {
+                timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                method: method_label.clone(),
+                path: path.to_string(),
+                request_url: Some(format!("echo:{upstream_path}")),
+                status: StatusCode::OK.as_u16(),
+                duration_ms: started_at.elapsed().as_millis() as u64,
+                proxy_account_id: chosen_id.clone(),
+                account_id: chosen_account_id.clone(),
+                model: request_model.clone(),
+                request_headers: request_headers_json.clone(),
+                response_headers: headers_to_json_string(vec![
+                    ("content-type".to_string(), content_type.to_string()),
+                ]),
+                request_body: request_body_text.clone(),
+                request_body_truncated,
+                response_body: Some(response_body_text),
+                response_body_truncated,
+                input_tokens,
+                output_tokens,
+                request_id: Some(request_id as u64),
+                ..ProxyLogEntry::default()
+            };
+            let _ = insert_proxy_log(&entry);
+            return Response::builder()
+                .status(StatusCode::OK)
+                .header("Content-Type", content_type)
+                .header("Access-Control-Allow-Origin", "*")
+                .header("Access-Control-Allow-Headers", "*")
+                .body(Body::from(body_bytes))
+                .unwrap_or_else(|_| {
+                    Response::builder()
+                        .status(axum::http::StatusCode::INTERNAL_SERVER_ERROR)
+                        .body(Body::empty())
+                        .unwrap()
+                });
+        }
+
+        // Proactive refresh: don't send a token that expires within `refresh_lead_secs`
+        if let Some(new_token) = refresh_before_send(&state, request_id, chosen_idx, &chosen_id, chosen_refresh.as_ref()).await {
+            chosen_token = new_token;
+        }
+
+        target = account_upstream_url(&state, chosen_idx, &upstream_path);
+        request_url = Some(target.clone());
+        log_proxy(&format!("req#{request_id} upstream for {chosen_id}: {target}"));
+        // An account's own base_url can point back here even when the global one doesn't
+        if upstream_loops_back_to_proxy(&target) {
+            let message = format!("Loop detected: upstream {target} for {chosen_id} points back at this proxy");
+            log_proxy(&format!("req#{request_id} {message}"));
+            let entry = ProxyLogEntry {
+                timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                method: method_label.clone(),
+                path: path.to_string(),
+                request_url: request_url.clone(),
+                status: StatusCode::LOOP_DETECTED.as_u16(),
+                duration_ms: started_at.elapsed().as_millis() as u64,
+                proxy_account_id: chosen_id.clone(),
+                account_id: chosen_account_id.clone(),
+                error: Some(message.clone()),
+                model: request_model.clone(),
+                request_headers: request_headers_json.clone(),
+                request_body: request_body_text.clone(),
+                request_body_truncated,
+                response_body: Some(message.clone()),
+                request_id: Some(request_id as u64),
+                ..ProxyLogEntry::default()
+            };
+            let _ = insert_proxy_log(&entry);
+            return proxy_error_response(StatusCode::LOOP_DETECTED, "server_error", "upstream_loop_detected", message);
+        }
+
         let mut upstream_headers = forward_headers.clone();
         apply_upstream_headers(
             &mut upstream_headers,
             &chosen_token,
             chosen_account_id.as_deref(),
+            Some(&chosen_org),
             &req_headers,
             !upstream_body_bytes.is_empty(),
             is_stream,
         );
 
-        let upstream_result = state.client
+        let hedge_window = proxy_config_snapshot()
+            .hedge_after_ms
+            .filter(|ms| *ms > 0 && !is_stream && is_hedgeable_request(&method, &upstream_body_bytes))
+            .map(std::time::Duration::from_millis);
+        let primary = account_client(&state, chosen_idx)
             .request(method.clone(), &target)
             .headers(upstream_headers)
             .body(upstream_body_bytes.clone())
-            .send()
-            .await;
+            .send();
+        tokio::pin!(primary);
+        let upstream_result = match hedge_window {
+            None => primary.await,
+            Some(window) => match tokio::time::timeout(window, &mut primary).await {
+                Ok(result) => result,
+                // A hedge account whose base_url loops back here is never raced
+                Err(_) => match pick_hedge_account(&state, chosen_idx)
+                    .filter(|(_, _, idx, ..)| !upstream_loops_back_to_proxy(&account_upstream_url(&state, *idx, &upstream_path)))
+                {
+                    None => primary.await,
+                    Some((hedge_token, hedge_account_id, hedge_idx, hedge_id, hedge_refresh, hedge_org)) => {
+                        log_proxy(&format!(
+                            "req#{request_id} hedge: {chosen_id} silent after {}ms, also sending to {}",
+                            window.as_millis(),
+                            hedge_id
+                        ));
+                        let mut hedge_headers = forward_headers.clone();
+                        apply_upstream_headers(
+                            &mut hedge_headers,
+                            &hedge_token,
+                            hedge_account_id.as_deref(),
+                            Some(&hedge_org),
+                            &req_headers,
+                            !upstream_body_bytes.is_empty(),
+                            is_stream,
+                        );
+                        let hedge_target = account_upstream_url(&state, hedge_idx, &upstream_path);
+                        let secondary = account_client(&state, hedge_idx)
+                            .request(method.clone(), &hedge_target)
+                            .headers(hedge_headers)
+                            .body(upstream_body_bytes.clone())
+                            .send();
+                        tokio::pin!(secondary);
+                        let (result, hedge_won) = tokio::select! {
+                            result = &mut primary => (result, false),
+                            result = &mut secondary => (result, true),
+                        };
+                        // A transport error on the first finisher falls back to the other attempt
+                        let (result, hedge_won) = match (result, hedge_won) {
+                            (Err(e), false) => {
+                                log_proxy(&format!("req#{request_id} hedge: {chosen_id} failed ({e}), waiting for {}", hedge_id));
+                                (secondary.await, true)
+                            }
+                            (Err(e), true) => {
+                                log_proxy(&format!("req#{request_id} hedge: {} failed ({e}), waiting for {chosen_id}", hedge_id));
+                                (primary.await, false)
+                            }
+                            ok => ok,
+                        };
+                        if hedge_won {
+                            log_proxy(&format!("req#{request_id} hedge: {} answered first, cancelled {chosen_id}", hedge_id));
+                            chosen_account_id = hedge_account_id;
+                            chosen_idx = hedge_idx;
+                            chosen_id = hedge_id;
+                            chosen_refresh = hedge_refresh;
+                            chosen_org = hedge_org;
+                            request_url = Some(hedge_target.clone());
+                            target = hedge_target;
+                        } else {
+                            log_proxy(&format!("req#{request_id} hedge: {chosen_id} answered first, cancelled {}", hedge_id));
+                        }
+                        result
+                    }
+                },
+            },
+        };
 
         let upstream_resp = match upstream_result {
             Ok(r) => r,
             Err(e) => {
-                log_proxy(&format!("req#{request_id} upstream error: {e}"));
+                circuit_record(&state, false);
+                let (error_status, error_code) = classify_upstream_error(&e);
+                log_proxy(&format!("req#{request_id} upstream error ({error_code}): {e}"));
                 let entry = ProxyLogEntry {
                     timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
                     method: method_label.clone(),
                     path: path.to_string(),
                     request_url: request_url.clone(),
-                    status: StatusCode::BAD_GATEWAY.as_u16(),
+                    status: error_status.as_u16(),
                     duration_ms: started_at.elapsed().as_millis() as u64,
                     proxy_account_id: chosen_id.clone(),
                     account_id: chosen_account_id.clone(),
-                    error: Some(format!("{e}")),
+                    error: Some(format!("{error_code}: {e}")),
                     model: request_model.clone(),
                     request_headers: request_headers_json.clone(),
                     response_headers: None,
                     request_body: request_body_text.clone(),
+                    request_body_truncated,
                     response_body: None,
+                    response_body_truncated: false,
                     input_tokens: None,
                     output_tokens: None,
                 cache_status: None,
@@ -6936,22 +10819,49 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                 cache_bypass_reason: None,
                 local_cached_input_tokens: None,
                 provider_cached_input_tokens: None,
+                request_id: Some(request_id as u64),
                 };
                 let _ = insert_proxy_log(&entry);
-                return Response::builder()
-                    .status(StatusCode::BAD_GATEWAY)
-                    .header("Access-Control-Allow-Origin", "*")
-                    .body(Body::from(format!("Upstream error: {e}")))
-                    .unwrap();
+                return proxy_error_response(error_status, "api_error", error_code, format!("Upstream error: {e}"));
             }
         };
 
         let upstream_status = upstream_resp.status();
         log_proxy(&format!("req#{request_id} upstream status: {}", upstream_status.as_u16()));
+        circuit_record(&state, !upstream_status.is_server_error());
+        if upstream_status.is_success() {
+            reset_cooldown_streak(&state, chosen_idx);
+            note_account_used(&state, chosen_idx);
+        }
 
         if is_stream && upstream_status == reqwest::StatusCode::BAD_REQUEST {
             let headers = upstream_resp.headers().clone();
-            let bytes = upstream_resp.bytes().await.unwrap_or_default();
+            let bytes = match read_capped_bytes(upstream_resp).await {
+                Ok(b) => b,
+                Err(e) => {
+                    log_proxy(&format!("req#{request_id} upstream body read failed: {e}"));
+                    let entry = ProxyLogEntry {
+                        timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                        method: method_label.clone(),
+                        path: path.to_string(),
+                        request_url: request_url.clone(),
+                        status: StatusCode::BAD_GATEWAY.as_u16(),
+                        duration_ms: started_at.elapsed().as_millis() as u64,
+                        proxy_account_id: chosen_id.clone(),
+                        account_id: chosen_account_id.clone(),
+                        error: Some(e.clone()),
+                        model: request_model.clone(),
+                        request_headers: request_headers_json.clone(),
+                        response_headers: headers_to_json_string(sanitize_reqwest_headers(&headers)),
+                        request_body: request_body_text.clone(),
+                        request_body_truncated,
+                        request_id: Some(request_id as u64),
+                        ..ProxyLogEntry::default()
+                    };
+                    let _ = insert_proxy_log(&entry);
+                    return proxy_error_response(StatusCode::BAD_GATEWAY, "api_error", "upstream_error", format!("Upstream error: {e}"));
+                }
+            };
             if let Some((resets_at, resets_in_seconds)) = parse_usage_limit_error(&bytes) {
                 let until = usage_limit_cooldown_until(resets_at, resets_in_seconds);
                 apply_usage_limit_policy(&state, chosen_idx, &chosen_id, until);
@@ -6961,7 +10871,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                     request_id,
                     chosen_idx,
                     &method,
-                    &target,
+                    &upstream_path,
                     &forward_headers,
                     &req_headers,
                     &upstream_body_bytes,
@@ -6969,6 +10879,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                     is_anthropic,
                     &request_model,
                     &request_body_text,
+                    request_body_truncated,
                     &request_headers_json,
                     &request_url,
                     &anthropic_reverse_tool_map,
@@ -6978,160 +10889,109 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                 ).await {
                     return resp;
                 }
-            }
-            return build_proxy_response_from_bytes(upstream_status, &headers, bytes);
-        }
-
-        // Handle 401: try token refresh once, then retry
-        if upstream_status == reqwest::StatusCode::UNAUTHORIZED {
-            if let Some(rt) = &chosen_refresh {
-                if let Some(new_token) = try_refresh_account(&chosen_id, rt).await {
-                    // Update pool with new token
-                    {
-                        let mut accounts_lock = state.accounts.write().unwrap();
-                        if let Some(acc) = accounts_lock.get_mut(chosen_idx) {
-                            acc.access_token = new_token.clone();
-                            acc.health = AccountHealth::Active;
-                        }
-                    }
-                    // Retry with refreshed token
-                    let mut retry_headers = forward_headers.clone();
-                    apply_upstream_headers(
-                        &mut retry_headers,
-                        &new_token,
-                        chosen_account_id.as_deref(),
-                        &req_headers,
-                        !upstream_body_bytes.is_empty(),
-                        is_stream,
-                    );
-                    if let Ok(retry_resp) = state.client
-                        .request(method.clone(), &target)
-                        .headers(retry_headers)
-                        .body(upstream_body_bytes.clone())
-                        .send()
-                        .await
-                    {
-                        let response_headers_json = headers_to_json_string(sanitize_reqwest_headers(retry_resp.headers()));
-                        if !is_stream {
-                            let status = retry_resp.status();
-                            let headers = retry_resp.headers().clone();
-                            let bytes = match retry_resp.bytes().await {
-                                Ok(b) => b,
-                                Err(e) => {
-                                    let entry = ProxyLogEntry {
-                                        timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
-                                        method: method_label.clone(),
-                                        path: path.to_string(),
-                                        request_url: request_url.clone(),
-                                        status: StatusCode::BAD_GATEWAY.as_u16(),
-                                        duration_ms: started_at.elapsed().as_millis() as u64,
-                                        proxy_account_id: chosen_id.clone(),
-                                        account_id: chosen_account_id.clone(),
-                                        error: Some(format!("{e}")),
-                                        model: request_model.clone(),
-                                        request_headers: request_headers_json.clone(),
-                                        response_headers: response_headers_json.clone(),
-                                        request_body: request_body_text.clone(),
-                                        response_body: None,
-                                        input_tokens: None,
-                                        output_tokens: None,
-                                    cache_status: None,
-                                    cache_key: None,
-                                    cache_eligible: None,
-                                    cache_bypass_reason: None,
-                                    local_cached_input_tokens: None,
-                                    provider_cached_input_tokens: None,
-                                    };
-                                    let _ = insert_proxy_log(&entry);
-                                    return Response::builder()
-                                        .status(StatusCode::BAD_GATEWAY)
-                                        .header("Access-Control-Allow-Origin", "*")
-                                        .body(Body::from(format!("Upstream error: {e}")))
-                                        .unwrap();
-                                }
-                            };
-                            if is_anthropic {
-                                let reverse_map = anthropic_reverse_tool_map.clone().unwrap_or_default();
-                                let model_name = request_model.clone().unwrap_or_default();
-                                let (converted, input_tokens, output_tokens, response_body_text) =
-                                    match build_claude_response_body(&bytes, &reverse_map, &model_name) {
-                                        Ok(v) => v,
-                                        Err(err) => {
-                                            let entry = ProxyLogEntry {
-                                                timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
-                                                method: method_label.clone(),
-                                                path: path.to_string(),
-                                                request_url: request_url.clone(),
-                                                status: StatusCode::BAD_GATEWAY.as_u16(),
-                                                duration_ms: started_at.elapsed().as_millis() as u64,
-                                                proxy_account_id: chosen_id.clone(),
-                                                account_id: chosen_account_id.clone(),
-                                                error: Some(err),
-                                                model: request_model.clone(),
-                                                request_headers: request_headers_json.clone(),
-                                                response_headers: response_headers_json.clone(),
-                                                request_body: request_body_text.clone(),
-                                                response_body: None,
-                                                input_tokens: None,
-                                                output_tokens: None,
-                                            cache_status: None,
-                                            cache_key: None,
-                                            cache_eligible: None,
-                                            cache_bypass_reason: None,
-                                            local_cached_input_tokens: None,
-                                            provider_cached_input_tokens: None,
-                                            };
-                                            let _ = insert_proxy_log(&entry);
-                                            return Response::builder()
-                                                .status(StatusCode::BAD_GATEWAY)
-                                                .header("Access-Control-Allow-Origin", "*")
-                                                .body(Body::from("Anthropic response conversion failed"))
-                                                .unwrap();
-                                        }
-                                    };
+            }
+            return build_proxy_response_from_bytes(upstream_status, &headers, bytes);
+        }
+
+        // Handle 401: try token refresh once, then retry
+        if upstream_status == reqwest::StatusCode::UNAUTHORIZED {
+            if let Some(new_token) =
+                refresh_after_unauthorized(&state, request_id, chosen_idx, &chosen_id, chosen_refresh.as_ref()).await
+            {
+                // Retry with refreshed token
+                let mut retry_headers = forward_headers.clone();
+                apply_upstream_headers(
+                    &mut retry_headers,
+                    &new_token,
+                    chosen_account_id.as_deref(),
+                    Some(&chosen_org),
+                    &req_headers,
+                    !upstream_body_bytes.is_empty(),
+                    is_stream,
+                );
+                if let Ok(retry_resp) = account_client(&state, chosen_idx)
+                    .request(method.clone(), &target)
+                    .headers(retry_headers)
+                    .body(upstream_body_bytes.clone())
+                    .send()
+                    .await
+                {
+                    let response_headers_json = headers_to_json_string(sanitize_reqwest_headers(retry_resp.headers()));
+                    if !is_stream {
+                        let status = retry_resp.status();
+                        let headers = retry_resp.headers().clone();
+                        let bytes = match read_capped_bytes(retry_resp).await {
+                            Ok(b) => b,
+                            Err(e) => {
                                 let entry = ProxyLogEntry {
                                     timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
                                     method: method_label.clone(),
                                     path: path.to_string(),
                                     request_url: request_url.clone(),
-                                    status: status.as_u16(),
+                                    status: StatusCode::BAD_GATEWAY.as_u16(),
                                     duration_ms: started_at.elapsed().as_millis() as u64,
                                     proxy_account_id: chosen_id.clone(),
                                     account_id: chosen_account_id.clone(),
-                                    error: None,
+                                    error: Some(format!("{e}")),
                                     model: request_model.clone(),
                                     request_headers: request_headers_json.clone(),
-                                    response_headers: headers_to_json_string(vec![
-                                        ("content-type".to_string(), "application/json".to_string()),
-                                    ]),
+                                    response_headers: response_headers_json.clone(),
                                     request_body: request_body_text.clone(),
-                                    response_body: response_body_text,
-                                    input_tokens,
-                                    output_tokens,
-                                    ..ProxyLogEntry::default()
+                                    request_body_truncated,
+                                    response_body: None,
+                                    response_body_truncated: false,
+                                    input_tokens: None,
+                                    output_tokens: None,
+                                cache_status: None,
+                                cache_key: None,
+                                cache_eligible: None,
+                                cache_bypass_reason: None,
+                                local_cached_input_tokens: None,
+                                provider_cached_input_tokens: None,
+                                request_id: Some(request_id as u64),
                                 };
                                 let _ = insert_proxy_log(&entry);
-                                let status = axum::http::StatusCode::from_u16(status.as_u16())
-                                    .unwrap_or(axum::http::StatusCode::INTERNAL_SERVER_ERROR);
-                                return Response::builder()
-                                    .status(status)
-                                    .header("Content-Type", "application/json")
-                                    .header("Access-Control-Allow-Origin", "*")
-                                    .header("Access-Control-Allow-Headers", "*")
-                                    .body(Body::from(converted))
-                                    .unwrap_or_else(|_| {
-                                        Response::builder()
-                                            .status(axum::http::StatusCode::INTERNAL_SERVER_ERROR)
-                                            .body(Body::empty())
-                                            .unwrap()
-                                    });
+                                return proxy_error_response(StatusCode::BAD_GATEWAY, "api_error", "upstream_error", format!("Upstream error: {e}"));
                             }
-                            let response_body_text = if bytes.is_empty() {
-                                None
-                            } else {
-                                Some(truncate_body(&bytes))
-                            };
-                            let (input_tokens, output_tokens) = extract_usage(&bytes);
+                        };
+                        if is_anthropic {
+                            let reverse_map = anthropic_reverse_tool_map.clone().unwrap_or_default();
+                            let model_name = request_model.clone().unwrap_or_default();
+                            let (converted, input_tokens, output_tokens, response_body_text, response_body_truncated) =
+                                match build_claude_response_body(&cfg, &bytes, &reverse_map, &model_name) {
+                                    Ok(v) => v,
+                                    Err(err) => {
+                                        let entry = ProxyLogEntry {
+                                            timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                                            method: method_label.clone(),
+                                            path: path.to_string(),
+                                            request_url: request_url.clone(),
+                                            status: StatusCode::BAD_GATEWAY.as_u16(),
+                                            duration_ms: started_at.elapsed().as_millis() as u64,
+                                            proxy_account_id: chosen_id.clone(),
+                                            account_id: chosen_account_id.clone(),
+                                            error: Some(err),
+                                            model: request_model.clone(),
+                                            request_headers: request_headers_json.clone(),
+                                            response_headers: response_headers_json.clone(),
+                                            request_body: request_body_text.clone(),
+                                            request_body_truncated,
+                                            response_body: None,
+                                            response_body_truncated: false,
+                                            input_tokens: None,
+                                            output_tokens: None,
+                                        cache_status: None,
+                                        cache_key: None,
+                                        cache_eligible: None,
+                                        cache_bypass_reason: None,
+                                        local_cached_input_tokens: None,
+                                        provider_cached_input_tokens: None,
+                                        request_id: Some(request_id as u64),
+                                        };
+                                        let _ = insert_proxy_log(&entry);
+                                        return proxy_error_response(StatusCode::BAD_GATEWAY, "api_error", "response_conversion_failed", "Anthropic response conversion failed");
+                                    }
+                                };
                             let entry = ProxyLogEntry {
                                 timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
                                 method: method_label.clone(),
@@ -7144,47 +11004,62 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                                 error: None,
                                 model: request_model.clone(),
                                 request_headers: request_headers_json.clone(),
-                                response_headers: response_headers_json.clone(),
+                                response_headers: headers_to_json_string(vec![
+                                    ("content-type".to_string(), "application/json".to_string()),
+                                ]),
                                 request_body: request_body_text.clone(),
+                                request_body_truncated,
                                 response_body: response_body_text,
+                                response_body_truncated,
                                 input_tokens,
                                 output_tokens,
+                                request_id: Some(request_id as u64),
                                 ..ProxyLogEntry::default()
                             };
                             let _ = insert_proxy_log(&entry);
-                            return build_proxy_response_from_bytes(status, &headers, bytes);
-                        }
-                        if is_anthropic {
-                            let entry = ProxyLogEntry {
-                                timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
-                                method: method_label.clone(),
-                                path: path.to_string(),
-                                request_url: request_url.clone(),
-                                status: retry_resp.status().as_u16(),
-                                duration_ms: started_at.elapsed().as_millis() as u64,
-                                proxy_account_id: chosen_id.clone(),
-                                account_id: chosen_account_id.clone(),
-                                error: None,
-                                model: request_model.clone(),
-                                request_headers: request_headers_json.clone(),
-                                response_headers: headers_to_json_string(vec![
-                                    ("content-type".to_string(), "text/event-stream".to_string()),
-                                ]),
-                                request_body: request_body_text.clone(),
-                                response_body: None,
-                                input_tokens: None,
-                                output_tokens: None,
-                            cache_status: None,
-                            cache_key: None,
-                            cache_eligible: None,
-                            cache_bypass_reason: None,
-                            local_cached_input_tokens: None,
-                            provider_cached_input_tokens: None,
-                            };
-                            let reverse_map = anthropic_reverse_tool_map.clone().unwrap_or_default();
-                            return build_anthropic_stream_response(retry_resp, reverse_map, entry).await;
+                            let status = axum::http::StatusCode::from_u16(status.as_u16())
+                                .unwrap_or(axum::http::StatusCode::INTERNAL_SERVER_ERROR);
+                            return Response::builder()
+                                .status(status)
+                                .header("Content-Type", "application/json")
+                                .header("Access-Control-Allow-Origin", "*")
+                                .header("Access-Control-Allow-Headers", "*")
+                                .body(Body::from(converted))
+                                .unwrap_or_else(|_| {
+                                    Response::builder()
+                                        .status(axum::http::StatusCode::INTERNAL_SERVER_ERROR)
+                                        .body(Body::empty())
+                                        .unwrap()
+                                });
                         }
-
+                        let (response_body_text, response_body_truncated) = log_body_field(&cfg, &bytes);
+                        let (input_tokens, output_tokens) = extract_usage(&bytes);
+                        let entry = ProxyLogEntry {
+                            timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                            method: method_label.clone(),
+                            path: path.to_string(),
+                            request_url: request_url.clone(),
+                            status: status.as_u16(),
+                            duration_ms: started_at.elapsed().as_millis() as u64,
+                            proxy_account_id: chosen_id.clone(),
+                            account_id: chosen_account_id.clone(),
+                            error: None,
+                            model: request_model.clone(),
+                            request_headers: request_headers_json.clone(),
+                            response_headers: response_headers_json.clone(),
+                            request_body: request_body_text.clone(),
+                            request_body_truncated,
+                            response_body: response_body_text,
+                            response_body_truncated,
+                            input_tokens,
+                            output_tokens,
+                            request_id: Some(request_id as u64),
+                            ..ProxyLogEntry::default()
+                        };
+                        let _ = insert_proxy_log(&entry);
+                        return build_proxy_response_from_bytes(status, &headers, bytes);
+                    }
+                    if is_anthropic {
                         let entry = ProxyLogEntry {
                             timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
                             method: method_label.clone(),
@@ -7197,9 +11072,13 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                             error: None,
                             model: request_model.clone(),
                             request_headers: request_headers_json.clone(),
-                            response_headers: response_headers_json,
+                            response_headers: headers_to_json_string(vec![
+                                ("content-type".to_string(), "text/event-stream".to_string()),
+                            ]),
                             request_body: request_body_text.clone(),
+                            request_body_truncated,
                             response_body: None,
+                            response_body_truncated: false,
                             input_tokens: None,
                             output_tokens: None,
                         cache_status: None,
@@ -7208,27 +11087,48 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                         cache_bypass_reason: None,
                         local_cached_input_tokens: None,
                         provider_cached_input_tokens: None,
+                        request_id: Some(request_id as u64),
                         };
-                        let _ = insert_proxy_log(&entry);
-                        return build_proxy_response(retry_resp).await;
+                        let reverse_map = anthropic_reverse_tool_map.clone().unwrap_or_default();
+                        return build_anthropic_stream_response(retry_resp, reverse_map, entry).await;
                     }
-                }
-            }
-            // Refresh failed or no refresh token → mark blocked
-            {
-                let mut accounts_lock = state.accounts.write().unwrap();
-                if let Some(acc) = accounts_lock.get_mut(chosen_idx) {
-                    acc.health = AccountHealth::Blocked;
+
+                    let entry = ProxyLogEntry {
+                        timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                        method: method_label.clone(),
+                        path: path.to_string(),
+                        request_url: request_url.clone(),
+                        status: retry_resp.status().as_u16(),
+                        duration_ms: started_at.elapsed().as_millis() as u64,
+                        proxy_account_id: chosen_id.clone(),
+                        account_id: chosen_account_id.clone(),
+                        error: None,
+                        model: request_model.clone(),
+                        request_headers: request_headers_json.clone(),
+                        response_headers: response_headers_json,
+                        request_body: request_body_text.clone(),
+                        request_body_truncated,
+                        response_body: None,
+                        response_body_truncated: false,
+                        input_tokens: None,
+                        output_tokens: None,
+                    cache_status: None,
+                    cache_key: None,
+                    cache_eligible: None,
+                    cache_bypass_reason: None,
+                    local_cached_input_tokens: None,
+                    provider_cached_input_tokens: None,
+                    request_id: Some(request_id as u64),
+                    };
+                    let _ = insert_proxy_log(&entry);
+                    return build_proxy_response(retry_resp).await;
                 }
             }
         } else if upstream_status == reqwest::StatusCode::FORBIDDEN {
-            let mut accounts_lock = state.accounts.write().unwrap();
-            if let Some(acc) = accounts_lock.get_mut(chosen_idx) {
-                acc.health = AccountHealth::Blocked;
-            }
+            transition_account_health(&state, chosen_idx, AccountHealth::Blocked, "forbidden", Some(403));
         } else if upstream_status == reqwest::StatusCode::TOO_MANY_REQUESTS {
             let headers = upstream_resp.headers().clone();
-            let bytes = match upstream_resp.bytes().await {
+            let bytes = match read_capped_bytes(upstream_resp).await {
                 Ok(b) => b,
                 Err(e) => {
                     let entry = ProxyLogEntry {
@@ -7245,7 +11145,9 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                         request_headers: request_headers_json.clone(),
                         response_headers: None,
                         request_body: request_body_text.clone(),
+                        request_body_truncated,
                         response_body: None,
+                        response_body_truncated: false,
                         input_tokens: None,
                         output_tokens: None,
                     cache_status: None,
@@ -7254,27 +11156,21 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                     cache_bypass_reason: None,
                     local_cached_input_tokens: None,
                     provider_cached_input_tokens: None,
+                    request_id: Some(request_id as u64),
                     };
                     let _ = insert_proxy_log(&entry);
-                    return Response::builder()
-                        .status(StatusCode::BAD_GATEWAY)
-                        .header("Access-Control-Allow-Origin", "*")
-                        .body(Body::from(format!("Upstream error: {e}")))
-                        .unwrap();
+                    return proxy_error_response(StatusCode::BAD_GATEWAY, "api_error", "upstream_error", format!("Upstream error: {e}"));
                 }
             };
 
-            let usage_limit = parse_usage_limit_error(&bytes);
-            if let Some((resets_at, resets_in_seconds)) = usage_limit {
-                let until = usage_limit_cooldown_until(resets_at, resets_in_seconds);
-                apply_usage_limit_policy(&state, chosen_idx, &chosen_id, until);
+            if record_rate_limit(&state, chosen_idx, &chosen_id, &bytes) {
                 log_proxy(&format!("req#{request_id} usage_limit_reached on {chosen_id}, selecting highest quota account"));
                 if let Some(resp) = retry_usage_limit_across_accounts(
                     state.clone(),
                     request_id,
                     chosen_idx,
                     &method,
-                    &target,
+                    &upstream_path,
                     &forward_headers,
                     &req_headers,
                     &upstream_body_bytes,
@@ -7282,6 +11178,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                     is_anthropic,
                     &request_model,
                     &request_body_text,
+                    request_body_truncated,
                     &request_headers_json,
                     &request_url,
                     &anthropic_reverse_tool_map,
@@ -7292,27 +11189,13 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                     return resp;
                 }
             } else {
-                let until = std::time::Instant::now()
-                    + std::time::Duration::from_secs(COOLDOWN_SECS);
-                {
-                    let mut accounts_lock = state.accounts.write().unwrap();
-                    if let Some(acc) = accounts_lock.get_mut(chosen_idx) {
-                        acc.health = AccountHealth::Cooldown(until);
-                    }
-                }
                 log_proxy(&format!("req#{request_id} 429 on {chosen_id}, trying another account"));
 
                 // Pick a different healthy account from the pool and retry immediately
-                let fallback = {
+                let (fallback, revived) = {
                     let now = std::time::Instant::now();
                     let mut accounts_lock = state.accounts.write().unwrap();
-                    for acc in accounts_lock.iter_mut() {
-                        if let AccountHealth::Cooldown(u) = &acc.health {
-                            if now >= *u {
-                                acc.health = AccountHealth::Active;
-                            }
-                        }
-                    }
+                    let revived = revive_elapsed_cooldowns(&mut accounts_lock, now);
                     let pool_size = accounts_lock.len();
                     let start = state.req_counter.load(Ordering::SeqCst);
                     let mut found = None;
@@ -7322,25 +11205,34 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                             found = Some((
                                 accounts_lock[idx].access_token.clone(),
                                 accounts_lock[idx].account_id.clone(),
+                                accounts_lock[idx].org.clone(),
+                                idx,
                             ));
                             break;
                         }
                     }
-                    found
+                    (found, revived)
                 };
-
-                if let Some((fallback_token, fallback_account_id)) = fallback {
+                record_revived_accounts(&revived);
+
+                // Sent to the fallback account's own upstream, never one that loops back here
+                let fallback = fallback
+                    .map(|(token, account_id, org, idx)| (token, account_id, org, idx, account_upstream_url(&state, idx, &upstream_path)))
+                    .filter(|fallback| !upstream_loops_back_to_proxy(&fallback.4));
+                if let Some((fallback_token, fallback_account_id, fallback_org, fallback_idx, fallback_target)) = fallback {
+                    request_url = Some(fallback_target.clone());
                     let mut retry_headers = forward_headers.clone();
                     apply_upstream_headers(
                         &mut retry_headers,
                         &fallback_token,
                         fallback_account_id.as_deref(),
+                        Some(&fallback_org),
                         &req_headers,
                         !upstream_body_bytes.is_empty(),
                         is_stream,
                     );
-                    if let Ok(retry_resp) = state.client
-                        .request(method.clone(), &target)
+                    if let Ok(retry_resp) = account_client(&state, fallback_idx)
+                        .request(method.clone(), &fallback_target)
                         .headers(retry_headers)
                         .body(upstream_body_bytes.clone())
                         .send()
@@ -7351,12 +11243,37 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                         let resp_hdrs_json = headers_to_json_string(sanitize_reqwest_headers(retry_resp.headers()));
                         if !is_stream {
                             let headers = retry_resp.headers().clone();
-                            let bytes = retry_resp.bytes().await.unwrap_or_default();
+                            let bytes = match read_capped_bytes(retry_resp).await {
+                                Ok(b) => b,
+                                Err(e) => {
+                                    log_proxy(&format!("req#{request_id} upstream body read failed: {e}"));
+                                    let entry = ProxyLogEntry {
+                                        timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                                        method: method_label.clone(),
+                                        path: path.to_string(),
+                                        request_url: request_url.clone(),
+                                        status: StatusCode::BAD_GATEWAY.as_u16(),
+                                        duration_ms: started_at.elapsed().as_millis() as u64,
+                                        proxy_account_id: chosen_id.clone(),
+                                        account_id: fallback_account_id.clone(),
+                                        error: Some(e.clone()),
+                                        model: request_model.clone(),
+                                        request_headers: request_headers_json.clone(),
+                                        response_headers: resp_hdrs_json.clone(),
+                                        request_body: request_body_text.clone(),
+                                        request_body_truncated,
+                                        request_id: Some(request_id as u64),
+                                        ..ProxyLogEntry::default()
+                                    };
+                                    let _ = insert_proxy_log(&entry);
+                                    return proxy_error_response(StatusCode::BAD_GATEWAY, "api_error", "upstream_error", format!("Upstream error: {e}"));
+                                }
+                            };
                             if is_anthropic {
                                 let reverse_map = anthropic_reverse_tool_map.clone().unwrap_or_default();
                                 let model_name = request_model.clone().unwrap_or_default();
-                                let (converted, input_tokens, output_tokens, response_body_text) =
-                                    match build_claude_response_body(&bytes, &reverse_map, &model_name) {
+                                let (converted, input_tokens, output_tokens, response_body_text, response_body_truncated) =
+                                    match build_claude_response_body(&cfg, &bytes, &reverse_map, &model_name) {
                                         Ok(v) => v,
                                         Err(err) => {
                                             let entry = ProxyLogEntry {
@@ -7373,7 +11290,9 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                                                 request_headers: request_headers_json.clone(),
                                                 response_headers: resp_hdrs_json.clone(),
                                                 request_body: request_body_text.clone(),
+                                                request_body_truncated,
                                                 response_body: None,
+                                                response_body_truncated: false,
                                                 input_tokens: None,
                                                 output_tokens: None,
                                             cache_status: None,
@@ -7382,13 +11301,10 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                                             cache_bypass_reason: None,
                                             local_cached_input_tokens: None,
                                             provider_cached_input_tokens: None,
+                                            request_id: Some(request_id as u64),
                                             };
                                             let _ = insert_proxy_log(&entry);
-                                            return Response::builder()
-                                                .status(StatusCode::BAD_GATEWAY)
-                                                .header("Access-Control-Allow-Origin", "*")
-                                                .body(Body::from("Anthropic response conversion failed"))
-                                                .unwrap();
+                                            return proxy_error_response(StatusCode::BAD_GATEWAY, "api_error", "response_conversion_failed", "Anthropic response conversion failed");
                                         }
                                     };
                                 let entry = ProxyLogEntry {
@@ -7407,9 +11323,12 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                                         ("content-type".to_string(), "application/json".to_string()),
                                     ]),
                                     request_body: request_body_text.clone(),
+                                    request_body_truncated,
                                     response_body: response_body_text,
+                                    response_body_truncated,
                                     input_tokens,
                                     output_tokens,
+                                    request_id: Some(request_id as u64),
                                     ..ProxyLogEntry::default()
                                 };
                                 let _ = insert_proxy_log(&entry);
@@ -7428,7 +11347,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                                             .unwrap()
                                     });
                             }
-                            let response_body_text = if bytes.is_empty() { None } else { Some(truncate_body(&bytes)) };
+                            let (response_body_text, response_body_truncated) = log_body_field(&cfg, &bytes);
                             let (input_tokens, output_tokens) = extract_usage(&bytes);
                             let entry = ProxyLogEntry {
                                 timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
@@ -7444,9 +11363,12 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                                 request_headers: request_headers_json.clone(),
                                 response_headers: resp_hdrs_json,
                                 request_body: request_body_text.clone(),
+                                request_body_truncated,
                                 response_body: response_body_text,
+                                response_body_truncated,
                                 input_tokens,
                                 output_tokens,
+                                request_id: Some(request_id as u64),
                                 ..ProxyLogEntry::default()
                             };
                             let _ = insert_proxy_log(&entry);
@@ -7469,7 +11391,9 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                                     ("content-type".to_string(), "text/event-stream".to_string()),
                                 ]),
                                 request_body: request_body_text.clone(),
+                                request_body_truncated,
                                 response_body: None,
+                                response_body_truncated: false,
                                 input_tokens: None,
                                 output_tokens: None,
                             cache_status: None,
@@ -7478,6 +11402,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                             cache_bypass_reason: None,
                             local_cached_input_tokens: None,
                             provider_cached_input_tokens: None,
+                            request_id: Some(request_id as u64),
                             };
                             let reverse_map = anthropic_reverse_tool_map.clone().unwrap_or_default();
                             return build_anthropic_stream_response(retry_resp, reverse_map, entry).await;
@@ -7496,7 +11421,9 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                             request_headers: request_headers_json.clone(),
                             response_headers: resp_hdrs_json,
                             request_body: request_body_text.clone(),
+                            request_body_truncated,
                             response_body: None,
+                            response_body_truncated: false,
                             input_tokens: None,
                             output_tokens: None,
                         cache_status: None,
@@ -7505,6 +11432,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                         cache_bypass_reason: None,
                         local_cached_input_tokens: None,
                         provider_cached_input_tokens: None,
+                        request_id: Some(request_id as u64),
                         };
                         let _ = insert_proxy_log(&entry);
                         return build_proxy_response(retry_resp).await;
@@ -7512,7 +11440,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                 }
             }
 
-            let response_body_text = if bytes.is_empty() { None } else { Some(truncate_body(&bytes)) };
+            let (response_body_text, response_body_truncated) = log_body_field(&cfg, &bytes);
             let (input_tokens, output_tokens) = extract_usage(&bytes);
             let entry = ProxyLogEntry {
                 timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
@@ -7528,9 +11456,12 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                 request_headers: request_headers_json.clone(),
                 response_headers: headers_to_json_string(sanitize_reqwest_headers(&headers)),
                 request_body: request_body_text.clone(),
+                request_body_truncated,
                 response_body: response_body_text,
+                response_body_truncated,
                 input_tokens,
                 output_tokens,
+                request_id: Some(request_id as u64),
                 ..ProxyLogEntry::default()
             };
             let _ = insert_proxy_log(&entry);
@@ -7538,10 +11469,29 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
         }
 
         let response_headers_json = headers_to_json_string(sanitize_reqwest_headers(upstream_resp.headers()));
+        if method == reqwest::Method::HEAD {
+            let headers = upstream_resp.headers().clone();
+            let entry = ProxyLogEntry {
+                timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                method: method_label.clone(),
+                path: path.to_string(),
+                request_url: request_url.clone(),
+                status: upstream_status.as_u16(),
+                duration_ms: started_at.elapsed().as_millis() as u64,
+                proxy_account_id: chosen_id.clone(),
+                account_id: chosen_account_id.clone(),
+                request_headers: request_headers_json.clone(),
+                response_headers: response_headers_json,
+                request_id: Some(request_id as u64),
+                ..ProxyLogEntry::default()
+            };
+            let _ = insert_proxy_log(&entry);
+            return build_proxy_response_from_bytes(upstream_status, &headers, Bytes::new());
+        }
         if !is_stream {
             let status = upstream_resp.status();
             let headers = upstream_resp.headers().clone();
-            let bytes = match upstream_resp.bytes().await {
+            let bytes = match read_capped_bytes(upstream_resp).await {
                 Ok(b) => b,
                 Err(e) => {
                     let entry = ProxyLogEntry {
@@ -7558,7 +11508,9 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                         request_headers: request_headers_json.clone(),
                         response_headers: response_headers_json.clone(),
                         request_body: request_body_text.clone(),
+                        request_body_truncated,
                         response_body: None,
+                        response_body_truncated: false,
                         input_tokens: None,
                         output_tokens: None,
                     cache_status: None,
@@ -7567,13 +11519,10 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                     cache_bypass_reason: None,
                     local_cached_input_tokens: None,
                     provider_cached_input_tokens: None,
+                    request_id: Some(request_id as u64),
                     };
                     let _ = insert_proxy_log(&entry);
-                    return Response::builder()
-                        .status(StatusCode::BAD_GATEWAY)
-                        .header("Access-Control-Allow-Origin", "*")
-                        .body(Body::from(format!("Upstream error: {e}")))
-                        .unwrap();
+                    return proxy_error_response(StatusCode::BAD_GATEWAY, "api_error", "upstream_error", format!("Upstream error: {e}"));
                 }
             };
             if status == reqwest::StatusCode::BAD_REQUEST {
@@ -7588,7 +11537,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                     request_id,
                     chosen_idx,
                     &method,
-                        &target,
+                        &upstream_path,
                         &forward_headers,
                         &req_headers,
                         &upstream_body_bytes,
@@ -7596,6 +11545,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                         is_anthropic,
                         &request_model,
                         &request_body_text,
+                        request_body_truncated,
                         &request_headers_json,
                         &request_url,
                         &anthropic_reverse_tool_map,
@@ -7610,8 +11560,8 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
             if is_anthropic {
                 let reverse_map = anthropic_reverse_tool_map.clone().unwrap_or_default();
                 let model_name = request_model.clone().unwrap_or_default();
-                let (converted, input_tokens, output_tokens, response_body_text) =
-                    match build_claude_response_body(&bytes, &reverse_map, &model_name) {
+                let (converted, input_tokens, output_tokens, response_body_text, response_body_truncated) =
+                    match build_claude_response_body(&cfg, &bytes, &reverse_map, &model_name) {
                         Ok(v) => v,
                         Err(err) => {
                             let entry = ProxyLogEntry {
@@ -7628,7 +11578,9 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                                 request_headers: request_headers_json.clone(),
                                 response_headers: response_headers_json.clone(),
                                 request_body: request_body_text.clone(),
+                                request_body_truncated,
                                 response_body: None,
+                                response_body_truncated: false,
                                 input_tokens: None,
                                 output_tokens: None,
                             cache_status: None,
@@ -7637,13 +11589,10 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                             cache_bypass_reason: None,
                             local_cached_input_tokens: None,
                             provider_cached_input_tokens: None,
+                            request_id: Some(request_id as u64),
                             };
                             let _ = insert_proxy_log(&entry);
-                            return Response::builder()
-                                .status(StatusCode::BAD_GATEWAY)
-                                .header("Access-Control-Allow-Origin", "*")
-                                .body(Body::from("Anthropic response conversion failed"))
-                                .unwrap();
+                            return proxy_error_response(StatusCode::BAD_GATEWAY, "api_error", "response_conversion_failed", "Anthropic response conversion failed");
                         }
                     };
                 let entry = ProxyLogEntry {
@@ -7662,12 +11611,23 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                         ("content-type".to_string(), "application/json".to_string()),
                     ]),
                     request_body: request_body_text.clone(),
+                    request_body_truncated,
                     response_body: response_body_text,
+                    response_body_truncated,
                     input_tokens,
                     output_tokens,
+                    request_id: Some(request_id as u64),
                     ..ProxyLogEntry::default()
                 };
                 let _ = insert_proxy_log(&entry);
+                if let Some(key) = &idempotency_key {
+                    let mut converted_headers = reqwest::header::HeaderMap::new();
+                    converted_headers.insert(
+                        reqwest::header::CONTENT_TYPE,
+                        reqwest::header::HeaderValue::from_static("application/json"),
+                    );
+                    idempotency_store(&state, key, status, &converted_headers, &converted);
+                }
                 let status = axum::http::StatusCode::from_u16(status.as_u16())
                     .unwrap_or(axum::http::StatusCode::INTERNAL_SERVER_ERROR);
                 return Response::builder()
@@ -7683,11 +11643,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                             .unwrap()
                     });
             }
-            let response_body_text = if bytes.is_empty() {
-                None
-            } else {
-                Some(truncate_body(&bytes))
-            };
+            let (response_body_text, response_body_truncated) = log_body_field(&cfg, &bytes);
             let (input_tokens, output_tokens) = extract_usage(&bytes);
             let entry = ProxyLogEntry {
                 timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
@@ -7703,12 +11659,24 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                 request_headers: request_headers_json.clone(),
                 response_headers: response_headers_json.clone(),
                 request_body: request_body_text.clone(),
+                request_body_truncated,
                 response_body: response_body_text,
+                response_body_truncated,
                 input_tokens,
                 output_tokens,
+                request_id: Some(request_id as u64),
                 ..ProxyLogEntry::default()
             };
             let _ = insert_proxy_log(&entry);
+            if cache_models_path && status.is_success() {
+                store_models_cache(&state, &upstream_path, &headers, &bytes);
+            }
+            if get_cache_ttl.is_some() {
+                get_cache_store(&state, &get_cache_key(&upstream_path, &chosen_id, &client_key), status, &headers, &bytes);
+            }
+            if let Some(key) = &idempotency_key {
+                idempotency_store(&state, key, status, &headers, &bytes);
+            }
             return build_proxy_response_from_bytes(status, &headers, bytes);
         }
 
@@ -7729,7 +11697,9 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
                     ("content-type".to_string(), "text/event-stream".to_string()),
                 ]),
                 request_body: request_body_text.clone(),
+                request_body_truncated,
                 response_body: None,
+                response_body_truncated: false,
                 input_tokens: None,
                 output_tokens: None,
             cache_status: None,
@@ -7738,6 +11708,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
             cache_bypass_reason: None,
             local_cached_input_tokens: None,
             provider_cached_input_tokens: None,
+            request_id: Some(request_id as u64),
             };
             let reverse_map = anthropic_reverse_tool_map.clone().unwrap_or_default();
             return build_anthropic_stream_response(upstream_resp, reverse_map, entry).await;
@@ -7757,7 +11728,9 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
             request_headers: request_headers_json.clone(),
             response_headers: response_headers_json,
             request_body: request_body_text.clone(),
+            request_body_truncated,
             response_body: None,
+            response_body_truncated: false,
             input_tokens: None,
             output_tokens: None,
         cache_status: None,
@@ -7766,6 +11739,7 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
         cache_bypass_reason: None,
         local_cached_input_tokens: None,
         provider_cached_input_tokens: None,
+        request_id: Some(request_id as u64),
         };
         let _ = insert_proxy_log(&entry);
         build_proxy_response(upstream_resp).await
@@ -7776,21 +11750,26 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
         let status = axum::http::StatusCode::from_u16(upstream_status.as_u16())
             .unwrap_or(axum::http::StatusCode::INTERNAL_SERVER_ERROR);
 
+        let cfg = proxy_config_snapshot();
         let mut builder = Response::builder().status(status);
-        for (k, v) in upstream_resp.headers() {
-            if skip_response_header(k.as_str()) { continue; }
-            if let (Ok(name), Ok(val)) = (
-                axum::http::HeaderName::from_bytes(k.as_str().as_bytes()),
-                axum::http::HeaderValue::from_bytes(v.as_bytes()),
-            ) {
-                builder = builder.header(name, val);
-            }
+        for (name, val) in forwarded_response_headers(upstream_resp.headers(), &cfg) {
+            builder = builder.header(name, val);
         }
         builder = builder
             .header("Access-Control-Allow-Origin", "*")
             .header("Access-Control-Allow-Headers", "*");
 
-        let stream = upstream_resp.bytes_stream();
+        let is_sse = upstream_resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_ascii_lowercase().contains("text/event-stream"))
+            .unwrap_or(false);
+        let heartbeat_secs = cfg.sse_heartbeat_secs.filter(|v| *v > 0);
+        let mut stream = capped_bytes_stream(upstream_resp);
+        if let (true, Some(secs)) = (is_sse, heartbeat_secs) {
+            stream = with_sse_heartbeat(stream, std::time::Duration::from_secs(secs));
+        }
         builder.body(Body::from_stream(stream)).unwrap_or_else(|_| {
             Response::builder()
                 .status(axum::http::StatusCode::INTERNAL_SERVER_ERROR)
@@ -7806,15 +11785,10 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
     ) -> Response<Body> {
         let status = axum::http::StatusCode::from_u16(status.as_u16())
             .unwrap_or(axum::http::StatusCode::INTERNAL_SERVER_ERROR);
+        let cfg = proxy_config_snapshot();
         let mut builder = Response::builder().status(status);
-        for (k, v) in headers.iter() {
-            if skip_response_header(k.as_str()) { continue; }
-            if let (Ok(name), Ok(val)) = (
-                axum::http::HeaderName::from_bytes(k.as_str().as_bytes()),
-                axum::http::HeaderValue::from_bytes(v.as_bytes()),
-            ) {
-                builder = builder.header(name, val);
-            }
+        for (name, val) in forwarded_response_headers(headers, &cfg) {
+            builder = builder.header(name, val);
         }
         builder = builder
             .header("Access-Control-Allow-Origin", "*")
@@ -7827,6 +11801,47 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
         })
     }
 
+    fn circuit_open_response(retry_after: u64) -> Response<Body> {
+        let mut resp = proxy_error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "server_error",
+            "circuit_open",
+            "Upstream is failing; requests are paused while it recovers",
+        );
+        if let Ok(value) = axum::http::HeaderValue::from_str(&retry_after.to_string()) {
+            resp.headers_mut().insert("Retry-After", value);
+        }
+        resp
+    }
+
+    /// Proxy-side error in OpenAI's `{ "error": { message, type, code } }` shape.
+    fn proxy_error_response(
+        status: StatusCode,
+        error_type: &str,
+        code: &str,
+        message: impl Into<String>,
+    ) -> Response<Body> {
+        let body = serde_json::json!({
+            "error": {
+                "message": message.into(),
+                "type": error_type,
+                "code": code,
+            }
+        });
+        Response::builder()
+            .status(status)
+            .header("Content-Type", "application/json")
+            .header("Access-Control-Allow-Origin", "*")
+            .header("Access-Control-Allow-Headers", "*")
+            .body(Body::from(body.to_string()))
+            .unwrap_or_else(|_| {
+                Response::builder()
+                    .status(axum::http::StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(Body::empty())
+                    .unwrap()
+            })
+    }
+
     fn build_cached_response(status: u16, content_type: &str, body: Bytes) -> Response<Body> {
         Response::builder()
             .status(axum::http::StatusCode::from_u16(status).unwrap_or(StatusCode::OK))
@@ -7859,14 +11874,90 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
 
     log_proxy("router ready");
 
-    let addr = format!("localhost:{proxy_port}");
-    log_proxy(&format!("starting proxy server: addr={addr}"));
-    let shutdown_notify = Arc::new(Notify::new());
-    let shutdown_waiter = shutdown_notify.clone();
-    tauri::async_runtime::spawn(async move {
-        let _ = shutdown_rx.await;
-        shutdown_waiter.notify_waiters();
-    });
+    let addr = format!("localhost:{proxy_port}");
+    log_proxy(&format!("starting proxy server: addr={addr}"));
+    let shutdown_notify = Arc::new(Notify::new());
+    let shutdown_waiter = shutdown_notify.clone();
+    tauri::async_runtime::spawn(async move {
+        let _ = shutdown_rx.await;
+        shutdown_waiter.notify_waiters();
+    });
+
+    if let Some(idle_secs) = cfg.idle_shutdown_secs.filter(|v| *v > 0) {
+        let idle_window = std::time::Duration::from_secs(idle_secs);
+        let idle_state = proxy_state.clone();
+        let idle_notify = shutdown_notify.clone();
+        tauri::async_runtime::spawn(async move {
+            loop {
+                // Registered up front so a manual stop is never missed
+                let stopped = idle_notify.notified();
+                let deadline = *idle_state.last_request_at.lock().unwrap() + idle_window;
+                tokio::select! {
+                    _ = stopped => return,
+                    _ = tokio::time::sleep_until(deadline.into()) => {}
+                }
+                let idle_for = idle_state.last_request_at.lock().unwrap().elapsed();
+                if idle_for >= idle_window {
+                    log_proxy(&format!("idle shutdown: no requests for {}s", idle_for.as_secs()));
+                    PROXY_SHUTDOWN.lock().unwrap().take();
+                    idle_notify.notify_waiters();
+                    return;
+                }
+            }
+        });
+    }
+
+    if let Some(reload_secs) = cfg.auto_reload_secs.filter(|v| *v > 0) {
+        let interval = std::time::Duration::from_secs(reload_secs);
+        let reload_state = proxy_state.clone();
+        let reload_notify = shutdown_notify.clone();
+        tauri::async_runtime::spawn(async move {
+            let mut listing = account_dir_listing();
+            loop {
+                let stopped = reload_notify.notified();
+                tokio::select! {
+                    _ = stopped => return,
+                    _ = tokio::time::sleep(interval) => {}
+                }
+                // A stop that landed mid-reload isn't seen by `notified`; the
+                // state being gone (or replaced by a restart) is
+                let live = PROXY_STATE.lock().unwrap().as_ref().is_some_and(|s| Arc::ptr_eq(s, &reload_state));
+                if !live {
+                    return;
+                }
+                let current = account_dir_listing();
+                if current == listing {
+                    continue;
+                }
+                match reload_pool_preserving_health(&reload_state) {
+                    Ok(count) => log_proxy(&format!("auto reload: accounts changed on disk, pool now {count}")),
+                    Err(err) => log_proxy(&format!("auto reload failed: {err}")),
+                }
+                listing = current;
+            }
+        });
+    }
+
+    {
+        let interval = std::time::Duration::from_secs(LAST_USED_WRITE_INTERVAL_SECS);
+        let used_state = proxy_state.clone();
+        let used_notify = shutdown_notify.clone();
+        tauri::async_runtime::spawn(async move {
+            loop {
+                let stopped = used_notify.notified();
+                let stopping = tokio::select! {
+                    _ = stopped => true,
+                    _ = tokio::time::sleep(interval) => {
+                        !PROXY_STATE.lock().unwrap().as_ref().is_some_and(|s| Arc::ptr_eq(s, &used_state))
+                    }
+                };
+                flush_last_used();
+                if stopping {
+                    return;
+                }
+            }
+        });
+    }
 
     // Store live state before running server.
     {
@@ -7878,8 +11969,9 @@ async fn start_api_proxy(port: Option<u16>) -> Result<Value, String> {
         *lock = Some(proxy_port);
     }
 
+    let bind_state = proxy_state.clone();
     tauri::async_runtime::spawn(async move {
-        let serve_result = run_proxy_server(&addr, app, shutdown_notify).await;
+        let serve_result = run_proxy_server(&addr, app, shutdown_notify, Some(&bind_state.bindings)).await;
 
         if let Err(err) = serve_result {
             log_proxy(&format!("server exited with error: {err}"));
@@ -8019,7 +12111,7 @@ async fn list_openai_compat_provider_models(config_id: String) -> Result<Vec<Str
         .map_err(|e| e.to_string())?;
     let status = resp.status().as_u16();
     let body_bytes = resp.bytes().await.map_err(|e| e.to_string())?;
-    let body_text = truncate_body(&body_bytes);
+    let (body_text, _) = cap_body_text(&body_bytes);
     log_proxy(&format!("openai-compat models status {status} body={body_text}"));
     if (200..300).contains(&status) {
         let body: Value = serde_json::from_slice(&body_bytes).map_err(|e| e.to_string())?;
@@ -8090,6 +12182,16 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
     async fn openai_compat_proxy_handler(
         State(state): State<Arc<OpenAICompatProxyState>>,
         req: axum::http::Request<Body>,
+    ) -> Response<Body> {
+        let origin = req.headers().get(axum::http::header::ORIGIN).cloned();
+        let mut resp = handle_openai_compat_request(state, req).await;
+        apply_cors_policy(resp.headers_mut(), origin.as_ref(), &proxy_config_snapshot());
+        resp
+    }
+
+    async fn handle_openai_compat_request(
+        state: Arc<OpenAICompatProxyState>,
+        req: axum::http::Request<Body>,
     ) -> Response<Body> {
         if req.method() == axum::http::Method::OPTIONS {
             return Response::builder()
@@ -8103,7 +12205,8 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
         }
 
         let config = state.config.read().unwrap().clone();
-        let request_id = PROXY_REQ_ID.fetch_add(1, Ordering::SeqCst);
+        let cfg = proxy_config_snapshot();
+        let request_id = next_request_id();
         let req_headers = req.headers().clone();
         if !proxy_api_key_valid(&req_headers) {
             return Response::builder()
@@ -8127,6 +12230,7 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
         if path == "/v1/models" || path.starts_with("/v1/models?") {
             let models = openai_compat_exposed_models(&config);
             let response_body = build_local_models_response(&models);
+            let (response_body_text, response_body_truncated) = truncate_body(&cfg, &response_body);
             let entry = ProxyLogEntry {
                 timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
                 method: method_label,
@@ -8143,7 +12247,9 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
                     ("content-type".to_string(), "application/json".to_string()),
                 ]),
                 request_body: None,
-                response_body: Some(truncate_body(&response_body)),
+                request_body_truncated: false,
+                response_body: Some(response_body_text),
+                response_body_truncated,
                 input_tokens: None,
                 output_tokens: None,
             cache_status: None,
@@ -8152,6 +12258,7 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
             cache_bypass_reason: None,
             local_cached_input_tokens: None,
             provider_cached_input_tokens: None,
+            request_id: Some(request_id as u64),
             };
             let _ = insert_proxy_log(&entry);
             return Response::builder()
@@ -8173,11 +12280,7 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
                     .unwrap();
             }
         };
-        let mut request_body_text = if body_bytes.is_empty() {
-            None
-        } else {
-            Some(truncate_body(&body_bytes))
-        };
+        let (mut request_body_text, mut request_body_truncated) = log_body_field(&cfg, &body_bytes);
         let mut request_model = extract_model(&body_bytes);
         let mut upstream_path = path.clone();
         let is_anthropic = upstream_path == "/v1/messages" || upstream_path.starts_with("/v1/messages?");
@@ -8204,6 +12307,7 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
         if cache_eligible {
             if let Some(key) = cache_key.as_ref() {
                 if let Ok(Some(hit)) = lookup_local_cache(key) {
+                    let (response_body_text, response_body_truncated) = truncate_body(&cfg, &hit.body);
                     let entry = ProxyLogEntry {
                         timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
                         method: method_label.clone(),
@@ -8221,7 +12325,9 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
                             ("x-codex-manager-cache".to_string(), "HIT".to_string()),
                         ]),
                         request_body: request_body_text.clone(),
-                        response_body: Some(truncate_body(&hit.body)),
+                        request_body_truncated,
+                        response_body: Some(response_body_text),
+                        response_body_truncated,
                         input_tokens: Some(hit.input_tokens),
                         output_tokens: Some(hit.output_tokens),
                         cache_status: Some("local_hit".to_string()),
@@ -8230,6 +12336,7 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
                         cache_bypass_reason: None,
                         local_cached_input_tokens: Some(hit.input_tokens),
                         provider_cached_input_tokens: Some(hit.provider_cached_input_tokens),
+                        request_id: Some(request_id as u64),
                     };
                     let _ = insert_proxy_log(&entry);
                     return Response::builder()
@@ -8268,6 +12375,7 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
             let count = count_codex_input_tokens(&codex_body);
             let response_body = serde_json::to_vec(&serde_json::json!({ "input_tokens": count }))
                 .unwrap_or_else(|_| b"{\"input_tokens\":0}".to_vec());
+            let (response_body_text, response_body_truncated) = truncate_body(&cfg, &response_body);
             let entry = ProxyLogEntry {
                 timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
                 method: method_label.clone(),
@@ -8284,7 +12392,9 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
                     ("content-type".to_string(), "application/json".to_string()),
                 ]),
                 request_body: request_body_text.clone(),
-                response_body: Some(truncate_body(&response_body)),
+                request_body_truncated,
+                response_body: Some(response_body_text),
+                response_body_truncated,
                 input_tokens: Some(count),
                 output_tokens: Some(0),
             cache_status: None,
@@ -8293,6 +12403,7 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
             cache_bypass_reason: None,
             local_cached_input_tokens: None,
             provider_cached_input_tokens: None,
+            request_id: Some(request_id as u64),
             };
             let _ = insert_proxy_log(&entry);
             return Response::builder()
@@ -8356,7 +12467,7 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
                     config.default_model.as_deref().unwrap_or("")
                 )
             );
-            request_body_text = Some(truncate_body(&serde_json::to_vec(&chat_request).unwrap_or_default()));
+            (request_body_text, request_body_truncated) = log_body_field(&cfg, &serde_json::to_vec(&chat_request).unwrap_or_default());
             upstream_body_bytes = serde_json::to_vec(&chat_request).unwrap_or_default().into();
             upstream_path = "/v1/chat/completions".to_string();
             translated_request_json = Some(responses_request);
@@ -8405,7 +12516,7 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
                 )
             );
             request_model = request_json.get("model").and_then(|v| v.as_str()).map(|v| v.to_string());
-            request_body_text = Some(truncate_body(&serde_json::to_vec(&chat_request).unwrap_or_default()));
+            (request_body_text, request_body_truncated) = log_body_field(&cfg, &serde_json::to_vec(&chat_request).unwrap_or_default());
             upstream_body_bytes = serde_json::to_vec(&chat_request).unwrap_or_default().into();
             upstream_path = "/v1/chat/completions".to_string();
             translated_request_json = Some(chat_request);
@@ -8418,7 +12529,7 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
                 ) {
                     request_json["model"] = serde_json::json!(mapped_model);
                     request_model = request_json.get("model").and_then(|v| v.as_str()).map(|v| v.to_string());
-                    request_body_text = Some(truncate_body(&serde_json::to_vec(&request_json).unwrap_or_default()));
+                    (request_body_text, request_body_truncated) = log_body_field(&cfg, &serde_json::to_vec(&request_json).unwrap_or_default());
                     upstream_body_bytes = serde_json::to_vec(&request_json).unwrap_or_default().into();
                 }
             }
@@ -8459,7 +12570,9 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
                     request_headers: request_headers_json,
                     response_headers: None,
                     request_body: request_body_text,
+                    request_body_truncated,
                     response_body: None,
+                    response_body_truncated: false,
                     input_tokens: None,
                     output_tokens: None,
                 cache_status: None,
@@ -8468,6 +12581,7 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
                 cache_bypass_reason: None,
                 local_cached_input_tokens: None,
                 provider_cached_input_tokens: None,
+                request_id: Some(request_id as u64),
                 };
                 let _ = insert_proxy_log(&entry);
                 return Response::builder()
@@ -8498,7 +12612,9 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
                     ("content-type".to_string(), "text/event-stream".to_string()),
                 ]),
                 request_body: request_body_text,
+                request_body_truncated,
                 response_body: None,
+                response_body_truncated: false,
                 input_tokens: None,
                 output_tokens: None,
             cache_status: None,
@@ -8507,6 +12623,7 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
             cache_bypass_reason: None,
             local_cached_input_tokens: None,
             provider_cached_input_tokens: None,
+            request_id: Some(request_id as u64),
             };
             return build_openai_chat_to_claude_stream_response(
                 upstream_resp,
@@ -8533,7 +12650,9 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
                     ("content-type".to_string(), "text/event-stream".to_string()),
                 ]),
                 request_body: request_body_text,
+                request_body_truncated,
                 response_body: None,
+                response_body_truncated: false,
                 input_tokens: None,
                 output_tokens: None,
             cache_status: None,
@@ -8542,6 +12661,7 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
             cache_bypass_reason: None,
             local_cached_input_tokens: None,
             provider_cached_input_tokens: None,
+            request_id: Some(request_id as u64),
             };
             return build_custom_openai_stream_response(
                 upstream_resp,
@@ -8552,17 +12672,47 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
         }
 
         let headers = upstream_resp.headers().clone();
-        let bytes = upstream_resp.bytes().await.unwrap_or_default();
+        let bytes = match read_capped_bytes(upstream_resp).await {
+            Ok(b) => b,
+            Err(e) => {
+                log_proxy(&format!("req#{request_id} upstream body read failed: {e}"));
+                let entry = ProxyLogEntry {
+                    timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                    method: method_label.clone(),
+                    path: path.clone(),
+                    request_url: request_url.clone(),
+                    status: StatusCode::BAD_GATEWAY.as_u16(),
+                    duration_ms: started_at.elapsed().as_millis() as u64,
+                    proxy_account_id: config.provider_name.clone(),
+                    account_id: None,
+                    error: Some(e.clone()),
+                    model: request_model.clone(),
+                    request_headers: request_headers_json.clone(),
+                    response_headers: response_headers_json.clone(),
+                    request_body: request_body_text.clone(),
+                    request_body_truncated,
+                    request_id: Some(request_id as u64),
+                    ..ProxyLogEntry::default()
+                };
+                let _ = insert_proxy_log(&entry);
+                return Response::builder()
+                    .status(StatusCode::BAD_GATEWAY)
+                    .header("Access-Control-Allow-Origin", "*")
+                    .body(Body::from(format!("Upstream error: {e}")))
+                    .unwrap();
+            }
+        };
         if is_anthropic && upstream_status.is_success() {
             let request_json = translated_request_json.unwrap_or_else(|| serde_json::json!({}));
-            match convert_chat_completions_non_stream_to_responses(&request_json, &bytes) {
-                Ok((responses_bytes, _, _, _)) => {
+            match convert_chat_completions_non_stream_to_responses(&cfg, &request_json, &bytes) {
+                Ok((responses_bytes, _, _, _, _)) => {
                     match build_claude_response_body(
+                        &cfg,
                         &responses_bytes,
                         &anthropic_reverse_tool_map.unwrap_or_default(),
                         request_model.as_deref().unwrap_or("unknown"),
                     ) {
-                        Ok((converted, input_tokens, output_tokens, response_body_text)) => {
+                        Ok((converted, input_tokens, output_tokens, response_body_text, response_body_truncated)) => {
                             let entry = ProxyLogEntry {
                                 timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
                                 method: method_label,
@@ -8579,9 +12729,12 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
                         ("content-type".to_string(), "application/json".to_string()),
                     ]),
                     request_body: request_body_text,
+                    request_body_truncated,
                     response_body: response_body_text,
+                    response_body_truncated,
                     input_tokens,
                     output_tokens,
+                    request_id: Some(request_id as u64),
                     ..ProxyLogEntry::default()
                 };
                             let _ = insert_proxy_log(&entry);
@@ -8594,6 +12747,7 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
                                 .unwrap();
                         }
                         Err(err) => {
+                            let (response_body_text, response_body_truncated) = truncate_body(&cfg, &bytes);
                             let entry = ProxyLogEntry {
                                 timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
                                 method: method_label,
@@ -8608,7 +12762,9 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
                                 request_headers: request_headers_json,
                                 response_headers: response_headers_json,
                                 request_body: request_body_text,
-                                response_body: Some(truncate_body(&bytes)),
+                                request_body_truncated,
+                                response_body: Some(response_body_text),
+                                response_body_truncated,
                                 input_tokens: None,
                                 output_tokens: None,
                             cache_status: None,
@@ -8617,6 +12773,7 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
                             cache_bypass_reason: None,
                             local_cached_input_tokens: None,
                             provider_cached_input_tokens: None,
+                            request_id: Some(request_id as u64),
                             };
                             let _ = insert_proxy_log(&entry);
                             return Response::builder()
@@ -8638,8 +12795,8 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
         }
         if custom_responses_path && upstream_status.is_success() {
             let request_json = translated_request_json.unwrap_or_else(|| serde_json::json!({}));
-            match convert_chat_completions_non_stream_to_responses(&request_json, &bytes) {
-                Ok((converted, input_tokens, output_tokens, response_body_text)) => {
+            match convert_chat_completions_non_stream_to_responses(&cfg, &request_json, &bytes) {
+                Ok((converted, input_tokens, output_tokens, response_body_text, response_body_truncated)) => {
                     let entry = ProxyLogEntry {
                         timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
                         method: method_label,
@@ -8656,9 +12813,12 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
                             ("content-type".to_string(), "application/json".to_string()),
                         ]),
                         request_body: request_body_text,
+                        request_body_truncated,
                         response_body: response_body_text,
+                        response_body_truncated,
                         input_tokens,
                         output_tokens,
+                        request_id: Some(request_id as u64),
                         ..ProxyLogEntry::default()
                     };
                     let _ = insert_proxy_log(&entry);
@@ -8671,6 +12831,7 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
                         .unwrap();
                 }
                 Err(err) => {
+                    let (response_body_text, response_body_truncated) = truncate_body(&cfg, &bytes);
                     let entry = ProxyLogEntry {
                         timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
                         method: method_label,
@@ -8685,7 +12846,9 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
                         request_headers: request_headers_json,
                         response_headers: response_headers_json,
                         request_body: request_body_text,
-                        response_body: Some(truncate_body(&bytes)),
+                        request_body_truncated,
+                        response_body: Some(response_body_text),
+                        response_body_truncated,
                         input_tokens: None,
                         output_tokens: None,
                     cache_status: None,
@@ -8694,6 +12857,7 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
                     cache_bypass_reason: None,
                     local_cached_input_tokens: None,
                     provider_cached_input_tokens: None,
+                    request_id: Some(request_id as u64),
                     };
                     let _ = insert_proxy_log(&entry);
                     return Response::builder()
@@ -8705,7 +12869,7 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
             }
         }
 
-        let response_body_text = if bytes.is_empty() { None } else { Some(truncate_body(&bytes)) };
+        let (response_body_text, response_body_truncated) = log_body_field(&cfg, &bytes);
         let (input_tokens, output_tokens) = extract_usage(&bytes);
         let entry = ProxyLogEntry {
             timestamp: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
@@ -8721,9 +12885,12 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
             request_headers: request_headers_json,
             response_headers: response_headers_json,
             request_body: request_body_text,
+            request_body_truncated,
             response_body: response_body_text,
+            response_body_truncated,
             input_tokens,
             output_tokens,
+            request_id: Some(request_id as u64),
             ..ProxyLogEntry::default()
         };
         let _ = insert_proxy_log(&entry);
@@ -8732,7 +12899,7 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
             .header("Access-Control-Allow-Origin", "*")
             .header("Access-Control-Allow-Headers", "*");
         for (k, v) in headers.iter() {
-            if skip_response_header(k.as_str()) {
+            if skip_response_header_with_config(k.as_str(), &cfg) {
                 continue;
             }
             if let (Ok(name), Ok(val)) = (
@@ -8767,7 +12934,7 @@ async fn start_openai_compat_proxy(config_id: String, port: Option<u16>) -> Resu
     }
 
     tauri::async_runtime::spawn(async move {
-        let serve_result = run_proxy_server(&addr, app, shutdown_notify).await;
+        let serve_result = run_proxy_server(&addr, app, shutdown_notify, None).await;
         if let Err(err) = serve_result {
             log_proxy(&format!("openai compat proxy exited with error: {err}"));
         }
@@ -8797,6 +12964,65 @@ fn stop_openai_compat_proxy() -> Result<Value, String> {
     }
 }
 
+const SHUTDOWN_DRAIN_SECS: u64 = 5;
+
+/// Wait for SIGTERM/SIGINT (Ctrl-C elsewhere), stop both proxies through their
+/// normal graceful-shutdown path, give in-flight requests a moment to drain and
+/// then exit the app. Without this a killed app never runs the serve tasks'
+/// shutdown, so a request log insert could be cut off mid-write.
+async fn shutdown_on_signal(app: tauri::AppHandle) {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let (mut term, mut int) = match (signal(SignalKind::terminate()), signal(SignalKind::interrupt())) {
+            (Ok(term), Ok(int)) => (term, int),
+            (Err(err), _) | (_, Err(err)) => {
+                log_proxy(&format!("signal handler install failed: {err}"));
+                return;
+            }
+        };
+        tokio::select! {
+            _ = term.recv() => log_proxy("received SIGTERM, shutting down"),
+            _ = int.recv() => log_proxy("received SIGINT, shutting down"),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        log_proxy("received Ctrl-C, shutting down");
+    }
+
+    stop_proxies_and_drain(std::time::Duration::from_secs(SHUTDOWN_DRAIN_SECS)).await;
+    // Taking the log DB lock waits out any insert still in progress
+    let _db_guard = PROXY_LOG_DB.get().map(|db| db.lock().unwrap_or_else(|e| e.into_inner()));
+    app.exit(0);
+}
+
+/// Signal both proxies to stop and wait up to `drain` for their serve tasks to
+/// finish. Returns whether both had stopped in time.
+async fn stop_proxies_and_drain(drain: std::time::Duration) -> bool {
+    if let Some(tx) = PROXY_SHUTDOWN.lock().unwrap().take() {
+        let _ = tx.send(());
+    }
+    if let Some(tx) = OPENAI_COMPAT_PROXY_SHUTDOWN.lock().unwrap().take() {
+        let _ = tx.send(());
+    }
+    // The serve tasks clear their state once graceful shutdown has finished
+    let deadline = std::time::Instant::now() + drain;
+    loop {
+        let running = PROXY_STATE.lock().unwrap().is_some() || OPENAI_COMPAT_PROXY_STATE.lock().unwrap().is_some();
+        if !running {
+            return true;
+        }
+        if std::time::Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+}
+
 #[tauri::command]
 fn get_openai_compat_proxy_status() -> Result<Value, String> {
     let port = *OPENAI_COMPAT_PROXY_PORT.lock().unwrap();
@@ -8820,82 +13046,607 @@ fn get_openai_compat_proxy_status() -> Result<Value, String> {
     } else {
         (None, None)
     };
-    Ok(serde_json::json!({
-        "running": running,
-        "port": port,
-        "config_id": config_id,
-        "provider_name": provider_name,
-    }))
+    Ok(serde_json::json!({
+        "running": running,
+        "port": port,
+        "config_id": config_id,
+        "provider_name": provider_name,
+    }))
+}
+
+#[tauri::command]
+fn stop_api_proxy() -> Result<Value, String> {
+    let mut lock = PROXY_SHUTDOWN.lock().unwrap();
+    if let Some(tx) = lock.take() {
+        let _ = tx.send(());
+        Ok(serde_json::json!({ "success": true, "message": "代理已停止" }))
+    } else {
+        Ok(serde_json::json!({ "success": false, "message": "代理未在运行" }))
+    }
+}
+
+/// Hot-reload accounts from disk into the running proxy pool without restart
+#[tauri::command]
+fn reload_proxy_accounts() -> Result<Value, String> {
+    let state = {
+        let lock = PROXY_STATE.lock().unwrap();
+        lock.clone()
+    };
+    let state = state.ok_or("代理未在运行")?;
+
+    let new_accounts = without_dropped_accounts(&state, load_proxy_accounts()?);
+    let count = new_accounts.len();
+    *state.pool_tag.lock().unwrap() = None;
+    {
+        let mut accounts_lock = state.accounts.write().unwrap();
+        if accounts_lock.len() != count {
+            state.req_counter.store(round_robin_seed(count), Ordering::SeqCst);
+        }
+        *accounts_lock = new_accounts;
+    }
+    Ok(serde_json::json!({ "success": true, "account_count": count }))
+}
+
+/// Reload the running pool with only the enabled accounts carrying `tag`.
+/// The tag sticks: auto reloads keep to it until `reload_proxy_accounts`.
+#[tauri::command]
+fn reload_proxy_accounts_by_tag(tag: String) -> Result<Value, String> {
+    let state = {
+        let lock = PROXY_STATE.lock().unwrap();
+        lock.clone()
+    };
+    let state = state.ok_or("代理未在运行")?;
+
+    let new_accounts = accounts_tagged(without_dropped_accounts(&state, load_proxy_accounts()?), &tag)?;
+    let count = new_accounts.len();
+    *state.pool_tag.lock().unwrap() = Some(tag.trim().to_string());
+    {
+        let mut accounts_lock = state.accounts.write().unwrap();
+        if accounts_lock.len() != count {
+            state.req_counter.store(round_robin_seed(count), Ordering::SeqCst);
+        }
+        *accounts_lock = new_accounts;
+    }
+    Ok(serde_json::json!({ "success": true, "account_count": count }))
+}
+
+#[tauri::command]
+fn get_proxy_status() -> Result<Value, String> {
+    let port = *PROXY_PORT.lock().unwrap();
+    let running = if let Some(port) = port {
+        let addr = format!("127.0.0.1:{port}");
+        let socket_addr: std::net::SocketAddr = addr
+            .parse()
+            .map_err(|e: std::net::AddrParseError| e.to_string())?;
+        std::net::TcpStream::connect_timeout(
+            &socket_addr,
+            std::time::Duration::from_millis(200),
+        )
+        .is_ok()
+    } else {
+        false
+    };
+
+    let (account_count, active, cooldown, blocked, models_cache_age_secs, totals, dropped, get_cache, circuit) = {
+        let lock = PROXY_STATE.lock().unwrap();
+        if let Some(state) = &*lock {
+            let now = std::time::Instant::now();
+            let accounts = state.accounts.read().unwrap();
+            let total = accounts.len();
+            let active = accounts.iter().filter(|a| a.health == AccountHealth::Active).count();
+            let cd = accounts.iter().filter(|a| matches!(&a.health, AccountHealth::Cooldown(u) if now < *u)).count();
+            let bl = accounts.iter().filter(|a| a.health == AccountHealth::Blocked).count();
+            let cache_age = state
+                .models_cache
+                .lock()
+                .unwrap()
+                .as_ref()
+                .map(|entry| entry.fetched_at.elapsed().as_secs());
+            let totals = (
+                state.total_requests.load(Ordering::SeqCst),
+                state.total_errors.load(Ordering::SeqCst),
+                Some(state.started_at.clone()),
+            );
+            let dropped = state.dropped_accounts.lock().unwrap().clone();
+            let get_cache = (
+                state.get_cache_hits.load(Ordering::Relaxed),
+                state.get_cache_misses.load(Ordering::Relaxed),
+            );
+            (total, active, cd, bl, cache_age, totals, dropped, get_cache, circuit_snapshot(state))
+        } else {
+            (0, 0, 0, 0, None, (0, 0, None), Vec::new(), (0, 0), Value::Null)
+        }
+    };
+
+    Ok(serde_json::json!({
+        "running": running,
+        "port": port,
+        "account_count": account_count,
+        "active": active,
+        "cooldown": cooldown,
+        "blocked": blocked,
+        "dropped": dropped.len(),
+        "dropped_accounts": dropped,
+        "models_cache_age_secs": models_cache_age_secs,
+        "get_cache_hits": get_cache.0,
+        "get_cache_misses": get_cache.1,
+        "circuit": circuit,
+        "total_requests": totals.0,
+        "total_errors": totals.1,
+        "started_at": totals.2,
+    }))
+}
+
+/// Report which pool account the next proxied request would use, without
+/// advancing `req_counter` or reviving cooldowns.
+#[tauri::command]
+fn peek_next_account() -> Result<Value, String> {
+    let state = {
+        let lock = PROXY_STATE.lock().unwrap();
+        lock.clone()
+    };
+    let state = state.ok_or("代理未在运行")?;
+    let now = std::time::Instant::now();
+    let accounts = state.accounts.read().unwrap();
+    let pool_size = accounts.len();
+    if pool_size == 0 {
+        return Ok(serde_json::json!({
+            "account_id": null,
+            "reason": "No accounts in pool",
+        }));
+    }
+    let available = |idx: usize| match &accounts[idx].health {
+        AccountHealth::Active => true,
+        // The request path revives elapsed cooldowns before picking.
+        AccountHealth::Cooldown(until) => now >= *until,
+        AccountHealth::Blocked => false,
+    };
+    let strategy = normalize_selection_strategy(&proxy_config_snapshot().selection_strategy);
+    let next = if strategy == "lru" {
+        (0..pool_size).filter(|idx| available(*idx)).min_by_key(|idx| accounts[*idx].last_used_at)
+    } else {
+        let start = state.req_counter.load(Ordering::SeqCst);
+        (0..pool_size).map(|i| (start + i) % pool_size).find(|idx| available(*idx))
+    };
+    if let Some(idx) = next {
+        return Ok(serde_json::json!({
+            "account_id": accounts[idx].id,
+            "index": idx,
+            "strategy": strategy,
+        }));
+    }
+    Ok(serde_json::json!({
+        "account_id": null,
+        "reason": "All accounts are rate-limited or blocked",
+    }))
+}
+
+const SIMULATE_SELECTION_MAX: usize = 100_000;
+
+/// Dry-run `n` picks (capped at `SIMULATE_SELECTION_MAX`) of the current
+/// `selection_strategy` against a snapshot of the live pool and report how
+/// many each account would get. Works on copies of `req_counter` and
+/// `last_used_at`; health is frozen as of now, so cooldowns don't expire or
+/// start mid-run.
+#[tauri::command]
+fn simulate_selection(n: usize) -> Result<Value, String> {
+    let state = {
+        let lock = PROXY_STATE.lock().unwrap();
+        lock.clone()
+    };
+    let state = state.ok_or("代理未在运行")?;
+    let n = n.min(SIMULATE_SELECTION_MAX);
+    let now = std::time::Instant::now();
+    let (ids, available, mut last_used) = {
+        let accounts = state.accounts.read().unwrap();
+        let available: Vec<bool> = accounts
+            .iter()
+            .map(|acc| match &acc.health {
+                AccountHealth::Active => true,
+                AccountHealth::Cooldown(until) => now >= *until,
+                AccountHealth::Blocked => false,
+            })
+            .collect();
+        let ids: Vec<String> = accounts.iter().map(|acc| acc.id.clone()).collect();
+        let last_used: Vec<Option<std::time::Instant>> = accounts.iter().map(|acc| acc.last_used_at).collect();
+        (ids, available, last_used)
+    };
+    let pool_size = ids.len();
+    let strategy = normalize_selection_strategy(&proxy_config_snapshot().selection_strategy);
+    let mut counter = state.req_counter.load(Ordering::SeqCst);
+    let mut counts = vec![0usize; pool_size];
+    let mut unassigned = 0usize;
+    for step in 0..n {
+        let chosen = if pool_size == 0 {
+            None
+        } else if strategy == "lru" {
+            (0..pool_size).filter(|idx| available[*idx]).min_by_key(|idx| last_used[*idx])
+        } else {
+            let start = counter;
+            counter = counter.wrapping_add(1);
+            (0..pool_size).map(|i| (start + i) % pool_size).find(|idx| available[*idx])
+        };
+        match chosen {
+            Some(idx) => {
+                counts[idx] += 1;
+                // Later than any real use, in pick order
+                last_used[idx] = Some(now + std::time::Duration::from_micros(step as u64 + 1));
+            }
+            None => unassigned += 1,
+        }
+    }
+    let distribution: Vec<Value> = ids
+        .iter()
+        .zip(counts.iter())
+        .zip(available.iter())
+        .map(|((id, count), available)| {
+            serde_json::json!({
+                "id": id,
+                "count": count,
+                "share": if n > 0 { *count as f64 / n as f64 } else { 0.0 },
+                "available": available,
+            })
+        })
+        .collect();
+    Ok(serde_json::json!({
+        "strategy": strategy,
+        "requests": n,
+        "unassigned": unassigned,
+        "distribution": distribution,
+    }))
+}
+
+/// Socket addresses the running API proxy actually bound, e.g. both
+/// `127.0.0.1:8080` and `[::1]:8080`, or only one when a stack was unavailable.
+#[tauri::command]
+fn get_proxy_bindings() -> Result<Vec<String>, String> {
+    let state = {
+        let lock = PROXY_STATE.lock().unwrap();
+        lock.clone()
+    };
+    let state = state.ok_or("代理未在运行")?;
+    let bindings = state.bindings.lock().unwrap().clone();
+    Ok(bindings)
+}
+
+/// Whether `presented` (a bare key or a full `Bearer ...` value) would pass the
+/// proxy's API key check, so a client's header can be verified without
+/// revealing the configured key. Always true when no key is configured.
+#[tauri::command]
+fn check_proxy_api_key(presented: String) -> Result<bool, String> {
+    let presented = presented.trim();
+    let token = presented.strip_prefix("Bearer ").unwrap_or(presented).trim();
+    let value = match axum::http::HeaderValue::from_str(&format!("Bearer {token}")) {
+        Ok(value) => value,
+        Err(_) => return Ok(false),
+    };
+    let mut headers = axum::http::HeaderMap::new();
+    headers.insert(axum::http::header::AUTHORIZATION, value);
+    Ok(proxy_api_key_valid(&headers))
+}
+
+/// Send a tiny streaming `/v1/responses` request through the running proxy and
+/// read the SSE stream to the end, timing the first byte and the whole stream.
+/// `account_used` comes from the request's log row (None with logging off).
+#[tauri::command]
+async fn test_proxy_stream() -> Result<Value, String> {
+    let port = (*PROXY_PORT.lock().unwrap()).ok_or("代理未在运行")?;
+    let cfg = proxy_config_snapshot();
+    let model = cfg
+        .model_override
+        .clone()
+        .filter(|m| !m.trim().is_empty())
+        .unwrap_or_else(|| "gpt-5".to_string());
+    let body = serde_json::json!({
+        "model": model,
+        "instructions": "Reply with OK.",
+        "input": [{ "role": "user", "content": [{ "type": "input_text", "text": "ping" }] }],
+        "stream": true,
+        "store": false,
+    });
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(60))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut request = client
+        .post(format!("http://127.0.0.1:{port}/v1/responses"))
+        .header(reqwest::header::ACCEPT, "text/event-stream")
+        .json(&body);
+    if let Some(key) = cfg.api_key.as_deref().filter(|k| !k.is_empty()) {
+        request = request.bearer_auth(key);
+    }
+
+    let started = std::time::Instant::now();
+    let resp = request.send().await.map_err(|e| e.to_string())?;
+    let status = resp.status().as_u16();
+    let request_id = resp
+        .headers()
+        .get("x-codex-manager-request-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<i64>().ok());
+    let mut metrics = StreamMetrics::default();
+    let mut first_byte_ms = None;
+    let mut error = None;
+    let mut stream = resp.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        match chunk {
+            Ok(chunk) => {
+                first_byte_ms.get_or_insert_with(|| started.elapsed().as_millis() as u64);
+                metrics.observe(&chunk);
+            }
+            Err(err) => {
+                error = Some(err.to_string());
+                break;
+            }
+        }
+    }
+    let total_ms = started.elapsed().as_millis() as u64;
+
+    let account_used = request_id.and_then(|id| {
+        let conn = proxy_log_db().ok()?;
+        conn.query_row(
+            "SELECT proxy_account_id FROM request_logs WHERE request_id = ?1 ORDER BY id DESC LIMIT 1",
+            params![id],
+            |row| row.get::<_, String>(0),
+        )
+        .ok()
+    });
+    Ok(serde_json::json!({
+        "ok": (200..300).contains(&status) && metrics.events > 0 && error.is_none(),
+        "status": status,
+        "events_received": metrics.events,
+        "first_byte_ms": first_byte_ms,
+        "total_ms": total_ms,
+        "account_used": account_used,
+        "error": error,
+    }))
+}
+
+/// List the live pool with each account's health, remaining cooldown,
+/// current 429 backoff level and seconds since it was last picked.
+#[tauri::command]
+fn get_proxy_pool() -> Result<Vec<Value>, String> {
+    let state = {
+        let lock = PROXY_STATE.lock().unwrap();
+        lock.clone()
+    };
+    let state = state.ok_or("代理未在运行")?;
+    let now = std::time::Instant::now();
+    let accounts = state.accounts.read().unwrap();
+    Ok(accounts
+        .iter()
+        .map(|acc| {
+            let cooldown_remaining_secs = match &acc.health {
+                AccountHealth::Cooldown(until) => Some(until.saturating_duration_since(now).as_secs()),
+                _ => None,
+            };
+            serde_json::json!({
+                "id": acc.id,
+                "health": health_state_label(&acc.health),
+                "cooldown_remaining_secs": cooldown_remaining_secs,
+                "backoff_level": acc.cooldown_streak,
+                "last_used_secs_ago": acc.last_used_at.map(|t| now.saturating_duration_since(t).as_secs()),
+            })
+        })
+        .collect())
+}
+
+/// `list_accounts` with each account's live pool health joined in as `health`:
+/// the pool label, `not_in_pool` for accounts added after the proxy started
+/// (or not proxy-enabled), or `proxy_stopped` when the proxy isn't running.
+#[tauri::command]
+fn list_accounts_with_health() -> Result<Vec<Value>, String> {
+    let accounts = list_accounts(None)?;
+    let state = {
+        let lock = PROXY_STATE.lock().unwrap();
+        lock.clone()
+    };
+    let health: Option<HashMap<String, &'static str>> = state.map(|state| {
+        state
+            .accounts
+            .read()
+            .unwrap()
+            .iter()
+            .map(|acc| (acc.id.clone(), health_state_label(&acc.health)))
+            .collect()
+    });
+    accounts
+        .into_iter()
+        .map(|account| {
+            let label = match &health {
+                Some(pool) => pool.get(&account.id).copied().unwrap_or("not_in_pool"),
+                None => "proxy_stopped",
+            };
+            let mut value = serde_json::to_value(&account).map_err(|e| e.to_string())?;
+            if let Some(obj) = value.as_object_mut() {
+                obj.insert("health".to_string(), Value::from(label));
+            }
+            Ok(value)
+        })
+        .collect()
 }
 
+/// Force a pool account into `active`, `cooldown` (for `secs`, default
+/// `COOLDOWN_SECS`) or `blocked`, e.g. to exercise failover by hand.
 #[tauri::command]
-fn stop_api_proxy() -> Result<Value, String> {
-    let mut lock = PROXY_SHUTDOWN.lock().unwrap();
-    if let Some(tx) = lock.take() {
-        let _ = tx.send(());
-        Ok(serde_json::json!({ "success": true, "message": "代理已停止" }))
-    } else {
-        Ok(serde_json::json!({ "success": false, "message": "代理未在运行" }))
-    }
+fn set_account_health(id: String, state: String, secs: Option<u64>) -> Result<(), String> {
+    let proxy_state = {
+        let lock = PROXY_STATE.lock().unwrap();
+        lock.clone()
+    };
+    let proxy_state = proxy_state.ok_or("代理未在运行")?;
+    let health = match state.trim().to_ascii_lowercase().as_str() {
+        "active" => AccountHealth::Active,
+        "cooldown" => AccountHealth::Cooldown(
+            std::time::Instant::now() + std::time::Duration::from_secs(secs.unwrap_or(COOLDOWN_SECS)),
+        ),
+        "blocked" => AccountHealth::Blocked,
+        other => return Err(format!("未知的账号状态: {other}")),
+    };
+    let idx = proxy_state
+        .accounts
+        .read()
+        .unwrap()
+        .iter()
+        .position(|acc| acc.id == id)
+        .ok_or_else(|| format!("账号不在代理池中: {id}"))?;
+    transition_account_health(&proxy_state, idx, health, "set manually", None);
+    log_proxy(&format!("account {id} health set to {state} manually"));
+    Ok(())
 }
 
-/// Hot-reload accounts from disk into the running proxy pool without restart
+/// Put every pool account back to `Active` (except ones dropped by
+/// `drop_dead_accounts`), clear cooldown backoff and pick a fresh round-robin
+/// offset, without re-reading accounts from disk.
 #[tauri::command]
-fn reload_proxy_accounts() -> Result<Value, String> {
+fn reset_proxy_rotation() -> Result<Value, String> {
     let state = {
         let lock = PROXY_STATE.lock().unwrap();
         lock.clone()
     };
     let state = state.ok_or("代理未在运行")?;
-
-    let new_accounts = load_proxy_accounts()?;
-    let count = new_accounts.len();
-    {
+    let (changed, active) = {
+        let dropped = state.dropped_accounts.lock().unwrap().clone();
         let mut accounts_lock = state.accounts.write().unwrap();
-        *accounts_lock = new_accounts;
+        let mut changed = Vec::new();
+        for acc in accounts_lock.iter_mut() {
+            acc.cooldown_streak = 0;
+            if acc.health != AccountHealth::Active && !dropped.contains(&acc.id) {
+                let from = std::mem::replace(&mut acc.health, AccountHealth::Active);
+                changed.push((acc.id.clone(), from));
+            }
+        }
+        state.req_counter.store(round_robin_seed(accounts_lock.len()), Ordering::SeqCst);
+        (changed, accounts_lock.len())
+    };
+    for (id, from) in &changed {
+        record_health_event(id, from, &AccountHealth::Active, "rotation reset", None);
     }
-    Ok(serde_json::json!({ "success": true, "account_count": count }))
+    log_proxy(&format!("rotation reset: {} account(s) reactivated", changed.len()));
+    Ok(serde_json::json!({
+        "success": true,
+        "active": active,
+        "reactivated": changed.len(),
+    }))
 }
 
+/// The headers the proxy would send upstream for account `id`, built by
+/// `apply_upstream_headers` with no client headers. The bearer token is
+/// redacted to its last 4 characters.
 #[tauri::command]
-fn get_proxy_status() -> Result<Value, String> {
-    let port = *PROXY_PORT.lock().unwrap();
-    let running = if let Some(port) = port {
-        let addr = format!("127.0.0.1:{port}");
-        let socket_addr: std::net::SocketAddr = addr
-            .parse()
-            .map_err(|e: std::net::AddrParseError| e.to_string())?;
-        std::net::TcpStream::connect_timeout(
-            &socket_addr,
-            std::time::Duration::from_millis(200),
-        )
-        .is_ok()
-    } else {
-        false
-    };
+fn preview_upstream_headers(id: String, is_stream: bool) -> Result<Value, String> {
+    if id.is_empty() || !id.chars().all(is_safe_account_id_char) {
+        return Err(format!("无效的账号 ID: {id}"));
+    }
+    let auth_path = accounts_dir().join(&id).join("auth.json");
+    let content = fs::read_to_string(&auth_path).map_err(|_| format!("Account {id} not found"))?;
+    let auth_data: Value = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+    let (mut access_token, _, mut account_id) = extract_auth_tokens(&auth_data);
+    let mut org = read_meta()
+        .get(&id)
+        .map(|m| AccountUpstreamIdentity {
+            organization: m.organization.clone(),
+            project: m.project.clone(),
+            client_profile: m.client_profile.clone(),
+        })
+        .unwrap_or_default();
+    // Prefer the live pool: it holds refreshed tokens and current org headers
+    let state = PROXY_STATE.lock().unwrap().clone();
+    if let Some(state) = state {
+        let accounts_lock = state.accounts.read().unwrap();
+        if let Some(acc) = accounts_lock.iter().find(|a| a.id == id) {
+            access_token = acc.access_token.clone();
+            account_id = acc.account_id.clone();
+            org = acc.org.clone();
+        }
+    }
 
-    let (account_count, active, cooldown, blocked) = {
-        let lock = PROXY_STATE.lock().unwrap();
-        if let Some(state) = &*lock {
-            let now = std::time::Instant::now();
-            let accounts = state.accounts.read().unwrap();
-            let total = accounts.len();
-            let active = accounts.iter().filter(|a| a.health == AccountHealth::Active).count();
-            let cd = accounts.iter().filter(|a| matches!(&a.health, AccountHealth::Cooldown(u) if now < *u)).count();
-            let bl = accounts.iter().filter(|a| a.health == AccountHealth::Blocked).count();
-            (total, active, cd, bl)
+    let mut headers = reqwest::header::HeaderMap::new();
+    let incoming_headers = axum::http::HeaderMap::new();
+    apply_upstream_headers(&mut headers, &access_token, account_id.as_deref(), Some(&org), &incoming_headers, true, is_stream);
+
+    let mut out = serde_json::Map::new();
+    for (name, value) in headers.iter() {
+        let value = if *name == reqwest::header::AUTHORIZATION {
+            let tail: String = access_token.chars().rev().take(4).collect::<Vec<_>>().into_iter().rev().collect();
+            format!("Bearer …{tail}")
         } else {
-            (0, 0, 0, 0)
-        }
-    };
+            value.to_str().unwrap_or("<binary>").to_string()
+        };
+        out.insert(name.as_str().to_string(), Value::String(value));
+    }
+    Ok(Value::Object(out))
+}
+
+/// Paths, sizes and running state for a "Copy diagnostics" button.
+/// Only metadata is reported; no file contents or tokens.
+#[tauri::command]
+fn get_diagnostics() -> Result<Value, String> {
+    fn path_info(path: PathBuf) -> Value {
+        let meta = fs::metadata(&path).ok();
+        serde_json::json!({
+            "path": path.to_string_lossy(),
+            "exists": meta.is_some(),
+            "size": meta.filter(|m| m.is_file()).map(|m| m.len()),
+        })
+    }
+
+    let account_count = fs::read_dir(accounts_dir())
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|entry| entry.path().join("auth.json").exists())
+                .count()
+        })
+        .unwrap_or(0);
+    let running = PROXY_STATE.lock().unwrap().is_some();
+    let port = *PROXY_PORT.lock().unwrap();
 
     Ok(serde_json::json!({
-        "running": running,
-        "port": port,
+        "codex_dir": path_info(codex_dir()),
+        "accounts_dir": path_info(accounts_dir()),
+        "proxy_log_path": path_info(proxy_log_path()),
+        "proxy_log_db_path": path_info(proxy_log_db_path()),
+        "proxy_config_path": path_info(proxy_config_path()),
         "account_count": account_count,
-        "active": active,
-        "cooldown": cooldown,
-        "blocked": blocked,
+        "proxy_running": running,
+        "proxy_port": port,
+    }))
+}
+
+/// Problems with where state would be written: HOME unset (paths fall back to
+/// /tmp) or a state directory that can't be created or written to.
+fn environment_problems() -> Vec<String> {
+    fn probe_dir(dir: &std::path::Path) -> Result<(), String> {
+        fs::create_dir_all(dir).map_err(|e| format!("无法创建目录 {}: {e}", dir.display()))?;
+        let probe = dir.join(format!(".write-test-{}", std::process::id()));
+        fs::write(&probe, b"ok").map_err(|e| format!("目录不可写 {}: {e}", dir.display()))?;
+        let _ = fs::remove_file(&probe);
+        Ok(())
+    }
+
+    let mut problems = Vec::new();
+    match std::env::var("HOME") {
+        Ok(home) if !home.trim().is_empty() => {}
+        _ => problems.push("HOME 未设置，数据将写入 /tmp，重启后可能丢失".to_string()),
+    }
+    let manager_dir = proxy_config_path().parent().map(|p| p.to_path_buf());
+    for dir in [Some(codex_dir()), manager_dir].into_iter().flatten() {
+        if let Err(err) = probe_dir(&dir) {
+            problems.push(format!("{err}（请检查 HOME 是否指向可写位置）"));
+        }
+    }
+    problems
+}
+
+#[tauri::command]
+fn check_environment() -> Result<Value, String> {
+    let problems = environment_problems();
+    Ok(serde_json::json!({
+        "ok": problems.is_empty(),
+        "home": std::env::var("HOME").ok(),
+        "codex_dir": codex_dir().to_string_lossy(),
+        "problems": problems,
     }))
 }
 
@@ -8924,6 +13675,36 @@ fn update_proxy_config(
     vector_api_base_url: Option<String>,
     vector_api_key: Option<String>,
     vector_model: Option<String>,
+    model_pricing: Option<HashMap<String, ModelPricing>>,
+    extra_stripped_headers: Option<Vec<String>>,
+    forward_headers_override: Option<Vec<String>>,
+    max_response_bytes: Option<usize>,
+    sse_heartbeat_secs: Option<u64>,
+    cache_models: Option<bool>,
+    forward_client_ip: Option<bool>,
+    strip_response_headers: Option<Vec<String>>,
+    idle_shutdown_secs: Option<u64>,
+    auto_refresh: Option<bool>,
+    log_bodies: Option<String>,
+    hedge_after_ms: Option<u64>,
+    drop_dead_accounts: Option<bool>,
+    allowed_origins: Option<Vec<String>>,
+    client_profiles: Option<HashMap<String, ClientProfile>>,
+    get_cache_secs: Option<u64>,
+    echo_mode: Option<bool>,
+    refresh_lead_secs: Option<u64>,
+    import_label_template: Option<String>,
+    stream_request_body: Option<bool>,
+    pretty_log_bodies: Option<bool>,
+    allowed_paths: Option<Vec<String>>,
+    selection_strategy: Option<String>,
+    connect_timeout_secs: Option<u64>,
+    idempotency_enabled: Option<bool>,
+    usage_warning_percent: Option<f64>,
+    default_model: Option<String>,
+    strict_session_affinity: Option<bool>,
+    auto_reload_secs: Option<u64>,
+    passthrough_response_headers: Option<Vec<String>>,
 ) -> Result<ProxyConfig, String> {
     let mut cfg = proxy_config_snapshot();
     if let Some(value) = api_key {
@@ -8989,35 +13770,552 @@ fn update_proxy_config(
         let trimmed = value.trim().to_string();
         cfg.vector_api_key = if trimmed.is_empty() { None } else { Some(trimmed) };
     }
-    if let Some(value) = vector_model {
-        let trimmed = value.trim().to_string();
-        cfg.vector_model = if trimmed.is_empty() { None } else { Some(trimmed) };
+    if let Some(value) = vector_model {
+        let trimmed = value.trim().to_string();
+        cfg.vector_model = if trimmed.is_empty() { None } else { Some(trimmed) };
+    }
+    if let Some(value) = model_pricing {
+        cfg.model_pricing = value
+            .into_iter()
+            .map(|(model, price)| {
+                (
+                    model.trim().to_string(),
+                    ModelPricing {
+                        input_per_1k: price.input_per_1k.max(0.0),
+                        output_per_1k: price.output_per_1k.max(0.0),
+                    },
+                )
+            })
+            .filter(|(model, _)| !model.is_empty())
+            .collect();
+    }
+    if let Some(value) = extra_stripped_headers {
+        cfg.extra_stripped_headers = normalize_header_list(value);
+    }
+    if let Some(value) = forward_headers_override {
+        cfg.forward_headers_override = normalize_header_list(value)
+            .into_iter()
+            .filter(|name| !is_protected_request_header(name))
+            .collect();
+    }
+    if let Some(value) = max_response_bytes {
+        // 0 clears the cap
+        cfg.max_response_bytes = if value == 0 { None } else { Some(value) };
+    }
+    if let Some(value) = sse_heartbeat_secs {
+        // 0 disables heartbeats
+        cfg.sse_heartbeat_secs = if value == 0 { None } else { Some(value.min(300)) };
+    }
+    if let Some(value) = cache_models {
+        cfg.cache_models = value;
+    }
+    if let Some(value) = forward_client_ip {
+        cfg.forward_client_ip = value;
+    }
+    if let Some(value) = strip_response_headers {
+        cfg.strip_response_headers = normalize_header_list(value);
+    }
+    if let Some(value) = idle_shutdown_secs {
+        // 0 keeps the proxy running indefinitely
+        cfg.idle_shutdown_secs = if value == 0 { None } else { Some(value) };
+    }
+    if let Some(value) = auto_refresh {
+        cfg.auto_refresh = value;
+    }
+    if let Some(value) = log_bodies {
+        cfg.log_bodies = normalize_log_bodies(&value).to_string();
+    }
+    if let Some(value) = hedge_after_ms {
+        // 0 disables hedging
+        cfg.hedge_after_ms = if value == 0 { None } else { Some(value) };
+    }
+    if let Some(value) = drop_dead_accounts {
+        cfg.drop_dead_accounts = value;
+    }
+    if let Some(value) = allowed_origins {
+        let mut origins: Vec<String> = Vec::new();
+        for origin in value {
+            let origin = origin.trim().trim_end_matches('/').to_ascii_lowercase();
+            if !origin.is_empty() && !origins.contains(&origin) {
+                origins.push(origin);
+            }
+        }
+        cfg.allowed_origins = origins;
+    }
+    if let Some(value) = client_profiles {
+        let mut profiles = HashMap::new();
+        for (name, profile) in value {
+            let name = name.trim().to_string();
+            if name.is_empty() {
+                continue;
+            }
+            let profile = ClientProfile {
+                version: profile.version.trim().to_string(),
+                user_agent: profile.user_agent.trim().to_string(),
+                originator: profile.originator.trim().to_string(),
+            };
+            for value in [&profile.version, &profile.user_agent, &profile.originator] {
+                if value.is_empty() || reqwest::header::HeaderValue::from_str(value).is_err() {
+                    return Err(format!("客户端配置 {name} 的请求头无效: {value:?}"));
+                }
+            }
+            profiles.insert(name, profile);
+        }
+        cfg.client_profiles = profiles;
+    }
+    if let Some(value) = get_cache_secs {
+        cfg.get_cache_secs = if value == 0 { None } else { Some(value) };
+    }
+    if let Some(value) = echo_mode {
+        cfg.echo_mode = value;
+    }
+    if let Some(value) = refresh_lead_secs {
+        cfg.refresh_lead_secs = if value == 0 { None } else { Some(value) };
+    }
+    if let Some(value) = import_label_template {
+        let trimmed = value.trim().to_string();
+        cfg.import_label_template = if trimmed.is_empty() { None } else { Some(trimmed) };
+    }
+    if let Some(value) = stream_request_body {
+        cfg.stream_request_body = value;
+    }
+    if let Some(value) = pretty_log_bodies {
+        cfg.pretty_log_bodies = value;
+    }
+    if let Some(value) = allowed_paths {
+        let mut paths: Vec<String> = Vec::new();
+        for path in value {
+            let path = path.trim();
+            if path.is_empty() {
+                continue;
+            }
+            let path = if path.starts_with('/') { path.to_string() } else { format!("/{path}") };
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+        cfg.allowed_paths = paths;
+    }
+    if let Some(value) = selection_strategy {
+        cfg.selection_strategy = normalize_selection_strategy(&value).to_string();
+    }
+    if let Some(value) = connect_timeout_secs {
+        cfg.connect_timeout_secs = if value == 0 { None } else { Some(value) };
+    }
+    if let Some(value) = idempotency_enabled {
+        cfg.idempotency_enabled = value;
+    }
+    if let Some(value) = usage_warning_percent {
+        // 0 (or anything outside 0-100) turns the warning off
+        cfg.usage_warning_percent = if value > 0.0 && value < 100.0 { Some(value) } else { None };
+    }
+    if let Some(value) = default_model {
+        let value = value.trim();
+        cfg.default_model = if value.is_empty() { None } else { Some(value.to_string()) };
+    }
+    if let Some(value) = strict_session_affinity {
+        cfg.strict_session_affinity = value;
+    }
+    if let Some(value) = auto_reload_secs {
+        // 0 turns the watcher off; takes effect on the next proxy start
+        cfg.auto_reload_secs = if value == 0 { None } else { Some(value) };
+    }
+    if let Some(value) = passthrough_response_headers {
+        cfg.passthrough_response_headers = normalize_header_list(value);
+    }
+    save_proxy_config(&cfg)?;
+    let mut lock = proxy_config().lock().unwrap();
+    *lock = cfg.clone();
+    Ok(cfg)
+}
+
+/// Re-read `proxy_config.json` from disk into the in-memory config.
+#[tauri::command]
+fn reload_proxy_config() -> Result<Value, String> {
+    let path = proxy_config_path();
+    let cfg = if path.exists() {
+        let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        serde_json::from_str::<ProxyConfig>(&content)
+            .map_err(|e| format!("代理配置解析失败: {e}"))?
+    } else {
+        ProxyConfig::default()
+    };
+    let previous = {
+        let mut lock = proxy_config().lock().unwrap();
+        std::mem::replace(&mut *lock, cfg.clone())
+    };
+    let running = PROXY_STATE.lock().unwrap().is_some();
+    // Handlers snapshot the config per request; these are only read by
+    // `start_api_proxy` (pool check, idle and pool watchers, client timeouts)
+    let mut restart_required: Vec<&str> = Vec::new();
+    if running
+        && normalize_proxy_upstream_mode(&previous.upstream_mode)
+            != normalize_proxy_upstream_mode(&cfg.upstream_mode)
+    {
+        restart_required.push("upstream_mode");
+    }
+    if running && previous.idle_shutdown_secs != cfg.idle_shutdown_secs {
+        restart_required.push("idle_shutdown_secs");
+    }
+    if running && previous.connect_timeout_secs != cfg.connect_timeout_secs {
+        restart_required.push("connect_timeout_secs");
+    }
+    if running && previous.auto_reload_secs != cfg.auto_reload_secs {
+        restart_required.push("auto_reload_secs");
+    }
+    log_proxy(&format!(
+        "proxy config reloaded from disk (running={running}, restart_required={restart_required:?})"
+    ));
+    Ok(serde_json::json!({
+        "success": true,
+        "running": running,
+        "restart_required": restart_required,
+        "config": cfg,
+    }))
+}
+
+#[tauri::command]
+fn generate_proxy_api_key() -> Result<String, String> {
+    let mut bytes = [0u8; 16];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    let key = format!(
+        "sk-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+        bytes[0], bytes[1], bytes[2], bytes[3],
+        bytes[4], bytes[5], bytes[6], bytes[7],
+        bytes[8], bytes[9], bytes[10], bytes[11],
+        bytes[12], bytes[13], bytes[14], bytes[15]
+    );
+    Ok(key)
+}
+
+#[tauri::command]
+fn clear_proxy_logs() -> Result<Value, String> {
+    let conn = proxy_log_db()?;
+    conn.execute("DELETE FROM request_logs", []).map_err(|e| e.to_string())?;
+    Ok(serde_json::json!({ "success": true }))
+}
+
+/// Move proxy_logs.db aside as `proxy_logs-<UTC timestamp>.db` and continue
+/// with a fresh, empty database. Everything in the file moves with it,
+/// including the AI cache and health events. Returns the archive path.
+#[tauri::command]
+fn rotate_proxy_log_db() -> Result<Value, String> {
+    let path = proxy_log_db_path();
+    let mut conn = proxy_log_db()?;
+    // Fold the WAL into the main file, then close it so the rename is clean
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+        .map_err(|e| e.to_string())?;
+    // Initialized, so inserts still succeed if nothing on disk can be reopened
+    let placeholder = Connection::open_in_memory().map_err(|e| e.to_string())?;
+    init_proxy_log_db(&placeholder)?;
+    drop(std::mem::replace(&mut *conn, placeholder));
+
+    let open_initialized = |path: &std::path::Path| -> Result<Connection, String> {
+        let conn = Connection::open(path).map_err(|e| e.to_string())?;
+        init_proxy_log_db(&conn)?;
+        Ok(conn)
+    };
+    let stamp = chrono::Utc::now().format("%Y%m%d-%H%M%S");
+    let archive = path.with_file_name(format!("proxy_logs-{stamp}.db"));
+    if let Err(err) = fs::rename(&path, &archive) {
+        // Nothing moved: carry on with the original file
+        if let Ok(original) = open_initialized(&path) {
+            *conn = original;
+        }
+        return Err(err.to_string());
+    }
+    for suffix in ["-wal", "-shm"] {
+        let _ = fs::remove_file(format!("{}{suffix}", path.display()));
+    }
+
+    match open_initialized(&path) {
+        Ok(fresh) => *conn = fresh,
+        Err(err) => {
+            // Put the old file back rather than lose logging to the placeholder
+            let restored = fs::rename(&archive, &path).map_err(|e| e.to_string()).and_then(|_| open_initialized(&path));
+            if let Ok(original) = restored {
+                *conn = original;
+            }
+            log_proxy(&format!("log db rotation failed, kept the current file: {err}"));
+            return Err(err);
+        }
+    }
+    log_proxy(&format!("log db rotated to {}", archive.display()));
+    Ok(serde_json::json!({
+        "success": true,
+        "archive_path": archive.to_string_lossy(),
+    }))
+}
+
+/// Last `lines` lines (default 200) of proxy.log, oldest first. The file is
+/// read backwards from the end in blocks, so a large log isn't loaded whole.
+#[tauri::command]
+fn get_proxy_log_file(lines: Option<usize>) -> Result<Vec<String>, String> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    const BLOCK: u64 = 8192;
+    let wanted = lines.unwrap_or(200).clamp(1, 10_000);
+    let mut file = match fs::File::open(proxy_log_path()) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.to_string()),
+    };
+    let mut pos = file.seek(SeekFrom::End(0)).map_err(|e| e.to_string())?;
+    let mut buf: Vec<u8> = Vec::new();
+    // One extra newline is needed to know the earliest wanted line is complete
+    while pos > 0 && buf.iter().filter(|b| **b == b'\n').count() <= wanted {
+        let step = BLOCK.min(pos);
+        pos -= step;
+        file.seek(SeekFrom::Start(pos)).map_err(|e| e.to_string())?;
+        let mut chunk = vec![0u8; step as usize];
+        file.read_exact(&mut chunk).map_err(|e| e.to_string())?;
+        chunk.extend_from_slice(&buf);
+        buf = chunk;
+    }
+    let text = String::from_utf8_lossy(&buf);
+    let all: Vec<&str> = text.lines().collect();
+    // When we stopped mid-file the first line is likely partial
+    let start = if pos > 0 { 1 } else { 0 };
+    let all = &all[start.min(all.len())..];
+    let skip = all.len().saturating_sub(wanted);
+    Ok(all[skip..].iter().map(|l| l.to_string()).collect())
+}
+
+/// Truncate proxy.log to zero length. Returns whether the file had any content.
+/// `log_proxy` opens in append mode, so a concurrent write simply lands at the
+/// new end of the file.
+#[tauri::command]
+fn clear_proxy_log_file() -> Result<bool, String> {
+    let path = proxy_log_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let had_content = fs::metadata(&path).map(|m| m.len() > 0).unwrap_or(false);
+    fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(&path)
+        .map_err(|e| e.to_string())?;
+    Ok(had_content)
+}
+
+/// Delete request logs older than `ts` (RFC 3339) and return how many were removed.
+#[tauri::command]
+fn purge_proxy_logs_before(ts: String) -> Result<usize, String> {
+    let cutoff = parse_log_timestamp(&ts)?;
+    let conn = proxy_log_db()?;
+    conn.execute("DELETE FROM request_logs WHERE timestamp < ?1", params![cutoff])
+        .map_err(|e| e.to_string())
+}
+
+fn parse_log_timestamp(ts: &str) -> Result<String, String> {
+    Ok(chrono::DateTime::parse_from_rfc3339(ts.trim())
+        .map_err(|e| format!("时间格式错误 (需要 RFC 3339，例如 2024-01-01T00:00:00Z): {e}"))?
+        .with_timezone(&chrono::Utc)
+        .format("%Y-%m-%dT%H:%M:%SZ")
+        .to_string())
+}
+
+/// Slowest requests first, optionally limited to a time range.
+#[tauri::command]
+fn get_slowest_requests(
+    limit: Option<usize>,
+    from_ts: Option<String>,
+    to_ts: Option<String>,
+) -> Result<Vec<ProxyLogSummary>, String> {
+    let limit = limit.unwrap_or(50).clamp(1, 1000) as i64;
+    let from = from_ts.as_deref().map(parse_log_timestamp).transpose()?;
+    let to = to_ts.as_deref().map(parse_log_timestamp).transpose()?;
+    let conn = proxy_log_db()?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, timestamp, method, path, status, duration_ms, proxy_account_id, account_id, error, model
+             FROM request_logs
+             WHERE (?1 IS NULL OR timestamp >= ?1) AND (?2 IS NULL OR timestamp <= ?2)
+             ORDER BY duration_ms DESC LIMIT ?3",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![from, to, limit], |row| {
+            Ok(ProxyLogSummary {
+                id: row.get(0)?,
+                timestamp: row.get(1)?,
+                method: row.get(2)?,
+                path: row.get(3)?,
+                status: row.get::<_, i64>(4)? as u16,
+                duration_ms: row.get::<_, i64>(5)? as u64,
+                proxy_account_id: row.get(6)?,
+                account_id: row.get(7)?,
+                error: row.get(8)?,
+                model: row.get(9)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    let mut logs = Vec::new();
+    for row in rows {
+        logs.push(row.map_err(|e| e.to_string())?);
+    }
+    Ok(logs)
+}
+
+/// Distinct models, proxy accounts and status codes present in `request_logs`,
+/// for building filter dropdowns.
+#[tauri::command]
+fn get_log_facets() -> Result<Value, String> {
+    let conn = proxy_log_db()?;
+    let models: Vec<String> = {
+        let mut stmt = conn
+            .prepare("SELECT DISTINCT model FROM request_logs WHERE model IS NOT NULL AND model != '' ORDER BY model")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>().map_err(|e| e.to_string())?
+    };
+    let accounts: Vec<String> = {
+        let mut stmt = conn
+            .prepare("SELECT DISTINCT proxy_account_id FROM request_logs WHERE proxy_account_id != '' ORDER BY proxy_account_id")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>().map_err(|e| e.to_string())?
+    };
+    let statuses: Vec<i64> = {
+        let mut stmt = conn
+            .prepare("SELECT DISTINCT status FROM request_logs ORDER BY status")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>().map_err(|e| e.to_string())?
+    };
+    Ok(serde_json::json!({
+        "models": models,
+        "proxy_account_ids": accounts,
+        "statuses": statuses,
+    }))
+}
+
+/// Request count, average latency and error count per path, busiest first.
+/// Query strings are dropped so `/v1/models?client_version=…` variants group together.
+#[tauri::command]
+fn get_path_stats(from_ts: Option<String>, to_ts: Option<String>) -> Result<Vec<Value>, String> {
+    let from = from_ts.as_deref().map(parse_log_timestamp).transpose()?;
+    let to = to_ts.as_deref().map(parse_log_timestamp).transpose()?;
+    let conn = proxy_log_db()?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT
+                CASE WHEN instr(path, '?') > 0 THEN substr(path, 1, instr(path, '?') - 1) ELSE path END AS base_path,
+                COUNT(*),
+                COALESCE(AVG(duration_ms), 0),
+                COALESCE(SUM(CASE WHEN status >= 400 THEN 1 ELSE 0 END), 0)
+             FROM request_logs
+             WHERE (?1 IS NULL OR timestamp >= ?1) AND (?2 IS NULL OR timestamp <= ?2)
+             GROUP BY base_path
+             ORDER BY COUNT(*) DESC",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![from, to], |row| {
+            let requests: i64 = row.get(1)?;
+            let errors: i64 = row.get(3)?;
+            Ok(serde_json::json!({
+                "path": row.get::<_, String>(0)?,
+                "requests": requests,
+                "avg_duration_ms": row.get::<_, f64>(2)?,
+                "errors": errors,
+                "error_rate": if requests > 0 { errors as f64 / requests as f64 } else { 0.0 },
+            }))
+        })
+        .map_err(|e| e.to_string())?;
+    let mut stats = Vec::new();
+    for row in rows {
+        stats.push(row.map_err(|e| e.to_string())?);
     }
-    save_proxy_config(&cfg)?;
-    let mut lock = proxy_config().lock().unwrap();
-    *lock = cfg.clone();
-    Ok(cfg)
+    Ok(stats)
 }
 
+/// Requests, errors (status >= 400) and error rate per pool account, worst
+/// error rate first. Cache hits and requests that never reached an account are
+/// left out.
 #[tauri::command]
-fn generate_proxy_api_key() -> Result<String, String> {
-    let mut bytes = [0u8; 16];
-    rand::rngs::OsRng.fill_bytes(&mut bytes);
-    let key = format!(
-        "sk-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
-        bytes[0], bytes[1], bytes[2], bytes[3],
-        bytes[4], bytes[5], bytes[6], bytes[7],
-        bytes[8], bytes[9], bytes[10], bytes[11],
-        bytes[12], bytes[13], bytes[14], bytes[15]
-    );
-    Ok(key)
+fn get_account_error_rates(from_ts: Option<String>, to_ts: Option<String>) -> Result<Vec<Value>, String> {
+    let from = from_ts.as_deref().map(parse_log_timestamp).transpose()?;
+    let to = to_ts.as_deref().map(parse_log_timestamp).transpose()?;
+    let conn = proxy_log_db()?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT
+                proxy_account_id,
+                COUNT(*),
+                COALESCE(SUM(CASE WHEN status >= 400 THEN 1 ELSE 0 END), 0)
+             FROM request_logs
+             WHERE (?1 IS NULL OR timestamp >= ?1) AND (?2 IS NULL OR timestamp <= ?2)
+               AND proxy_account_id NOT IN ('', 'local-cache', 'models-cache', 'get-cache', 'custom-openai')
+             GROUP BY proxy_account_id",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![from, to], |row| {
+            let requests: i64 = row.get(1)?;
+            let errors: i64 = row.get(2)?;
+            Ok((row.get::<_, String>(0)?, requests, errors))
+        })
+        .map_err(|e| e.to_string())?;
+    let mut stats = Vec::new();
+    for row in rows {
+        stats.push(row.map_err(|e| e.to_string())?);
+    }
+    let rate = |requests: i64, errors: i64| if requests > 0 { errors as f64 / requests as f64 } else { 0.0 };
+    stats.sort_by(|a, b| rate(b.1, b.2).total_cmp(&rate(a.1, a.2)).then(b.1.cmp(&a.1)));
+    Ok(stats
+        .into_iter()
+        .map(|(id, requests, errors)| {
+            serde_json::json!({
+                "proxy_account_id": id,
+                "requests": requests,
+                "errors": errors,
+                "error_rate": rate(requests, errors),
+            })
+        })
+        .collect())
 }
 
+/// Most recent account health transitions, newest first.
 #[tauri::command]
-fn clear_proxy_logs() -> Result<Value, String> {
+fn get_health_events(account_id: Option<String>, limit: Option<i64>) -> Result<Vec<HealthEvent>, String> {
+    let limit = limit.unwrap_or(200).clamp(1, 1000);
+    let account_id = account_id.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
     let conn = proxy_log_db()?;
-    conn.execute("DELETE FROM request_logs", []).map_err(|e| e.to_string())?;
-    Ok(serde_json::json!({ "success": true }))
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, account_id, from_state, to_state, reason, status_code, timestamp
+             FROM health_events
+             WHERE (?1 IS NULL OR account_id = ?1)
+             ORDER BY id DESC LIMIT ?2",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![account_id, limit], |row| {
+            Ok(HealthEvent {
+                id: row.get(0)?,
+                account_id: row.get(1)?,
+                from_state: row.get(2)?,
+                to_state: row.get(3)?,
+                reason: row.get(4)?,
+                status_code: row.get::<_, Option<i64>>(5)?.map(|v| v as u16),
+                timestamp: row.get(6)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    let mut events = Vec::new();
+    for row in rows {
+        events.push(row.map_err(|e| e.to_string())?);
+    }
+    Ok(events)
 }
 
 #[tauri::command]
@@ -9096,39 +14394,125 @@ fn get_proxy_logs_filtered(
     Ok(logs)
 }
 
+/// One page of `get_proxy_logs_filtered` together with the matching total.
+/// Both queries run under the same DB lock, so no insert can land in between
+/// and skew the count against the rows.
 #[tauri::command]
-fn get_proxy_log_detail(log_id: i64) -> Result<ProxyLogDetail, String> {
+fn get_proxy_logs_page(
+    filter: Option<String>,
+    errors_only: Option<bool>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+) -> Result<Value, String> {
+    let filter = filter.unwrap_or_default();
+    let limit = limit.unwrap_or(50) as i64;
+    let offset = offset.unwrap_or(0) as i64;
+    let mut clauses: Vec<&str> = Vec::new();
+    let mut params_vec: Vec<rusqlite::types::Value> = Vec::new();
+    if errors_only.unwrap_or(false) {
+        clauses.push("(status < 200 OR status >= 400)");
+    }
+    if !filter.is_empty() {
+        clauses.push("(method LIKE ?1 OR path LIKE ?1 OR CAST(status AS TEXT) LIKE ?1 OR proxy_account_id LIKE ?1 OR account_id LIKE ?1 OR error LIKE ?1 OR model LIKE ?1)");
+        params_vec.push(format!("%{}%", filter).into());
+    }
+    let where_sql = if clauses.is_empty() {
+        String::new()
+    } else {
+        format!(" WHERE {}", clauses.join(" AND "))
+    };
+
     let conn = proxy_log_db()?;
-    let mut stmt = conn.prepare(
-        "SELECT id, timestamp, method, path, request_url, status, duration_ms, proxy_account_id, account_id, error, model, request_headers, response_headers, request_body, response_body, input_tokens, output_tokens, cache_status, cache_key, cache_eligible, cache_bypass_reason, local_cached_input_tokens, provider_cached_input_tokens FROM request_logs WHERE id = ?1",
-    ).map_err(|e| e.to_string())?;
-    let log = stmt.query_row(params![log_id], |row| {
-        Ok(ProxyLogDetail {
-            id: row.get(0)?,
-            timestamp: row.get(1)?,
-            method: row.get(2)?,
-            path: row.get(3)?,
-            request_url: row.get(4)?,
-            status: row.get::<_, i64>(5)? as u16,
-            duration_ms: row.get::<_, i64>(6)? as u64,
-            proxy_account_id: row.get(7)?,
-            account_id: row.get(8)?,
-            error: row.get(9)?,
-            model: row.get(10)?,
-            request_headers: row.get(11)?,
-            response_headers: row.get(12)?,
-            request_body: row.get(13)?,
-            response_body: row.get(14)?,
-            input_tokens: row.get(15)?,
-            output_tokens: row.get(16)?,
-            cache_status: row.get(17)?,
-            cache_key: row.get(18)?,
-            cache_eligible: row.get::<_, Option<i64>>(19)?.map(|v| v != 0),
-            cache_bypass_reason: row.get(20)?,
-            local_cached_input_tokens: row.get(21)?,
-            provider_cached_input_tokens: row.get(22)?,
+    let total: i64 = conn
+        .query_row(
+            &format!("SELECT COUNT(*) FROM request_logs{where_sql}"),
+            rusqlite::params_from_iter(params_vec.iter()),
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    let next = params_vec.len() + 1;
+    let sql = format!(
+        "SELECT id, timestamp, method, path, status, duration_ms, proxy_account_id, account_id, error, model FROM request_logs{where_sql} ORDER BY id DESC LIMIT ?{} OFFSET ?{}",
+        next,
+        next + 1
+    );
+    params_vec.push(limit.into());
+    params_vec.push(offset.into());
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let rows_iter = stmt
+        .query_map(rusqlite::params_from_iter(params_vec), |row| {
+            Ok(ProxyLogSummary {
+                id: row.get(0)?,
+                timestamp: row.get(1)?,
+                method: row.get(2)?,
+                path: row.get(3)?,
+                status: row.get::<_, i64>(4)? as u16,
+                duration_ms: row.get::<_, i64>(5)? as u64,
+                proxy_account_id: row.get(6)?,
+                account_id: row.get(7)?,
+                error: row.get(8)?,
+                model: row.get(9)?,
+            })
         })
-    }).map_err(|e| {
+        .map_err(|e| e.to_string())?;
+    let mut rows = Vec::new();
+    for row in rows_iter {
+        rows.push(row.map_err(|e| e.to_string())?);
+    }
+    Ok(serde_json::json!({
+        "rows": rows,
+        "total": total,
+        "limit": limit,
+        "offset": offset,
+    }))
+}
+
+const PROXY_LOG_DETAIL_COLUMNS: &str = "id, timestamp, method, path, request_url, status, duration_ms, proxy_account_id, account_id, error, model, request_headers, response_headers, request_body, response_body, input_tokens, output_tokens, cache_status, cache_key, cache_eligible, cache_bypass_reason, local_cached_input_tokens, provider_cached_input_tokens, request_id, event_count, response_bytes, request_body_truncated, response_body_truncated";
+
+fn proxy_log_detail_from_row(row: &rusqlite::Row) -> rusqlite::Result<ProxyLogDetail> {
+    let (request_body, request_body_encoding) = split_log_body(row.get(13)?);
+    let (response_body, response_body_encoding) = split_log_body(row.get(14)?);
+    Ok(ProxyLogDetail {
+        id: row.get(0)?,
+        timestamp: row.get(1)?,
+        method: row.get(2)?,
+        path: row.get(3)?,
+        request_url: row.get(4)?,
+        status: row.get::<_, i64>(5)? as u16,
+        duration_ms: row.get::<_, i64>(6)? as u64,
+        proxy_account_id: row.get(7)?,
+        account_id: row.get(8)?,
+        error: row.get(9)?,
+        model: row.get(10)?,
+        request_headers: row.get(11)?,
+        response_headers: row.get(12)?,
+        request_body,
+        response_body,
+        request_body_encoding,
+        response_body_encoding,
+        input_tokens: row.get(15)?,
+        output_tokens: row.get(16)?,
+        cache_status: row.get(17)?,
+        cache_key: row.get(18)?,
+        cache_eligible: row.get::<_, Option<i64>>(19)?.map(|v| v != 0),
+        cache_bypass_reason: row.get(20)?,
+        local_cached_input_tokens: row.get(21)?,
+        provider_cached_input_tokens: row.get(22)?,
+        request_id: row.get(23)?,
+        event_count: row.get(24)?,
+        response_bytes: row.get(25)?,
+        request_body_truncated: row.get::<_, Option<i64>>(26)?.is_some_and(|v| v != 0),
+        response_body_truncated: row.get::<_, Option<i64>>(27)?.is_some_and(|v| v != 0),
+    })
+}
+
+#[tauri::command]
+fn get_proxy_log_detail(log_id: i64) -> Result<ProxyLogDetail, String> {
+    let conn = proxy_log_db()?;
+    let mut stmt = conn
+        .prepare(&format!("SELECT {PROXY_LOG_DETAIL_COLUMNS} FROM request_logs WHERE id = ?1"))
+        .map_err(|e| e.to_string())?;
+    let log = stmt.query_row(params![log_id], proxy_log_detail_from_row).map_err(|e| {
         let msg = format!("日志详情查询失败 (id={log_id}): {e}");
         log_proxy(&msg);
         msg
@@ -9136,6 +14520,99 @@ fn get_proxy_log_detail(log_id: i64) -> Result<ProxyLogDetail, String> {
     Ok(log)
 }
 
+fn csv_field(value: &str) -> String {
+    if value.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Export request logs (newest first) as `csv` summary rows or full `json`
+/// detail rows. `data` is the file content, base64-encoded so it crosses IPC
+/// as one string rather than a JSON array of bytes. With `compress`, the bytes
+/// are gzipped (save as `.csv.gz` / `.json.gz`); `uncompressed_size` lets the
+/// UI show the ratio.
+#[tauri::command]
+fn export_proxy_logs(format: Option<String>, limit: Option<usize>, compress: Option<bool>) -> Result<Value, String> {
+    let format = format.unwrap_or_else(|| "csv".to_string()).trim().to_ascii_lowercase();
+    let limit = limit.map(|v| v as i64).unwrap_or(-1);
+    let rows: Vec<ProxyLogDetail> = {
+        let conn = proxy_log_db()?;
+        let mut stmt = conn
+            .prepare(&format!("SELECT {PROXY_LOG_DETAIL_COLUMNS} FROM request_logs ORDER BY id DESC LIMIT ?1"))
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![limit], proxy_log_detail_from_row)
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>().map_err(|e| e.to_string())?
+    };
+
+    let data = match format.as_str() {
+        "json" => serde_json::to_vec_pretty(&rows).map_err(|e| e.to_string())?,
+        "csv" => {
+            let mut out = String::from("id,timestamp,method,path,status,duration_ms,proxy_account_id,account_id,model,input_tokens,output_tokens,request_id,error\n");
+            for row in &rows {
+                let fields = [
+                    row.id.to_string(),
+                    row.timestamp.clone(),
+                    row.method.clone(),
+                    row.path.clone(),
+                    row.status.to_string(),
+                    row.duration_ms.to_string(),
+                    row.proxy_account_id.clone(),
+                    row.account_id.clone().unwrap_or_default(),
+                    row.model.clone().unwrap_or_default(),
+                    row.input_tokens.map(|v| v.to_string()).unwrap_or_default(),
+                    row.output_tokens.map(|v| v.to_string()).unwrap_or_default(),
+                    row.request_id.map(|v| v.to_string()).unwrap_or_default(),
+                    row.error.clone().unwrap_or_default(),
+                ];
+                let line: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+                out.push_str(&line.join(","));
+                out.push('\n');
+            }
+            out.into_bytes()
+        }
+        other => return Err(format!("不支持的导出格式: {other}")),
+    };
+
+    let uncompressed_size = data.len();
+    let compress = compress.unwrap_or(false);
+    let data = if compress {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&data).map_err(|e| e.to_string())?;
+        encoder.finish().map_err(|e| e.to_string())?
+    } else {
+        data
+    };
+    Ok(serde_json::json!({
+        "format": format,
+        "compressed": compress,
+        "rows": rows.len(),
+        "size": data.len(),
+        "uncompressed_size": uncompressed_size,
+        "data": base64::engine::general_purpose::STANDARD.encode(&data),
+    }))
+}
+
+/// Look up a log row by the `x-codex-manager-request-id` a client received.
+#[tauri::command]
+fn get_proxy_log_by_request_id(request_id: i64) -> Result<ProxyLogDetail, String> {
+    let log_id: i64 = {
+        let conn = proxy_log_db()?;
+        conn.query_row(
+            "SELECT id FROM request_logs WHERE request_id = ?1 ORDER BY id DESC LIMIT 1",
+            params![request_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("未找到请求日志 (request_id={request_id})"))?
+    };
+    get_proxy_log_detail(log_id)
+}
+
 #[tauri::command]
 fn get_proxy_token_stats(hours: Option<i64>) -> Result<ProxyTokenStats, String> {
     let hours = hours.unwrap_or(24).clamp(1, 24 * 30);
@@ -9245,6 +14722,113 @@ fn get_proxy_token_stats(hours: Option<i64>) -> Result<ProxyTokenStats, String>
     })
 }
 
+fn lookup_model_pricing<'a>(
+    pricing: &'a HashMap<String, ModelPricing>,
+    model: &str,
+) -> Option<&'a ModelPricing> {
+    pricing.get(model).or_else(|| {
+        pricing
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(model))
+            .map(|(_, price)| price)
+    })
+}
+
+#[tauri::command]
+fn get_cost_report(from_ts: Option<String>, to_ts: Option<String>) -> Result<CostReport, String> {
+    let cfg = proxy_config_snapshot();
+    let conn = proxy_log_db()?;
+    let from_ts = from_ts.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    let to_ts = to_ts.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT
+                COALESCE(NULLIF(account_id, ''), proxy_account_id) as account_name,
+                COALESCE(model, '') as model_name,
+                COUNT(*) as requests,
+                COALESCE(SUM(input_tokens), 0) as input_tokens,
+                COALESCE(SUM(output_tokens), 0) as output_tokens
+             FROM request_logs
+             WHERE (?1 IS NULL OR timestamp >= ?1) AND (?2 IS NULL OR timestamp < ?2)
+             GROUP BY account_name, model_name",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![from_ts, to_ts], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, i64>(4)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut accounts: HashMap<String, CostReportItem> = HashMap::new();
+    let mut models: HashMap<String, CostReportItem> = HashMap::new();
+    let mut unpriced = ProxyTokenStatsItem {
+        name: "unpriced".to_string(),
+        requests: 0,
+        input_tokens: 0,
+        output_tokens: 0,
+        total_tokens: 0,
+    };
+    let mut total_cost = 0.0;
+    for row in rows {
+        let (account_name, model_name, requests, input_tokens, output_tokens) =
+            row.map_err(|e| e.to_string())?;
+        let account = accounts.entry(account_name.clone()).or_insert_with(|| CostReportItem {
+            name: account_name,
+            requests: 0,
+            input_tokens: 0,
+            output_tokens: 0,
+            cost: 0.0,
+        });
+        account.requests += requests;
+        account.input_tokens += input_tokens;
+        account.output_tokens += output_tokens;
+
+        let Some(price) = lookup_model_pricing(&cfg.model_pricing, &model_name) else {
+            unpriced.requests += requests;
+            unpriced.input_tokens += input_tokens;
+            unpriced.output_tokens += output_tokens;
+            unpriced.total_tokens += input_tokens + output_tokens;
+            continue;
+        };
+        let cost = input_tokens as f64 / 1000.0 * price.input_per_1k
+            + output_tokens as f64 / 1000.0 * price.output_per_1k;
+        account.cost += cost;
+        total_cost += cost;
+        let model = models.entry(model_name.clone()).or_insert_with(|| CostReportItem {
+            name: model_name,
+            requests: 0,
+            input_tokens: 0,
+            output_tokens: 0,
+            cost: 0.0,
+        });
+        model.requests += requests;
+        model.input_tokens += input_tokens;
+        model.output_tokens += output_tokens;
+        model.cost += cost;
+    }
+
+    let mut accounts: Vec<CostReportItem> = accounts.into_values().collect();
+    accounts.sort_by(|a, b| b.cost.total_cmp(&a.cost).then(b.requests.cmp(&a.requests)));
+    let mut models: Vec<CostReportItem> = models.into_values().collect();
+    models.sort_by(|a, b| b.cost.total_cmp(&a.cost).then(b.requests.cmp(&a.requests)));
+
+    Ok(CostReport {
+        from_ts,
+        to_ts,
+        total_cost,
+        accounts,
+        models,
+        unpriced,
+    })
+}
+
 #[tauri::command]
 fn get_ai_cache_overview(hours: Option<i64>) -> Result<AICacheOverview, String> {
     let hours = hours.unwrap_or(24).clamp(1, 24 * 30);
@@ -9436,10 +15020,14 @@ struct AccountUsage {
     secondary_window_minutes: Option<u64>,
     secondary_resets_at: Option<i64>,
     // meta
-    availability: String, // "available" | "unavailable" | "primary_window_available_only" | "unknown"
+    availability: String, // "available" | "warning" | "unavailable" | "primary_window_available_only" | "unknown"
     captured_at: i64,     // unix timestamp seconds when this data was fetched
 }
 
+const USAGE_FETCH_ATTEMPTS: u32 = 3;
+const USAGE_RETRY_BASE_MS: u64 = 500;
+const USAGE_FETCH_DEADLINE_SECS: u64 = 45;
+
 async fn fetch_account_usage_by_id(id: &str) -> Result<AccountUsage, String> {
     let auth_path = accounts_dir().join(id).join("auth.json");
     if !auth_path.exists() {
@@ -9457,22 +15045,43 @@ async fn fetch_account_usage_by_id(id: &str) -> Result<AccountUsage, String> {
 
     let client = reqwest::Client::builder()
         .timeout(std::time::Duration::from_secs(30))
+        .connect_timeout(upstream_connect_timeout())
         .build()
         .map_err(|e| e.to_string())?;
 
-    let resp = client
-        .get("https://chatgpt.com/backend-api/wham/usage")
-        .header("Authorization", format!("Bearer {}", access_token))
-        .header("Content-Type", "application/json")
-        .send()
-        .await
-        .map_err(|e| format!("Request failed: {e}"))?;
-
-    if !resp.status().is_success() {
-        let status = resp.status();
-        let body = resp.text().await.unwrap_or_default();
-        return Err(format!("Usage API returned {status}: {body}"));
-    }
+    // Network errors and 5xx are retried with backoff; 4xx is final for this token.
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(USAGE_FETCH_DEADLINE_SECS);
+    let mut attempt = 0;
+    let resp = loop {
+        attempt += 1;
+        let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+        let result = client
+            .get("https://chatgpt.com/backend-api/wham/usage")
+            .header("Authorization", format!("Bearer {}", access_token))
+            .header("Content-Type", "application/json")
+            .timeout(remaining.min(std::time::Duration::from_secs(30)))
+            .send()
+            .await;
+        let err = match result {
+            Ok(resp) if resp.status().is_success() => break resp,
+            Ok(resp) if !resp.status().is_server_error() => {
+                let status = resp.status();
+                let body = resp.text().await.unwrap_or_default();
+                return Err(format!("Usage API returned {status}: {body}"));
+            }
+            Ok(resp) => {
+                let status = resp.status();
+                let body = resp.text().await.unwrap_or_default();
+                format!("Usage API returned {status}: {body}")
+            }
+            Err(e) => format!("Request failed: {e}"),
+        };
+        let backoff = std::time::Duration::from_millis(USAGE_RETRY_BASE_MS << (attempt - 1));
+        if attempt >= USAGE_FETCH_ATTEMPTS || std::time::Instant::now() + backoff >= deadline {
+            return Err(err);
+        }
+        tokio::time::sleep(backoff).await;
+    };
 
     let json: Value = resp.json().await.map_err(|e| e.to_string())?;
 
@@ -9484,32 +15093,174 @@ async fn fetch_account_usage_by_id(id: &str) -> Result<AccountUsage, String> {
     let window_minutes         = window_secs.map(|s| (s + 59) / 60);
     let resets_at              = pw.and_then(|v| v.get("reset_at")).and_then(|v| v.as_i64());
 
-    let secondary_used_percent = sw.and_then(|v| v.get("used_percent")).and_then(|v| v.as_f64());
-    let secondary_secs         = sw.and_then(|v| v.get("limit_window_seconds")).and_then(|v| v.as_u64());
-    let secondary_window_minutes = secondary_secs.map(|s| (s + 59) / 60);
-    let secondary_resets_at    = sw.and_then(|v| v.get("reset_at")).and_then(|v| v.as_i64());
+    let secondary_used_percent = sw.and_then(|v| v.get("used_percent")).and_then(|v| v.as_f64());
+    let secondary_secs         = sw.and_then(|v| v.get("limit_window_seconds")).and_then(|v| v.as_u64());
+    let secondary_window_minutes = secondary_secs.map(|s| (s + 59) / 60);
+    let secondary_resets_at    = sw.and_then(|v| v.get("reset_at")).and_then(|v| v.as_i64());
+
+    // "warning": either window is at or past `usage_warning_percent` but under 100
+    let warn_at = proxy_config_snapshot().usage_warning_percent.unwrap_or(f64::INFINITY);
+    let warned = used_percent.is_some_and(|p| p >= warn_at) || secondary_used_percent.is_some_and(|s| s >= warn_at);
+    let availability = match (used_percent, secondary_used_percent) {
+        (None, _)                                       => "unknown",
+        (Some(p), _) if p >= 100.0                      => "unavailable",
+        (Some(_), Some(s)) if s >= 100.0                => "unavailable",
+        _ if warned                                     => "warning",
+        (Some(_), None)                                 => "primary_window_available_only",
+        _                                               => "available",
+    }.to_string();
+
+    let captured_at = chrono::Utc::now().timestamp();
+
+    let usage = AccountUsage {
+        account_id: id.to_string(),
+        used_percent,
+        window_minutes,
+        resets_at,
+        secondary_used_percent,
+        secondary_window_minutes,
+        secondary_resets_at,
+        availability,
+        captured_at,
+    };
+    usage_cache().lock().unwrap().insert(id.to_string(), usage.clone());
+    Ok(usage)
+}
+
+static USAGE_CACHE: OnceLock<Mutex<HashMap<String, AccountUsage>>> = OnceLock::new();
+
+/// Last usage snapshot fetched for each account id.
+fn usage_cache() -> &'static Mutex<HashMap<String, AccountUsage>> {
+    USAGE_CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Bucket every on-disk account into available / rate_limited / blocked /
+/// expired / unknown, combining live pool health (when the proxy is running),
+/// cached usage and access-token expiry.
+#[tauri::command]
+fn get_account_health_summary() -> Result<Value, String> {
+    let state = {
+        let lock = PROXY_STATE.lock().unwrap();
+        lock.clone()
+    };
+    let now = std::time::Instant::now();
+    let pool: HashMap<String, AccountHealth> = match state {
+        Some(state) => state
+            .accounts
+            .read()
+            .unwrap()
+            .iter()
+            .map(|a| (a.id.clone(), a.health.clone()))
+            .collect(),
+        None => HashMap::new(),
+    };
+    let cache = usage_cache().lock().unwrap().clone();
+
+    let mut buckets: Vec<(&str, Vec<String>)> = ["available", "rate_limited", "blocked", "expired", "unknown"]
+        .into_iter()
+        .map(|name| (name, Vec::new()))
+        .collect();
+    for item in get_accounts_expiry()? {
+        let Some(id) = item.get("id").and_then(|v| v.as_str()).map(|s| s.to_string()) else {
+            continue;
+        };
+        let expired = item
+            .get("expires_in_secs")
+            .and_then(|v| v.as_i64())
+            .is_some_and(|secs| secs <= 0);
+        let availability = cache.get(&id).map(|u| u.availability.as_str());
+        let bucket = match pool.get(&id) {
+            Some(AccountHealth::Blocked) => "blocked",
+            _ if expired => "expired",
+            Some(AccountHealth::Cooldown(until)) if now < *until => "rate_limited",
+            _ if availability == Some("unavailable") => "rate_limited",
+            Some(_) => "available",
+            None => match availability {
+                Some("available") | Some("warning") | Some("primary_window_available_only") => "available",
+                _ => "unknown",
+            },
+        };
+        if let Some((_, ids)) = buckets.iter_mut().find(|(name, _)| *name == bucket) {
+            ids.push(id);
+        }
+    }
+
+    let mut out = serde_json::Map::new();
+    for (name, mut ids) in buckets {
+        ids.sort();
+        out.insert(
+            name.to_string(),
+            serde_json::json!({ "count": ids.len(), "ids": ids }),
+        );
+    }
+    Ok(Value::Object(out))
+}
 
-    let availability = match (used_percent, secondary_used_percent) {
-        (None, _)                                       => "unknown",
-        (Some(p), _) if p >= 100.0                      => "unavailable",
-        (Some(_), None)                                 => "primary_window_available_only",
-        (Some(_), Some(s)) if s >= 100.0                => "unavailable",
-        _                                               => "available",
-    }.to_string();
+/// Aggregate remaining primary-window quota across non-blocked pool accounts,
+/// using the last cached usage snapshot for each (no network calls).
+#[tauri::command]
+fn get_pool_capacity() -> Result<Value, String> {
+    let state = {
+        let lock = PROXY_STATE.lock().unwrap();
+        lock.clone()
+    };
+    let accounts: Vec<(String, &'static str)> = match state {
+        Some(state) => {
+            let accounts_lock = state.accounts.read().unwrap();
+            let accounts = accounts_lock
+                .iter()
+                .map(|a| (a.id.clone(), health_state_label(&a.health)))
+                .collect();
+            accounts
+        }
+        None => load_proxy_accounts()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|a| (a.id, "active"))
+            .collect(),
+    };
 
-    let captured_at = chrono::Utc::now().timestamp();
+    let cache = usage_cache().lock().unwrap().clone();
+    let mut total_remaining = 0.0;
+    let mut counted = 0usize;
+    let mut exhausted = 0usize;
+    let mut unknown = 0usize;
+    let mut items = Vec::new();
+    for (id, health) in accounts {
+        if health == "blocked" {
+            continue;
+        }
+        let usage = cache.get(&id);
+        let remaining = usage
+            .and_then(|u| u.used_percent)
+            .map(|used| (100.0 - used).clamp(0.0, 100.0));
+        match remaining {
+            Some(r) => {
+                total_remaining += r;
+                counted += 1;
+                if r <= 0.0 {
+                    exhausted += 1;
+                }
+            }
+            None => unknown += 1,
+        }
+        items.push(serde_json::json!({
+            "id": id,
+            "health": health,
+            "remaining_percent": remaining,
+            "resets_at": usage.and_then(|u| u.resets_at),
+            "captured_at": usage.map(|u| u.captured_at),
+        }));
+    }
 
-    Ok(AccountUsage {
-        account_id: id.to_string(),
-        used_percent,
-        window_minutes,
-        resets_at,
-        secondary_used_percent,
-        secondary_window_minutes,
-        secondary_resets_at,
-        availability,
-        captured_at,
-    })
+    Ok(serde_json::json!({
+        "total_remaining_percent": total_remaining,
+        "average_remaining_percent": if counted > 0 { Some(total_remaining / counted as f64) } else { None },
+        "accounts_counted": counted,
+        "accounts_exhausted": exhausted,
+        "accounts_unknown": unknown,
+        "accounts": items,
+    }))
 }
 
 fn usage_score(usage: &AccountUsage) -> f64 {
@@ -9525,7 +15276,7 @@ fn usage_score(usage: &AccountUsage) -> f64 {
 async fn select_highest_quota_account(
     state: Arc<ProxyState>,
     exclude_idx: usize,
-) -> Option<(usize, String, String, Option<String>)> {
+) -> Option<(usize, String, String, Option<String>, AccountUpstreamIdentity)> {
     let mut exclude = HashSet::new();
     exclude.insert(exclude_idx);
     select_highest_quota_account_excluding(state, &exclude).await
@@ -9534,31 +15285,31 @@ async fn select_highest_quota_account(
 async fn select_highest_quota_account_excluding(
     state: Arc<ProxyState>,
     exclude: &HashSet<usize>,
-) -> Option<(usize, String, String, Option<String>)> {
+) -> Option<(usize, String, String, Option<String>, AccountUpstreamIdentity)> {
     let candidates = {
         let accounts_lock = state.accounts.read().unwrap();
         accounts_lock
             .iter()
             .enumerate()
             .filter(|(idx, acc)| !exclude.contains(idx) && acc.health == AccountHealth::Active)
-            .map(|(idx, acc)| (idx, acc.id.clone(), acc.access_token.clone(), acc.account_id.clone()))
+            .map(|(idx, acc)| (idx, acc.id.clone(), acc.access_token.clone(), acc.account_id.clone(), acc.org.clone()))
             .collect::<Vec<_>>()
     };
 
     let fallback = candidates.first().cloned();
-    let mut best: Option<(usize, String, String, Option<String>, f64)> = None;
-    for (idx, id, token, account_id) in candidates {
+    let mut best: Option<(usize, String, String, Option<String>, AccountUpstreamIdentity, f64)> = None;
+    for (idx, id, token, account_id, org) in candidates {
         if let Ok(usage) = fetch_account_usage_by_id(&id).await {
             let score = usage_score(&usage);
             match best {
-                Some((_, _, _, _, best_score)) if score <= best_score => {}
-                _ => best = Some((idx, id, token, account_id, score)),
+                Some((_, _, _, _, _, best_score)) if score <= best_score => {}
+                _ => best = Some((idx, id, token, account_id, org, score)),
             }
         }
     }
 
     match best {
-        Some((idx, id, token, account_id, _)) => Some((idx, id, token, account_id)),
+        Some((idx, id, token, account_id, org, _)) => Some((idx, id, token, account_id, org)),
         None => fallback,
     }
 }
@@ -9569,6 +15320,138 @@ async fn get_account_usage(id: String) -> Result<AccountUsage, String> {
     fetch_account_usage_by_id(&id).await
 }
 
+/// Last usage snapshot for `id` without touching the network, or None if it
+/// was never fetched. `captured_at` tells the UI how old it is.
+#[tauri::command]
+fn get_cached_account_usage(id: String) -> Result<Option<AccountUsage>, String> {
+    Ok(usage_cache().lock().unwrap().get(&id).cloned())
+}
+
+const BENCHMARK_SAMPLES: usize = 3;
+const BENCHMARK_MAX_SAMPLES: usize = 10;
+const BENCHMARK_CONCURRENCY: usize = 4;
+const BENCHMARK_DEADLINE_SECS: u64 = 60;
+
+/// Time `samples` sequential `/v1/models` requests for one account, stopping
+/// early at `deadline`. Latencies cover the full response, body included.
+async fn benchmark_account(id: String, url: String, samples: usize, deadline: std::time::Instant) -> Value {
+    let auth_data = fs::read_to_string(accounts_dir().join(&id).join("auth.json"))
+        .ok()
+        .and_then(|s| serde_json::from_str::<Value>(&s).ok())
+        .unwrap_or(Value::Null);
+    let (access_token, _, account_id) = extract_auth_tokens(&auth_data);
+    if access_token.trim().is_empty() {
+        return serde_json::json!({ "id": id, "success": false, "error": "No access token for this account" });
+    }
+    let meta = read_meta();
+    let org = meta
+        .get(&id)
+        .map(|m| AccountUpstreamIdentity {
+            organization: m.organization.clone(),
+            project: m.project.clone(),
+            client_profile: m.client_profile.clone(),
+        })
+        .unwrap_or_default();
+    let outbound_proxy = meta.get(&id).and_then(|m| m.outbound_proxy.clone());
+
+    let mut builder = reqwest::Client::builder().timeout(std::time::Duration::from_secs(15));
+    if let Some(proxy_url) = &outbound_proxy {
+        match reqwest::Proxy::all(proxy_url) {
+            Ok(proxy) => builder = builder.proxy(proxy),
+            Err(err) => return serde_json::json!({ "id": id, "success": false, "error": format!("outbound proxy: {err}") }),
+        }
+    }
+    let client = match builder.build() {
+        Ok(client) => client,
+        Err(err) => return serde_json::json!({ "id": id, "success": false, "error": err.to_string() }),
+    };
+    let mut headers = reqwest::header::HeaderMap::new();
+    apply_upstream_headers(
+        &mut headers,
+        &access_token,
+        account_id.as_deref(),
+        Some(&org),
+        &axum::http::HeaderMap::new(),
+        false,
+        false,
+    );
+
+    let mut latencies: Vec<u64> = Vec::new();
+    let mut error = None;
+    for _ in 0..samples {
+        let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+        if remaining.is_zero() {
+            error = Some("benchmark deadline reached".to_string());
+            break;
+        }
+        let started = std::time::Instant::now();
+        let result = client
+            .get(&url)
+            .headers(headers.clone())
+            .timeout(remaining.min(std::time::Duration::from_secs(15)))
+            .send()
+            .await;
+        match result {
+            Ok(resp) if resp.status().is_success() => {
+                if let Err(err) = resp.bytes().await {
+                    error = Some(err.to_string());
+                    break;
+                }
+                latencies.push(started.elapsed().as_millis() as u64);
+            }
+            Ok(resp) => {
+                error = Some(format!("HTTP {}", resp.status().as_u16()));
+                break;
+            }
+            Err(err) => {
+                error = Some(err.to_string());
+                break;
+            }
+        }
+    }
+
+    let ok = latencies.len();
+    let avg_ms = if ok > 0 { Some(latencies.iter().sum::<u64>() / ok as u64) } else { None };
+    serde_json::json!({
+        "id": id,
+        "success": error.is_none() && ok > 0,
+        "samples": ok,
+        "min_ms": latencies.iter().min(),
+        "avg_ms": avg_ms,
+        "max_ms": latencies.iter().max(),
+        "error": error,
+    })
+}
+
+/// Measure `/v1/models` latency for every managed account (`samples` requests
+/// each, default 3). Accounts run a few at a time under one overall deadline;
+/// results are sorted fastest first, failed accounts last.
+#[tauri::command]
+async fn benchmark_accounts(samples: Option<usize>) -> Result<Vec<Value>, String> {
+    let samples = samples.unwrap_or(BENCHMARK_SAMPLES).clamp(1, BENCHMARK_MAX_SAMPLES);
+    let ids: Vec<String> = fs::read_dir(accounts_dir())
+        .map_err(|e| e.to_string())?
+        .flatten()
+        .filter(|entry| entry.path().join("auth.json").exists())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .collect();
+
+    let url = build_upstream_url(&normalize_models_path("/v1/models"));
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(BENCHMARK_DEADLINE_SECS);
+    let mut results: Vec<Value> = futures_util::stream::iter(ids)
+        .map(|id| benchmark_account(id, url.clone(), samples, deadline))
+        .buffer_unordered(BENCHMARK_CONCURRENCY)
+        .collect()
+        .await;
+    results.sort_by_key(|r| {
+        (
+            !r.get("success").and_then(|v| v.as_bool()).unwrap_or(false),
+            r.get("avg_ms").and_then(|v| v.as_u64()).unwrap_or(u64::MAX),
+        )
+    });
+    Ok(results)
+}
+
 /// Fetch available Codex models from upstream for UI selection.
 #[tauri::command]
 async fn list_codex_models() -> Result<Vec<String>, String> {
@@ -9595,7 +15478,7 @@ async fn list_codex_models() -> Result<Vec<String>, String> {
             .map_err(|e| e.to_string())?;
         let status = resp.status().as_u16();
         let body_bytes = resp.bytes().await.map_err(|e| e.to_string())?;
-        let body_text = truncate_body(&body_bytes);
+        let (body_text, _) = cap_body_text(&body_bytes);
         log_proxy(&format!("models: custom status {status} body={body_text}"));
         if !(200..300).contains(&status) {
             return Err(format!("获取模型失败 (HTTP {status})，且未配置模型覆盖。请直接填写模型名称，例如 glm-5。"));
@@ -9620,7 +15503,7 @@ async fn list_codex_models() -> Result<Vec<String>, String> {
         return Ok(models);
     }
 
-    let (auth_data, is_managed) = load_any_auth_data()?;
+    let (auth_data, managed_id) = load_any_auth_data()?;
     let (access_token, refresh_token, account_id) = extract_auth_tokens(&auth_data);
     if access_token.trim().is_empty() {
         return Err("账号缺少 access_token，请重新登录。".to_string());
@@ -9636,6 +15519,7 @@ async fn list_codex_models() -> Result<Vec<String>, String> {
         &mut headers,
         &access_token,
         account_id.as_deref(),
+        None,
         &incoming_headers,
         false,
         false,
@@ -9648,9 +15532,11 @@ async fn list_codex_models() -> Result<Vec<String>, String> {
         .await
         .map_err(|e| e.to_string())?;
 
-    if resp.status() == reqwest::StatusCode::UNAUTHORIZED && is_managed {
-        if let (Some(id), Some(rt)) = (account_id.as_deref(), refresh_token.as_deref()) {
-            if let Some(new_token) = try_refresh_account(id, rt).await {
+    // Only a managed account may be refreshed, and not one marked `no_refresh`
+    let refreshable_id = managed_id.filter(|id| !read_meta().get(id).is_some_and(|m| m.no_refresh));
+    if resp.status() == reqwest::StatusCode::UNAUTHORIZED {
+        if let (Some(id), Some(rt)) = (refreshable_id.as_deref(), refresh_token.as_deref()) {
+            if let Ok(new_token) = try_refresh_account(id, rt).await {
                 headers.insert(
                     reqwest::header::AUTHORIZATION,
                     reqwest::header::HeaderValue::from_str(&format!("Bearer {}", new_token))
@@ -9668,7 +15554,7 @@ async fn list_codex_models() -> Result<Vec<String>, String> {
 
     let status = resp.status().as_u16();
     let body_bytes = resp.bytes().await.map_err(|e| e.to_string())?;
-    let body_text = truncate_body(&body_bytes);
+    let (body_text, _) = cap_body_text(&body_bytes);
     log_proxy(&format!("models: status {status} body={body_text}"));
     if !(200..300).contains(&status) {
         return Err(format!("获取模型失败 (HTTP {status}): {body_text}"));
@@ -9734,28 +15620,78 @@ pub fn run() {
     tauri::Builder::default()
         .setup(|app| {
             let _ = APP_HANDLE.set(app.handle().clone());
+            for problem in environment_problems() {
+                log_proxy(&format!("[env] {problem}"));
+            }
+            tauri::async_runtime::spawn(shutdown_on_signal(app.handle().clone()));
+            if let Ok(pruned) = prune_orphan_meta() {
+                if !pruned.is_empty() {
+                    log_proxy(&format!("[meta] pruned orphan entries: {}", pruned.join(", ")));
+                }
+            }
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             list_accounts,
+            get_accounts_expiry,
+            get_accounts_without_refresh,
+            audit_accounts,
+            export_account,
+            export_accounts_manifest,
             get_current_account,
             switch_account,
             delete_account,
+            prune_orphan_meta,
+            get_accounts_meta,
+            set_accounts_meta,
+            rename_account,
+            normalize_account_ids,
+            snapshot_state,
+            restore_state,
             update_label,
             update_proxy_enabled,
+            set_account_org,
+            set_account_outbound_proxy,
+            set_account_client_profile,
+            set_account_base_url,
+            set_account_no_refresh,
+            set_account_tags,
+            refresh_accounts_by_tag,
             import_current,
+            import_current_merge,
+            import_accounts_from_dir,
             get_config,
+            set_config,
             launch_codex_login,
             oauth_login,
             get_oauth_url,
             complete_oauth_manual,
             refresh_account_token,
+            verify_refresh_token,
             get_account_usage,
+            get_cached_account_usage,
+            benchmark_accounts,
+            get_pool_capacity,
+            get_account_health_summary,
             list_codex_models,
             start_api_proxy,
             stop_api_proxy,
             reload_proxy_accounts,
+            reload_proxy_accounts_by_tag,
             get_proxy_status,
+            peek_next_account,
+            get_proxy_pool,
+            get_proxy_bindings,
+            check_proxy_api_key,
+            list_accounts_with_health,
+            get_refresh_stats,
+            simulate_selection,
+            test_proxy_stream,
+            set_account_health,
+            reset_proxy_rotation,
+            get_diagnostics,
+            check_environment,
+            preview_upstream_headers,
             list_openai_compat_configs,
             create_openai_compat_config,
             update_openai_compat_config,
@@ -9766,12 +15702,26 @@ pub fn run() {
             get_openai_compat_proxy_status,
             get_proxy_config,
             update_proxy_config,
+            reload_proxy_config,
             generate_proxy_api_key,
             clear_proxy_logs,
+            rotate_proxy_log_db,
+            get_proxy_log_file,
+            clear_proxy_log_file,
+            purge_proxy_logs_before,
+            get_health_events,
+            get_path_stats,
+            get_account_error_rates,
+            get_log_facets,
+            get_slowest_requests,
             get_proxy_logs_count_filtered,
             get_proxy_logs_filtered,
+            get_proxy_logs_page,
             get_proxy_log_detail,
+            get_proxy_log_by_request_id,
+            export_proxy_logs,
             get_proxy_token_stats,
+            get_cost_report,
             get_ai_cache_overview,
             get_ai_cache_trend,
             list_ai_cache_entries,
@@ -9787,3 +15737,842 @@ pub fn run() {
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Tests that touch process-wide statics (PROXY_PORT, PROXY_STATE, env) take this lock
+    static GLOBALS: Mutex<()> = Mutex::new(());
+
+    fn lock_globals() -> std::sync::MutexGuard<'static, ()> {
+        let guard = GLOBALS.lock().unwrap_or_else(|e| e.into_inner());
+        // Start every such test from the default config, not whatever is on disk
+        *proxy_config().lock().unwrap() = ProxyConfig::default();
+        guard
+    }
+
+    fn test_account(id: &str) -> ProxyAccount {
+        ProxyAccount {
+            id: id.to_string(),
+            account_id: Some(format!("acct-{id}")),
+            access_token: format!("token-{id}"),
+            refresh_token: None,
+            health: AccountHealth::Active,
+            org: AccountUpstreamIdentity::default(),
+            cooldown_streak: 0,
+            outbound_proxy: None,
+            access_expires_at: None,
+            last_used_at: None,
+            base_url: None,
+        }
+    }
+
+    fn test_state(accounts: Vec<ProxyAccount>) -> ProxyState {
+        let count = accounts.len();
+        ProxyState {
+            client: reqwest::Client::new(),
+            accounts: Arc::new(RwLock::new(accounts)),
+            req_counter: AtomicUsize::new(round_robin_seed(count)),
+            models_cache: Mutex::new(None),
+            models_refreshing: AtomicBool::new(false),
+            total_requests: AtomicUsize::new(0),
+            total_errors: AtomicUsize::new(0),
+            started_at: String::new(),
+            last_request_at: Mutex::new(std::time::Instant::now()),
+            account_clients: Mutex::new(HashMap::new()),
+            dropped_accounts: Mutex::new(Vec::new()),
+            pool_tag: Mutex::new(None),
+            get_cache: Mutex::new(std::collections::VecDeque::new()),
+            get_cache_hits: AtomicUsize::new(0),
+            get_cache_misses: AtomicUsize::new(0),
+            circuit: Mutex::new(CircuitBreaker::default()),
+            idempotency_cache: Mutex::new(std::collections::VecDeque::new()),
+            idempotency_inflight: Mutex::new(HashMap::new()),
+            bindings: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Point HOME at a fresh empty directory for the rest of the test.
+    fn temp_home(name: &str) -> PathBuf {
+        let home = std::env::temp_dir().join(format!("codex-manager-test-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&home);
+        fs::create_dir_all(&home).unwrap();
+        std::env::set_var("HOME", &home);
+        home
+    }
+
+    #[test]
+    fn upstream_url_targets_local_port_matches_loopback_hosts() {
+        for target in [
+            "http://127.0.0.1:8520/v1/responses",
+            "http://localhost:8520/v1",
+            "http://LOCALHOST:8520",
+            "http://[::1]:8520/v1",
+            "http://0.0.0.0:8520/v1",
+            "http://127.1.2.3:8520/",
+        ] {
+            assert!(upstream_url_targets_local_port(target, 8520), "{target}");
+        }
+    }
+
+    #[test]
+    fn upstream_url_targets_local_port_ignores_other_ports_and_hosts() {
+        for target in [
+            "http://127.0.0.1:8521/v1",
+            "https://chatgpt.com/backend-api/codex",
+            "http://192.168.1.10:8520/v1",
+            "http://example.com:8520/v1",
+            "not a url",
+        ] {
+            assert!(!upstream_url_targets_local_port(target, 8520), "{target}");
+        }
+        // Known default ports count when the proxy listens on them
+        assert!(upstream_url_targets_local_port("http://localhost/v1", 80));
+    }
+
+    #[test]
+    fn upstream_loops_back_to_proxy_uses_running_port() {
+        let _guard = lock_globals();
+        let previous = PROXY_PORT.lock().unwrap().take();
+        assert!(!upstream_loops_back_to_proxy("http://127.0.0.1:8520/v1"));
+        *PROXY_PORT.lock().unwrap() = Some(8520);
+        assert!(upstream_loops_back_to_proxy("http://127.0.0.1:8520/v1"));
+        assert!(!upstream_loops_back_to_proxy("https://chatgpt.com/backend-api/codex"));
+        *PROXY_PORT.lock().unwrap() = previous;
+    }
+
+    #[test]
+    fn rename_account_keeps_active_account_matched_by_refresh_token() {
+        let _guard = lock_globals();
+        let home = temp_home("rename");
+        let auth = serde_json::json!({
+            "tokens": { "access_token": "not-a-jwt", "refresh_token": "rt-shared" },
+        });
+        fs::create_dir_all(accounts_dir().join("acct-1")).unwrap();
+        fs::write(accounts_dir().join("acct-1").join("auth.json"), auth.to_string()).unwrap();
+        fs::write(auth_file(), auth.to_string()).unwrap();
+
+        assert_eq!(get_current_account().unwrap().map(|a| a.id), Some("acct-1".to_string()));
+        // The current-account view carries the same meta as `list_accounts`
+        let mut meta = read_meta();
+        let entry = meta.entry("acct-1".to_string()).or_insert_with(|| MetaEntry::new(0));
+        entry.last_used_at = Some(42);
+        entry.no_refresh = true;
+        entry.base_url = Some("https://example.test/v1".to_string());
+        write_meta(&meta);
+        let current = get_current_account().unwrap().unwrap();
+        assert_eq!(current.last_used_at, Some(42));
+        assert!(current.no_refresh);
+        assert_eq!(current.base_url.as_deref(), Some("https://example.test/v1"));
+        assert!(rename_account("acct-1".to_string(), "work-main".to_string()).unwrap());
+        assert!(!accounts_dir().join("acct-1").exists());
+        assert_eq!(get_current_account().unwrap().map(|a| a.id), Some("work-main".to_string()));
+
+        // The source id is checked before anything outside accounts/ is touched
+        fs::create_dir_all(home.join("outside")).unwrap();
+        assert!(rename_account("../../outside".to_string(), "moved".to_string()).is_err());
+        assert!(home.join("outside").exists());
+        assert!(!accounts_dir().join("moved").exists());
+
+        let _ = fs::remove_dir_all(home);
+    }
+
+    #[test]
+    fn round_robin_seed_honours_test_hook() {
+        let _guard = lock_globals();
+        std::env::set_var("CODEXMANAGER_RR_SEED", "2");
+        let state = test_state(vec![test_account("a"), test_account("b"), test_account("c")]);
+        let (pool_size, picked) = pick_account(&state);
+        assert_eq!(pool_size, 3);
+        assert_eq!(picked.map(|p| p.3), Some("c".to_string()));
+        // The next pick continues round-robin from the seed
+        assert_eq!(pick_account(&state).1.map(|p| p.3), Some("a".to_string()));
+
+        std::env::set_var("CODEXMANAGER_RR_SEED", "7");
+        let state = test_state(vec![test_account("a"), test_account("b"), test_account("c")]);
+        assert_eq!(pick_account(&state).1.map(|p| p.3), Some("b".to_string()));
+
+        std::env::remove_var("CODEXMANAGER_RR_SEED");
+        for _ in 0..32 {
+            assert!(round_robin_seed(3) < 3);
+        }
+        assert_eq!(round_robin_seed(0), 0);
+    }
+
+    #[test]
+    fn extract_model_reads_top_level_nested_and_bom_bodies() {
+        assert_eq!(extract_model(br#"{"model":"gpt-5"}"#), Some("gpt-5".to_string()));
+        assert_eq!(extract_model(b"\xEF\xBB\xBF{\"model\":\"gpt-5\"}"), Some("gpt-5".to_string()));
+        assert_eq!(extract_model(br#"{"request":{"model":"o3"}}"#), Some("o3".to_string()));
+        assert_eq!(extract_model(br#"{"params":{"model":"gpt-4.1"}}"#), Some("gpt-4.1".to_string()));
+        // The top-level field wins over nested ones
+        assert_eq!(extract_model(br#"{"model":"a","body":{"model":"b"}}"#), Some("a".to_string()));
+    }
+
+    #[test]
+    fn extract_model_is_none_when_absent_or_unusable() {
+        assert_eq!(extract_model(b""), None);
+        assert_eq!(extract_model(b"\xEF\xBB\xBF"), None);
+        assert_eq!(extract_model(br#"{"input":"hi"}"#), None);
+        assert_eq!(extract_model(br#"{"model":"   "}"#), None);
+        assert_eq!(extract_model(br#"{"model":42}"#), None);
+        assert_eq!(extract_model(b"not json"), None);
+    }
+
+    fn upstream_headers(pairs: &[(&str, &str)]) -> reqwest::header::HeaderMap {
+        let mut headers = reqwest::header::HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(
+                reqwest::header::HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                reqwest::header::HeaderValue::from_str(value).unwrap(),
+            );
+        }
+        headers
+    }
+
+    fn forwarded_names(headers: &reqwest::header::HeaderMap, cfg: &ProxyConfig) -> Vec<String> {
+        let mut names: Vec<String> = forwarded_response_headers(headers, cfg)
+            .into_iter()
+            .map(|(name, _)| name.as_str().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn response_headers_drop_hop_by_hop_and_content_length_by_default() {
+        let headers = upstream_headers(&[
+            ("content-type", "text/event-stream"),
+            ("content-encoding", "gzip"),
+            ("content-length", "123"),
+            ("transfer-encoding", "chunked"),
+            ("connection", "keep-alive"),
+            ("x-request-id", "abc"),
+        ]);
+        assert_eq!(
+            forwarded_names(&headers, &ProxyConfig::default()),
+            vec!["content-encoding", "content-type", "x-request-id"]
+        );
+    }
+
+    #[test]
+    fn response_headers_honour_strip_and_passthrough_lists() {
+        let headers = upstream_headers(&[
+            ("content-type", "application/json"),
+            ("content-encoding", "br"),
+            ("content-length", "10"),
+            ("x-vendor-debug", "1"),
+        ]);
+        let mut cfg = ProxyConfig {
+            strip_response_headers: vec!["x-vendor-debug".into(), "content-encoding".into(), "content-type".into()],
+            ..ProxyConfig::default()
+        };
+        assert!(forwarded_names(&headers, &cfg).is_empty());
+
+        // Passthrough wins over the strip list, but never over content-length
+        cfg.passthrough_response_headers = vec!["content-encoding".into(), "content-type".into(), "content-length".into()];
+        assert_eq!(forwarded_names(&headers, &cfg), vec!["content-encoding", "content-type"]);
+    }
+
+    #[test]
+    fn response_header_values_are_forwarded_untouched() {
+        let headers = upstream_headers(&[("content-type", "application/json; charset=utf-8"), ("content-encoding", "gzip")]);
+        let forwarded = forwarded_response_headers(&headers, &ProxyConfig::default());
+        let value = |name: &str| forwarded.iter().find(|(n, _)| n.as_str() == name).map(|(_, v)| v.to_str().unwrap().to_string());
+        assert_eq!(value("content-type").as_deref(), Some("application/json; charset=utf-8"));
+        assert_eq!(value("content-encoding").as_deref(), Some("gzip"));
+    }
+
+    fn gzip(data: &[u8]) -> Bytes {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        Bytes::from(encoder.finish().unwrap())
+    }
+
+    #[test]
+    fn decode_request_body_inflates_gzip() {
+        let body = br#"{"model":"gpt-5","input":"hi"}"#;
+        for encoding in ["gzip", "x-gzip", " GZIP "] {
+            let decoded = decode_request_body(Some(encoding), gzip(body), 1024).unwrap();
+            assert_eq!(decoded.as_ref(), body, "{encoding}");
+        }
+        // Without an encoding (or `identity`) the body is passed through as is
+        let raw = Bytes::from_static(b"plain");
+        assert_eq!(decode_request_body(None, raw.clone(), 1024).unwrap(), raw);
+        assert_eq!(decode_request_body(Some("identity"), raw.clone(), 1024).unwrap(), raw);
+    }
+
+    #[test]
+    fn decode_request_body_rejects_unknown_encoding_and_bad_data() {
+        let err = decode_request_body(Some("br"), Bytes::from_static(b"x"), 1024).unwrap_err();
+        assert!(err.contains("Unsupported content-encoding: br"), "{err}");
+        let err = decode_request_body(Some("gzip"), Bytes::from_static(b"not gzip"), 1024).unwrap_err();
+        assert!(err.contains("Invalid gzip request body"), "{err}");
+    }
+
+    #[test]
+    fn decode_request_body_enforces_limit_on_decoded_size() {
+        let body = vec![b'a'; 4096];
+        let compressed = gzip(&body);
+        assert!(compressed.len() < 1024);
+        let err = decode_request_body(Some("gzip"), compressed.clone(), 1024).unwrap_err();
+        assert_eq!(err, "Request body too large");
+        assert_eq!(decode_request_body(Some("gzip"), compressed, 4096).unwrap().len(), 4096);
+    }
+
+    #[test]
+    fn upstream_accept_value_table() {
+        let cases: &[(Option<&str>, bool, &str)] = &[
+            // Streams always ask for SSE, whatever the client sent
+            (None, true, "text/event-stream"),
+            (Some("application/json"), true, "text/event-stream"),
+            (Some("*/*"), true, "text/event-stream"),
+            // Missing, blank or wildcard falls back to JSON
+            (None, false, "application/json"),
+            (Some(""), false, "application/json"),
+            (Some("*/*"), false, "application/json"),
+            (Some(" */* "), false, "application/json"),
+            // An SSE Accept on a non-stream request isn't kept
+            (Some("text/event-stream"), false, "application/json"),
+            // Explicit values are kept
+            (Some("application/json"), false, "application/json"),
+            (Some("application/x-ndjson"), false, "application/x-ndjson"),
+            (Some("application/json, text/plain"), false, "application/json, text/plain"),
+        ];
+        for (incoming, is_stream, expected) in cases {
+            assert_eq!(
+                upstream_accept_value(*incoming, *is_stream).to_str().unwrap(),
+                *expected,
+                "incoming={incoming:?} is_stream={is_stream}"
+            );
+        }
+    }
+
+    #[test]
+    fn only_get_head_and_unstored_posts_are_hedgeable() {
+        assert!(is_hedgeable_request(&reqwest::Method::GET, b""));
+        assert!(is_hedgeable_request(&reqwest::Method::HEAD, b""));
+        assert!(is_hedgeable_request(&reqwest::Method::POST, br#"{"store":false,"input":"hi"}"#));
+        assert!(!is_hedgeable_request(&reqwest::Method::POST, br#"{"input":"hi"}"#));
+        assert!(!is_hedgeable_request(&reqwest::Method::POST, br#"{"store":true}"#));
+        assert!(!is_hedgeable_request(&reqwest::Method::POST, b"not json"));
+        assert!(!is_hedgeable_request(&reqwest::Method::PUT, br#"{"store":false}"#));
+    }
+
+    #[test]
+    fn last_used_is_kept_in_memory_until_flushed() {
+        let _guard = lock_globals();
+        let home = temp_home("last-used");
+        fs::create_dir_all(accounts_dir().join("a")).unwrap();
+        fs::write(accounts_dir().join("a").join("auth.json"), "{}").unwrap();
+        let state = test_state(vec![test_account("a"), test_account("gone")]);
+
+        note_account_used(&state, 0);
+        note_account_used(&state, 1);
+        assert!(!meta_file().exists());
+
+        flush_last_used();
+        let meta = read_meta();
+        assert!(meta.get("a").and_then(|m| m.last_used_at).is_some());
+        assert!(!meta.contains_key("gone"));
+        assert!(LAST_USED_PENDING.get().unwrap().lock().unwrap().is_empty());
+
+        let _ = fs::remove_dir_all(home);
+    }
+
+    #[test]
+    fn get_cache_key_separates_clients_and_accounts() {
+        let bearer = upstream_headers(&[("authorization", "Bearer key-1")]);
+        let x_api_key = upstream_headers(&[("x-api-key", "key-1")]);
+        let other = upstream_headers(&[("authorization", "Bearer key-2")]);
+        let client = presented_client_key(&bearer);
+        assert!(!client.is_empty());
+        assert!(!client.contains("key-1"));
+        assert_eq!(presented_client_key(&x_api_key), client);
+        assert_ne!(presented_client_key(&other), client);
+        assert_eq!(presented_client_key(&axum::http::HeaderMap::new()), "");
+
+        let key = get_cache_key("/responses/resp_1", "a", &client);
+        assert_ne!(key, get_cache_key("/responses/resp_1", "b", &client));
+        assert_ne!(key, get_cache_key("/responses/resp_1", "a", &presented_client_key(&other)));
+    }
+
+    #[test]
+    fn signal_shutdown_lets_in_flight_log_writes_finish() {
+        let _guard = lock_globals();
+        let home = temp_home("signal-drain");
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let (tx, rx) = oneshot::channel::<()>();
+            *PROXY_SHUTDOWN.lock().unwrap() = Some(tx);
+            *PROXY_STATE.lock().unwrap() = Some(Arc::new(test_state(vec![test_account("a")])));
+            // Stands in for the serve task: it finishes a request's log write after
+            // the shutdown signal, then clears the running state
+            let serve = tokio::spawn(async move {
+                let _ = rx.await;
+                tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+                let entry = ProxyLogEntry {
+                    timestamp: "2026-01-01T00:00:00Z".to_string(),
+                    method: "POST".to_string(),
+                    path: "/v1/responses".to_string(),
+                    status: 200,
+                    proxy_account_id: "a".to_string(),
+                    request_id: Some(892_892),
+                    ..ProxyLogEntry::default()
+                };
+                insert_proxy_log(&entry).unwrap();
+                *PROXY_STATE.lock().unwrap() = None;
+            });
+
+            assert!(stop_proxies_and_drain(std::time::Duration::from_secs(5)).await);
+            serve.await.unwrap();
+            let conn = proxy_log_db().unwrap();
+            let rows: i64 = conn
+                .query_row("SELECT COUNT(*) FROM request_logs WHERE request_id = ?1", params![892_892], |row| row.get(0))
+                .unwrap();
+            assert_eq!(rows, 1);
+            let integrity: String = conn.query_row("PRAGMA integrity_check", [], |row| row.get(0)).unwrap();
+            assert_eq!(integrity, "ok");
+            drop(conn);
+        });
+
+        let _ = fs::remove_dir_all(home);
+    }
+
+    #[test]
+    fn request_bodies_with_an_idempotency_key_are_buffered() {
+        let cfg = ProxyConfig {
+            stream_request_body: true,
+            enable_logging: false,
+            enable_exact_cache: false,
+            ..ProxyConfig::default()
+        };
+        let mut headers = axum::http::HeaderMap::new();
+        headers.insert("idempotency-key", axum::http::HeaderValue::from_static("k1"));
+        assert!(can_stream_request_body(&cfg, &reqwest::Method::POST, "/v1/responses", &headers));
+        let cfg = ProxyConfig { idempotency_enabled: true, ..cfg };
+        assert!(!can_stream_request_body(&cfg, &reqwest::Method::POST, "/v1/responses", &headers));
+        assert!(can_stream_request_body(&cfg, &reqwest::Method::POST, "/v1/responses", &axum::http::HeaderMap::new()));
+    }
+
+    #[test]
+    fn head_through_proxy_returns_headers_without_body_or_error() {
+        let _guard = lock_globals();
+        let home = temp_home("head");
+        fs::create_dir_all(accounts_dir().join("a")).unwrap();
+        let auth = serde_json::json!({ "tokens": { "access_token": "token-a", "account_id": "acct-a" } });
+        fs::write(accounts_dir().join("a").join("auth.json"), auth.to_string()).unwrap();
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            // Mock upstream: remembers the method it got for the probed path
+            let seen_method: Arc<Mutex<Option<String>>> = Arc::new(Mutex::new(None));
+            let seen = seen_method.clone();
+            let upstream = axum::Router::new().fallback(move |req: axum::http::Request<axum::body::Body>| {
+                let seen = seen.clone();
+                async move {
+                    if req.uri().path().ends_with("/responses/resp_1") {
+                        *seen.lock().unwrap() = Some(req.method().to_string());
+                    }
+                    axum::http::Response::builder()
+                        .header("content-type", "application/json")
+                        .header("x-upstream", "mock")
+                        .body(axum::body::Body::from("{\"id\":\"resp_1\"}"))
+                        .unwrap()
+                }
+            });
+            let upstream_listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let upstream_port = upstream_listener.local_addr().unwrap().port();
+            tokio::spawn(async move {
+                let _ = axum::serve(upstream_listener, upstream).await;
+            });
+            std::env::set_var("CODEXMANAGER_UPSTREAM_BASE_URL", format!("http://127.0.0.1:{upstream_port}"));
+
+            let proxy_port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+            start_api_proxy(Some(proxy_port)).await.unwrap();
+            let client = reqwest::Client::new();
+            let url = format!("http://127.0.0.1:{proxy_port}/v1/responses/resp_1");
+            let mut resp = None;
+            for _ in 0..50 {
+                if let Ok(r) = client.head(&url).send().await {
+                    resp = Some(r);
+                    break;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            }
+            let resp = resp.expect("proxy never answered");
+            assert_eq!(resp.status(), reqwest::StatusCode::OK);
+            assert_eq!(resp.headers().get("x-upstream").and_then(|v| v.to_str().ok()), Some("mock"));
+            let request_id: i64 = resp
+                .headers()
+                .get("x-codex-manager-request-id")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse().ok())
+                .unwrap();
+            assert!(resp.bytes().await.unwrap().is_empty());
+            assert_eq!(seen_method.lock().unwrap().as_deref(), Some("HEAD"));
+
+            let row = get_proxy_log_by_request_id(request_id).unwrap();
+            assert_eq!(row.method, "HEAD");
+            assert_eq!(row.status, 200);
+            assert_eq!(row.error, None);
+            assert_eq!(row.response_body, None);
+
+            stop_api_proxy().unwrap();
+            std::env::remove_var("CODEXMANAGER_UPSTREAM_BASE_URL");
+        });
+
+        let _ = fs::remove_dir_all(home);
+    }
+
+    #[test]
+    fn set_accounts_meta_rejects_bad_base_url_and_proxy() {
+        let _guard = lock_globals();
+        let home = temp_home("set-meta");
+        for (field, value) in [("base_url", "ftp://example.com"), ("base_url", "not a url"), ("outbound_proxy", "::bad::")] {
+            let value = serde_json::json!({ "a": { "added_at": 1, field: value } });
+            assert!(set_accounts_meta(value).is_err(), "{field}");
+            assert!(!meta_file().exists(), "{field}");
+        }
+
+        let value = serde_json::json!({ "a": { "added_at": 1, "base_url": " https://example.com/v1/ ", "outbound_proxy": "  " } });
+        assert!(set_accounts_meta(value).unwrap());
+        let meta = read_meta();
+        assert_eq!(meta["a"].base_url.as_deref(), Some("https://example.com/v1"));
+        assert_eq!(meta["a"].outbound_proxy, None);
+
+        let _ = fs::remove_dir_all(home);
+    }
+
+    #[test]
+    fn idempotency_key_covers_client_and_body() {
+        let key = idempotency_cache_key("POST", "/v1/responses", "c1", "k", b"{\"a\":1}");
+        assert_eq!(key, idempotency_cache_key("POST", "/v1/responses", "c1", "k", b"{\"a\":1}"));
+        assert_ne!(key, idempotency_cache_key("POST", "/v1/responses", "c2", "k", b"{\"a\":1}"));
+        assert_ne!(key, idempotency_cache_key("POST", "/v1/responses", "c1", "k", b"{\"a\":2}"));
+        assert_ne!(key, idempotency_cache_key("POST", "/v1/responses", "c1", "k2", b"{\"a\":1}"));
+    }
+
+    #[test]
+    fn account_base_url_may_not_point_at_the_running_proxy() {
+        let _guard = lock_globals();
+        let previous = PROXY_PORT.lock().unwrap().replace(8520);
+        assert!(normalize_account_base_url(Some("http://127.0.0.1:8520/v1".to_string())).is_err());
+        assert!(normalize_account_base_url(Some("http://localhost:8520".to_string())).is_err());
+        assert_eq!(
+            normalize_account_base_url(Some("http://127.0.0.1:9000/v1/".to_string())).unwrap().as_deref(),
+            Some("http://127.0.0.1:9000/v1")
+        );
+        *PROXY_PORT.lock().unwrap() = previous;
+    }
+
+    #[test]
+    fn default_model_prefers_url_and_header_and_skips_reads() {
+        let cfg = ProxyConfig { default_model: Some(" gpt-default ".to_string()), ..ProxyConfig::default() };
+        let none = axum::http::HeaderMap::new();
+        let post = reqwest::Method::POST;
+        let model = |method: &reqwest::Method, path: &str, headers: &axum::http::HeaderMap| {
+            default_request_model(&cfg, method, path, headers)
+        };
+
+        assert_eq!(model(&post, "/v1/responses", &none).as_deref(), Some("gpt-default"));
+        assert_eq!(model(&post, "/v1/responses?model=gpt-q", &none).as_deref(), Some("gpt-q"));
+        assert_eq!(model(&post, "/openai/deployments/gpt-d/chat/completions", &none).as_deref(), Some("gpt-d"));
+        let mut headers = axum::http::HeaderMap::new();
+        headers.insert("x-model", axum::http::HeaderValue::from_static("gpt-h"));
+        assert_eq!(model(&post, "/v1/responses", &headers).as_deref(), Some("gpt-h"));
+
+        assert_eq!(model(&reqwest::Method::GET, "/v1/responses/resp_1", &none), None);
+        assert_eq!(model(&reqwest::Method::HEAD, "/v1/responses", &none), None);
+        assert_eq!(model(&post, "/v1/models", &none), None);
+        assert_eq!(model(&reqwest::Method::GET, "/v1/models/gpt-m", &none).as_deref(), Some("gpt-m"));
+    }
+
+    #[test]
+    fn truncate_body_reports_truncation_without_sniffing_the_text() {
+        let cfg = ProxyConfig::default();
+        // A binary body exactly at the cap is stored whole
+        let (text, truncated) = truncate_body(&cfg, &vec![0xffu8; MAX_LOG_BODY_BYTES]);
+        assert!(text.starts_with(LOG_BODY_BASE64_PREFIX));
+        assert!(!truncated);
+        assert!(truncate_body(&cfg, &vec![0xffu8; MAX_LOG_BODY_BYTES + 1]).1);
+
+        // Text that merely ends like the truncation note isn't flagged
+        let lookalike = format!("hello{LOG_BODY_TRUNCATED_NOTE}12 bytes");
+        assert_eq!(truncate_body(&cfg, lookalike.as_bytes()), (lookalike.clone(), false));
+        let (text, truncated) = truncate_body(&cfg, &vec![b'a'; MAX_LOG_BODY_BYTES + 10]);
+        assert!(truncated);
+        assert!(text.ends_with("10 bytes"));
+
+        assert_eq!(log_body_field(&cfg, b""), (None, false));
+    }
+
+    #[test]
+    fn pretty_log_bodies_skips_bodies_that_are_not_stored_or_too_large() {
+        let cfg = ProxyConfig { pretty_log_bodies: true, ..ProxyConfig::default() };
+        let body = br#"{"model":"gpt-5"}"#;
+        assert!(truncate_body(&cfg, body).0.contains('\n'));
+
+        let not_logged = ProxyConfig { log_bodies: "none".to_string(), ..cfg.clone() };
+        assert_eq!(truncate_body(&not_logged, body).0, r#"{"model":"gpt-5"}"#);
+
+        // Over the cap the compact text is cut as-is
+        let large = serde_json::to_vec(&serde_json::json!({ "input": "a".repeat(MAX_LOG_BODY_BYTES) })).unwrap();
+        let (text, truncated) = truncate_body(&cfg, &large);
+        assert!(truncated);
+        assert!(text.starts_with(r#"{"input":"aaa"#));
+    }
+
+    #[test]
+    fn strict_inbound_session_id_covers_header_combinations() {
+        // Both present and equal: the client's session id is kept
+        assert_eq!(strict_inbound_session_id(Some("conv-1"), Some("conv-1")), Some("conv-1".to_string()));
+        assert_eq!(strict_inbound_session_id(Some("conv-1"), Some(" conv-1 ")), Some("conv-1".to_string()));
+
+        // Both present but different: derived from the conversation, not the session
+        let derived = strict_inbound_session_id(Some("sess-9"), Some("conv-1")).unwrap();
+        assert_eq!(Some(derived.clone()), derive_session_id(Some("conv-1"), "conversation"));
+        assert_ne!(derived, "sess-9");
+        assert_eq!(strict_inbound_session_id(Some("sess-8"), Some("conv-1")), Some(derived));
+
+        // Either header missing: the caller falls back to its own id
+        assert_eq!(strict_inbound_session_id(Some("sess-9"), None), None);
+        assert_eq!(strict_inbound_session_id(None, Some("conv-1")), None);
+        assert_eq!(strict_inbound_session_id(None, None), None);
+    }
+
+    #[test]
+    fn import_after_oauth_maps_import_outcomes() {
+        let account = CodexAccount {
+            id: String::new(),
+            email: "user@example.com".to_string(),
+            plan: "plus".to_string(),
+            user_id: "user-1".to_string(),
+            expires_at: 0,
+            last_refresh: None,
+            has_refresh_token: true,
+            openai_api_key: None,
+            label: None,
+            added_at: 0,
+            proxy_enabled: true,
+            organization: None,
+            project: None,
+            tags: Vec::new(),
+            token_kind: "jwt".to_string(),
+            outbound_proxy: None,
+            last_used_at: None,
+            client_profile: None,
+            no_refresh: false,
+            base_url: None,
+        };
+
+        // The first attempt fails, the retry imports
+        let mut calls = Vec::new();
+        let result = import_after_oauth_with(&account, Some("work".to_string()), |label| {
+            calls.push(label);
+            if calls.len() == 1 {
+                Err("busy".to_string())
+            } else {
+                Ok(serde_json::json!({ "id": "acct-1" }))
+            }
+        });
+        assert_eq!(calls, vec![Some("work".to_string()), Some("work".to_string())]);
+        assert_eq!(result["success"], true);
+        assert_eq!(result["imported"], true);
+        assert_eq!(result["id"], "acct-1");
+        assert_eq!(result["email"], "user@example.com");
+        assert_eq!(result["plan"], "plus");
+        assert!(result.get("import_error").is_none());
+
+        // Both attempts fail: still a success, with the last error surfaced
+        let mut attempts = 0;
+        let result = import_after_oauth_with(&account, None, |_| {
+            attempts += 1;
+            Err(format!("attempt {attempts} failed"))
+        });
+        assert_eq!(attempts, 2);
+        assert_eq!(result["success"], true);
+        assert_eq!(result["imported"], false);
+        assert!(result["id"].is_null());
+        assert_eq!(result["import_error"], "attempt 2 failed");
+        assert_eq!(result["email"], "user@example.com");
+    }
+
+    #[test]
+    fn auto_reload_keeps_the_tag_filter() {
+        let _guard = lock_globals();
+        let home = temp_home("reload-tag");
+        let auth = serde_json::json!({ "tokens": { "access_token": "token" } });
+        let mut meta = HashMap::new();
+        for id in ["a", "b"] {
+            fs::create_dir_all(accounts_dir().join(id)).unwrap();
+            fs::write(accounts_dir().join(id).join("auth.json"), auth.to_string()).unwrap();
+            meta.insert(id.to_string(), MetaEntry::new(0));
+        }
+        meta.get_mut("a").unwrap().tags = vec!["work".to_string()];
+        write_meta(&meta);
+        *PROXY_STATE.lock().unwrap() = Some(Arc::new(test_state(Vec::new())));
+        let state = PROXY_STATE.lock().unwrap().clone().unwrap();
+        let pool_ids = || state.accounts.read().unwrap().iter().map(|a| a.id.clone()).collect::<Vec<_>>();
+
+        reload_proxy_accounts_by_tag(" work ".to_string()).unwrap();
+        assert_eq!(pool_ids(), vec!["a".to_string()]);
+        assert_eq!(reload_pool_preserving_health(&state).unwrap(), 1);
+        assert_eq!(pool_ids(), vec!["a".to_string()]);
+
+        // The tag disappearing leaves the narrowed pool as it was
+        meta.get_mut("a").unwrap().tags.clear();
+        write_meta(&meta);
+        assert!(reload_pool_preserving_health(&state).is_err());
+        assert_eq!(pool_ids(), vec!["a".to_string()]);
+
+        // A full reload drops the filter for later reloads too
+        reload_proxy_accounts().unwrap();
+        assert_eq!(reload_pool_preserving_health(&state).unwrap(), 2);
+
+        *PROXY_STATE.lock().unwrap() = None;
+        let _ = fs::remove_dir_all(home);
+    }
+
+    #[test]
+    fn concurrent_log_writes_are_not_dropped() {
+        let _guard = lock_globals();
+        let home = temp_home("log-concurrency");
+        let first_id: u64 = 841_000;
+        proxy_log_db()
+            .unwrap()
+            .execute("DELETE FROM request_logs WHERE request_id BETWEEN ?1 AND ?2", params![first_id as i64, (first_id + 49) as i64])
+            .unwrap();
+
+        let barrier = Arc::new(std::sync::Barrier::new(50));
+        let writers: Vec<_> = (0..50)
+            .map(|i| {
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    insert_proxy_log(&ProxyLogEntry {
+                        timestamp: "2026-01-01T00:00:00Z".to_string(),
+                        method: "POST".to_string(),
+                        path: "/v1/responses".to_string(),
+                        status: 200,
+                        proxy_account_id: "a".to_string(),
+                        request_id: Some(first_id + i),
+                        ..ProxyLogEntry::default()
+                    })
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap().unwrap();
+        }
+
+        let rows: i64 = proxy_log_db()
+            .unwrap()
+            .query_row(
+                "SELECT COUNT(*) FROM request_logs WHERE request_id BETWEEN ?1 AND ?2",
+                params![first_id as i64, (first_id + 49) as i64],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(rows, 50);
+
+        let _ = fs::remove_dir_all(home);
+    }
+
+    #[test]
+    fn request_ids_continue_after_logged_ones() {
+        let _guard = lock_globals();
+        let home = temp_home("request-ids");
+        let conn = proxy_log_db().unwrap();
+        conn.execute(
+            "INSERT INTO request_logs (timestamp, method, path, status, duration_ms, proxy_account_id, request_id)
+             VALUES ('2026-01-01T00:00:00Z', 'POST', '/v1/responses', 200, 0, 'a', ?1)",
+            params![861_000_000i64],
+        )
+        .unwrap();
+        // What a fresh launch does when it first opens this DB
+        seed_request_ids(&conn);
+        drop(conn);
+
+        let id = next_request_id();
+        assert!(id > 861_000_000);
+        // Synthesized response ids don't consume request ids
+        let _ = synth_response_id("resp");
+        assert_eq!(next_request_id(), id + 1);
+
+        let _ = fs::remove_dir_all(home);
+    }
+
+    #[test]
+    fn restore_checks_the_log_db_first_and_keeps_live_logs_without_overwrite() {
+        use std::io::Read;
+
+        let _guard = lock_globals();
+        let home = temp_home("restore");
+        let count_rows = |request_id: i64| -> i64 {
+            proxy_log_db()
+                .unwrap()
+                .query_row("SELECT COUNT(*) FROM request_logs WHERE request_id = ?1", params![request_id], |row| row.get(0))
+                .unwrap()
+        };
+        let log_row = |request_id: u64| ProxyLogEntry {
+            timestamp: "2026-01-01T00:00:00Z".to_string(),
+            method: "POST".to_string(),
+            path: "/v1/responses".to_string(),
+            status: 200,
+            proxy_account_id: "a".to_string(),
+            request_id: Some(request_id),
+            ..ProxyLogEntry::default()
+        };
+        fs::create_dir_all(accounts_dir().join("a")).unwrap();
+        fs::write(accounts_dir().join("a").join("auth.json"), r#"{"tokens":{"access_token":"t"}}"#).unwrap();
+        insert_proxy_log(&log_row(855_001)).unwrap();
+        let snapshot = snapshot_state(Some(true)).unwrap();
+        fs::remove_dir_all(accounts_dir().join("a")).unwrap();
+
+        // Same archive with a corrupt logs database: rejected before any account is written
+        let mut corrupt = tar::Builder::new(Vec::new());
+        let mut archive = tar::Archive::new(snapshot.as_slice());
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let path = entry.path().unwrap().to_string_lossy().to_string();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).unwrap();
+            if path == "proxy_logs.db" {
+                data = b"not a database".to_vec();
+            }
+            append_snapshot_file(&mut corrupt, &path, &data).unwrap();
+        }
+        assert!(restore_state(corrupt.into_inner().unwrap(), false).is_err());
+        assert!(!accounts_dir().join("a").exists());
+
+        // Without overwrite the live rows stay and the snapshot's are added
+        insert_proxy_log(&log_row(855_002)).unwrap();
+        restore_state(snapshot, false).unwrap();
+        assert!(accounts_dir().join("a").join("auth.json").exists());
+        assert_eq!(count_rows(855_002), 1);
+        assert_eq!(count_rows(855_001), 2);
+
+        let _ = fs::remove_dir_all(home);
+    }
+
+    #[test]
+    fn rate_limit_honours_usage_limit_bodies() {
+        let _guard = lock_globals();
+        let state = Arc::new(test_state(vec![test_account("a"), test_account("b")]));
+        let body = br#"{"error":{"type":"usage_limit_reached","resets_in_seconds":3600}}"#;
+        assert!(record_rate_limit(&state, 0, "a", body));
+        assert!(!record_rate_limit(&state, 1, "b", br#"{"error":{"type":"rate_limit_exceeded"}}"#));
+
+        let accounts = state.accounts.read().unwrap();
+        let AccountHealth::Cooldown(usage_until) = accounts[0].health else {
+            panic!("usage limit should cool the account down");
+        };
+        assert!(usage_until > std::time::Instant::now() + std::time::Duration::from_secs(3500));
+        let AccountHealth::Cooldown(backoff_until) = accounts[1].health else {
+            panic!("plain 429 should back off");
+        };
+        assert!(backoff_until < usage_until);
+    }
+}
@@ -56,8 +56,12 @@ export default function AddAccountDialog({ open, onClose }: Props) {
     try {
       const values = form.getFieldsValue()
       message.loading({ content: '请在浏览器中完成登录...', key: 'oauth' })
-      await oauthLogin(values.label)
-      message.success({ content: 'OAuth 登录并导入成功', key: 'oauth' })
+      const res = await oauthLogin(values.label)
+      if (res.imported) {
+        message.success({ content: 'OAuth 登录并导入成功', key: 'oauth' })
+      } else {
+        message.warning({ content: `登录成功但导入失败，请使用"导入缓存"：${res.import_error ?? ''}`, key: 'oauth' })
+      }
       handleCancel()
     } catch (e) {
       message.error({ content: String(e), key: 'oauth' })
@@ -88,8 +92,12 @@ export default function AddAccountDialog({ open, onClose }: Props) {
     setLoading(true)
     try {
       const values = form.getFieldsValue()
-      await accountService.completeOAuthManual(callbackInput.trim(), values.label)
-      message.success('OAuth 登录并导入成功')
+      const res = await accountService.completeOAuthManual(callbackInput.trim(), values.label)
+      if (res.imported) {
+        message.success('OAuth 登录并导入成功')
+      } else {
+        message.warning(`登录成功但导入失败，请使用"导入缓存"：${res.import_error ?? ''}`)
+      }
       handleCancel()
     } catch (e) {
       message.error(String(e))
@@ -155,6 +155,7 @@ function UsageCell({ usage, loading }: { usage: AccountUsage | undefined; loadin
   const availText =
     availability === 'available' ? '可用' :
     availability === 'unavailable' ? '已耗尽' :
+    availability === 'warning' ? '即将耗尽' :
     availability === 'primary_window_available_only' ? '部分可用' : '未知'
 
   function ProgressRow({
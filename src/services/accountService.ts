@@ -12,7 +12,7 @@ export interface AccountUsage {
   secondary_window_minutes: number | null
   secondary_resets_at: number | null
   // meta
-  availability: 'available' | 'unavailable' | 'primary_window_available_only' | 'unknown'
+  availability: 'available' | 'warning' | 'unavailable' | 'primary_window_available_only' | 'unknown'
   captured_at: number  // unix seconds
 }
 
@@ -166,6 +166,17 @@ export interface AnthropicKeyEntry {
   added_at: number
 }
 
+// imported is false when login succeeded but the account couldn't be imported;
+// it is then active in auth.json and can be added via "导入缓存" (import_current)
+export interface OAuthLoginResult {
+  success: boolean
+  imported: boolean
+  email: string
+  plan: string
+  id: string | null
+  import_error?: string
+}
+
 export const accountService = {
   list: () => invoke<CodexAccount[]>('list_accounts'),
   current: () => invoke<CodexAccount | null>('get_current_account'),
@@ -179,11 +190,11 @@ export const accountService = {
   getConfig: () => invoke<{ raw: string }>('get_config'),
 
   oauthLogin: (label?: string) =>
-    invoke<{ success: boolean; email: string; plan: string; id: string }>('oauth_login', { label: label ?? null }),
+    invoke<OAuthLoginResult>('oauth_login', { label: label ?? null }),
   getOAuthUrl: () =>
     invoke<{ auth_url: string }>('get_oauth_url'),
   completeOAuthManual: (callbackUrl: string, label?: string) =>
-    invoke<{ success: boolean; email: string; plan: string; id: string }>('complete_oauth_manual', { callbackUrl, label: label ?? null }),
+    invoke<OAuthLoginResult>('complete_oauth_manual', { callbackUrl, label: label ?? null }),
   refreshToken: (id: string) =>
     invoke<{ success: boolean; email: string; expires_at: number }>('refresh_account_token', { id }),
   getUsage: (id: string) => invoke<AccountUsage>('get_account_usage', { id }),
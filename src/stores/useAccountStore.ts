@@ -1,6 +1,6 @@
 import { create } from 'zustand'
 import type { CodexAccount } from '../types/account'
-import { accountService, type AccountUsage, type ProxyStatus } from '../services/accountService'
+import { accountService, type AccountUsage, type OAuthLoginResult, type ProxyStatus } from '../services/accountService'
 
 interface AccountState {
   accounts: CodexAccount[]
@@ -20,7 +20,7 @@ interface AccountState {
   importCurrent: (label?: string) => Promise<void>
   refresh: () => Promise<void>
 
-  oauthLogin: (label?: string) => Promise<void>
+  oauthLogin: (label?: string) => Promise<OAuthLoginResult>
   refreshAccountToken: (id: string) => Promise<void>
   fetchUsage: (id: string) => Promise<void>
   fetchProxyStatus: () => Promise<void>
@@ -103,10 +103,11 @@ export const useAccountStore = create<AccountState>((set, get) => ({
   },
 
   oauthLogin: async (label) => {
-    await accountService.oauthLogin(label)
+    const result = await accountService.oauthLogin(label)
     await get().fetchAccounts()
     await get().fetchCurrent()
     await get().fetchProxyStatus()
+    return result
   },
 
   refreshAccountToken: async (id) => {